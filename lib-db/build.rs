@@ -0,0 +1,24 @@
+//! List the versions of the embedded migrations, the same way diesel names them in
+//! `__diesel_schema_migrations`.
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=../migrations");
+    let mut versions = Vec::new();
+    for entry in fs::read_dir("../migrations").expect("migrations folder") {
+        let name = entry.expect("migration").file_name();
+        let name = name.to_string_lossy();
+        // Diesel skips the hidden files, the version is the part before the first underscore.
+        if name.starts_with('.') {
+            continue;
+        }
+        let version = name.split('_').next().unwrap_or_default().replace('-', "");
+        versions.push(version);
+    }
+    versions.sort();
+    let out = Path::new(&env::var("OUT_DIR").expect("out dir")).join("migration_versions.rs");
+    let code = format!("const MIGRATION_VERSIONS: &[&str] = &{:?};\n", versions);
+    fs::write(out, code).expect("write the migration versions");
+}
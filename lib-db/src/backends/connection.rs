@@ -0,0 +1,82 @@
+//! The sqlite connection of a store, with the cipher of the encrypted columns of the store.
+//!
+//! The cipher travels with the connection so that the stores of different tenants can be used
+//! from the same threads.
+use crate::crypto::FieldCipher;
+use diesel::connection::{AnsiTransactionManager, SimpleConnection};
+use diesel::deserialize::{Queryable, QueryableByName};
+use diesel::query_builder::{AsQuery, QueryFragment, QueryId};
+use diesel::sql_types::HasSqlType;
+use diesel::sqlite::{Sqlite, SqliteConnection};
+use diesel::{ConnectionResult, QueryResult};
+use std::sync::Arc;
+
+pub struct Connection {
+    inner: SqliteConnection,
+    cipher: Option<Arc<FieldCipher>>,
+}
+
+impl Connection {
+    /// Open the database, the encrypted columns use the cipher.
+    pub(crate) fn open(url: &str, cipher: Option<Arc<FieldCipher>>) -> ConnectionResult<Self> {
+        use diesel::Connection as DieselConnection;
+
+        Ok(Connection {
+            inner: SqliteConnection::establish(url)?,
+            cipher,
+        })
+    }
+
+    /// The cipher of the encrypted columns, None when they are not encrypted.
+    pub(crate) fn cipher(&self) -> Option<&FieldCipher> {
+        self.cipher.as_ref().map(|c| &**c)
+    }
+}
+
+impl SimpleConnection for Connection {
+    fn batch_execute(&self, query: &str) -> QueryResult<()> {
+        self.inner.batch_execute(query)
+    }
+}
+
+impl diesel::Connection for Connection {
+    type Backend = Sqlite;
+    type TransactionManager = AnsiTransactionManager;
+
+    fn establish(database_url: &str) -> ConnectionResult<Self> {
+        Self::open(database_url, None)
+    }
+
+    fn execute(&self, query: &str) -> QueryResult<usize> {
+        self.inner.execute(query)
+    }
+
+    fn query_by_index<T, U>(&self, source: T) -> QueryResult<Vec<U>>
+    where
+        T: AsQuery,
+        T::Query: QueryFragment<Sqlite> + QueryId,
+        Sqlite: HasSqlType<T::SqlType>,
+        U: Queryable<T::SqlType, Sqlite>,
+    {
+        self.inner.query_by_index(source)
+    }
+
+    fn query_by_name<T, U>(&self, source: &T) -> QueryResult<Vec<U>>
+    where
+        T: QueryFragment<Sqlite> + QueryId,
+        U: QueryableByName<Sqlite>,
+    {
+        self.inner.query_by_name(source)
+    }
+
+    fn execute_returning_count<T>(&self, source: &T) -> QueryResult<usize>
+    where
+        T: QueryFragment<Sqlite> + QueryId,
+    {
+        self.inner.execute_returning_count(source)
+    }
+
+    fn transaction_manager(&self) -> &AnsiTransactionManager {
+        self.inner.transaction_manager()
+    }
+}
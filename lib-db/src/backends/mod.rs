@@ -1,8 +1,11 @@
-//! Contains the auto-generated diesel schema. To update
+//! Contains the sqlite connection and the auto-generated diesel schema. To update the schema
 //!
 //!   `diesel setup --database-url $HOME/.weaver/history.sqlite3`
 //    `diesel migration run --database-url $HOME/.weaver/history.sqlite3`
 //    `diesel print-schema --database-url $HOME/.weaver/history.sqlite3 > lib-db/src/backends/schema.rs`
 //
 
+mod connection;
 pub mod schema;
+
+pub use self::connection::Connection;
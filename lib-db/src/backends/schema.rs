@@ -1,3 +1,10 @@
+table! {
+    action_tags (action_id, tag_id) {
+        action_id -> Integer,
+        tag_id -> Integer,
+    }
+}
+
 table! {
     actions2 (id) {
         id -> Nullable<Integer>,
@@ -9,6 +16,24 @@ table! {
         annotation -> Nullable<Text>,
         host_id -> Nullable<Integer>,
         status -> Nullable<Integer>,
+        parent_id -> Nullable<Text>,
+    }
+}
+
+table! {
+    api_tokens (id) {
+        id -> Nullable<Integer>,
+        name -> Text,
+        token_hash -> Text,
+        created -> Text,
+    }
+}
+
+table! {
+    blind_words (kind, row_id, word_bidx) {
+        kind -> Text,
+        row_id -> Integer,
+        word_bidx -> Text,
     }
 }
 
@@ -18,6 +43,7 @@ table! {
         kind -> Text,
         command -> Text,
         page_id -> Nullable<Integer>,
+        command_bidx -> Nullable<Text>,
     }
 }
 
@@ -28,6 +54,15 @@ table! {
     }
 }
 
+table! {
+    focus_events (id) {
+        id -> Nullable<Integer>,
+        action_id -> Integer,
+        kind -> Text,
+        happened -> Text,
+    }
+}
+
 table! {
     hosts (id) {
         id -> Nullable<Integer>,
@@ -42,11 +77,73 @@ table! {
     }
 }
 
+table! {
+    navigations (action_id) {
+        action_id -> Integer,
+        tab_id -> Nullable<Integer>,
+        referrer_action_id -> Nullable<Integer>,
+        transition -> Nullable<Text>,
+    }
+}
+
 table! {
     pages (id) {
         id -> Nullable<Integer>,
         normalized_url -> Text,
         title -> Nullable<Text>,
+        normalized_url_bidx -> Nullable<Text>,
+    }
+}
+
+table! {
+    page_copies (page_id) {
+        page_id -> Nullable<Integer>,
+        repo_id -> Text,
+        sent -> Bool,
+    }
+}
+
+table! {
+    page_tags (page_id, tag_id) {
+        page_id -> Integer,
+        tag_id -> Integer,
+    }
+}
+
+table! {
+    reading_list_pages (list_id, page_id) {
+        list_id -> Integer,
+        page_id -> Integer,
+        added -> Text,
+    }
+}
+
+table! {
+    reading_lists (id) {
+        id -> Nullable<Integer>,
+        name -> Text,
+        created -> Text,
+    }
+}
+
+table! {
+    settings (name) {
+        name -> Text,
+        value -> Text,
+    }
+}
+
+table! {
+    stars (page_id) {
+        page_id -> Integer,
+        created -> Text,
+    }
+}
+
+table! {
+    tags (id) {
+        id -> Nullable<Integer>,
+        name -> Text,
     }
 }
 
@@ -57,21 +154,43 @@ table! {
         url_expr -> Text,
         title_match -> Nullable<Text>,
         body_match -> Nullable<Text>,
+        pattern_kind -> Text,
     }
 }
 
+joinable!(action_tags -> tags (tag_id));
 joinable!(actions2 -> commands (command_id));
 joinable!(actions2 -> epics (epic_id));
 joinable!(actions2 -> hosts (host_id));
 joinable!(actions2 -> locations (location_id));
 joinable!(commands -> pages (page_id));
+joinable!(focus_events -> actions2 (action_id));
+joinable!(navigations -> actions2 (action_id));
+joinable!(page_copies -> pages (page_id));
+joinable!(page_tags -> pages (page_id));
+joinable!(page_tags -> tags (tag_id));
+joinable!(reading_list_pages -> pages (page_id));
+joinable!(reading_list_pages -> reading_lists (list_id));
+joinable!(stars -> pages (page_id));
 
 allow_tables_to_appear_in_same_query!(
+    action_tags,
     actions2,
+    api_tokens,
+    blind_words,
     commands,
     epics,
+    focus_events,
     hosts,
     locations,
+    navigations,
+    page_copies,
+    page_tags,
     pages,
+    reading_list_pages,
+    reading_lists,
+    settings,
+    stars,
+    tags,
     url_restrictions,
 );
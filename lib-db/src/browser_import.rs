@@ -0,0 +1,512 @@
+//! Import the visits from the history databases of Chrome and Firefox.
+//!
+//! The history databases are copied and the copies opened read-only, each visit becomes a url
+//! action with its original time and transition type, the same as the ones logged by the Chrome
+//! extension.
+#![allow(proc_macro_derive_resolution_fallback)]
+use crate::db::{actions2, commands, pages, sessions};
+use crate::store_policies::Restrictions;
+use crate::Connection;
+use chrono::{DateTime, TimeZone, Utc};
+use diesel;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Integer, Nullable, Text};
+use lib_error::*;
+use lib_goo::entities::NewAction;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Visits closer than this to an existing action for the same url are already present,
+/// the extension logs the time the visit reached the server.
+const SAME_VISIT_SECS: i64 = 5;
+
+/// Microseconds between 1601-01-01, the Chrome epoch, and 1970-01-01.
+const CHROME_EPOCH_OFFSET: i64 = 11_644_473_600_000_000;
+
+/// The supported browsers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Browser {
+    /// The `History` file in the Chrome profile folder.
+    Chrome,
+    /// The `places.sqlite` file in the Firefox profile folder.
+    Firefox,
+}
+
+/// One visit read from the browser history.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Visit {
+    pub url: String,
+    pub title: Option<String>,
+    /// RFC3339 time of the visit.
+    pub executed: String,
+    /// Transition type, with the names used by the Chrome extension.
+    pub transition: String,
+}
+
+/// Counts reported after an import.
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportReport {
+    pub imported: usize,
+    /// Visits already present in the store.
+    pub existing: usize,
+    /// Visits of urls which should not be logged.
+    pub not_logged: usize,
+    /// Visits of other schemes or of frames.
+    pub ignored: usize,
+    /// Page entries created or found for the imported urls.
+    pub pages: usize,
+}
+
+#[derive(QueryableByName)]
+struct Row {
+    #[sql_type = "BigInt"]
+    visit_id: i64,
+    /// The visit this one comes from, only read for Firefox.
+    #[sql_type = "Nullable<BigInt>"]
+    from_visit: Option<i64>,
+    #[sql_type = "Text"]
+    url: String,
+    #[sql_type = "Nullable<Text>"]
+    title: Option<String>,
+    #[sql_type = "Nullable<BigInt>"]
+    visit_time: Option<i64>,
+    #[sql_type = "Integer"]
+    transition: i32,
+}
+
+/// Chrome core transition, in the low byte of `visits.transition`.
+fn chrome_transition(transition: i32) -> Option<&'static str> {
+    match transition & 0xff {
+        0 => Some("link"),
+        1 => Some("typed"),
+        2 => Some("auto_bookmark"),
+        5 => Some("generated"),
+        6 => Some("auto_toplevel"),
+        7 => Some("form_submit"),
+        8 => Some("reload"),
+        9 => Some("keyword"),
+        10 => Some("keyword_generated"),
+        // Sub frames are not pages.
+        _ => None,
+    }
+}
+
+/// Firefox permanent and temporary redirects, `moz_historyvisits.visit_type` of the target.
+const FIREFOX_REDIRECTS: [i32; 2] = [5, 6];
+
+/// Firefox `moz_historyvisits.visit_type`, mapped to the Chrome names.
+fn firefox_transition(visit_type: i32) -> Option<&'static str> {
+    match visit_type {
+        1 => Some("link"),
+        2 => Some("typed"),
+        3 => Some("auto_bookmark"),
+        9 => Some("reload"),
+        // Embedded resources, downloads and frames are not pages, the redirects are collapsed.
+        _ => None,
+    }
+}
+
+// Collapse the Firefox redirects: the target visit takes the type of the visit the redirect
+// comes from, which is dropped. The rows are ordered by time, a chain of redirects ends up with
+// the type of its first visit.
+fn collapse_redirects(rows: &mut Vec<Row>) {
+    let mut types: HashMap<i64, i32> = rows.iter().map(|r| (r.visit_id, r.transition)).collect();
+    let mut sources = HashSet::new();
+    for row in rows.iter_mut() {
+        if !FIREFOX_REDIRECTS.contains(&row.transition) {
+            continue;
+        }
+        let source = row
+            .from_visit
+            .and_then(|id| types.get(&id).map(|t| (id, *t)));
+        row.transition = match source {
+            Some((id, source_type)) => {
+                sources.insert(id);
+                source_type
+            }
+            // The source was removed from the history, the redirect was most likely a link.
+            None => 1,
+        };
+        types.insert(row.visit_id, row.transition);
+    }
+    rows.retain(|r| !sources.contains(&r.visit_id));
+}
+
+fn to_rfc3339(micros: i64) -> String {
+    let time: DateTime<Utc> =
+        Utc.timestamp(micros / 1_000_000, ((micros % 1_000_000) * 1000) as u32);
+    time.to_rfc3339()
+}
+
+/// A copy of a browser history database, removed when dropped.
+///
+/// The browser may be running: opening its files, even read-only, can create or update the
+/// `-wal` and `-shm` files next to them. The database and its write-ahead log are copied to a
+/// temporary folder and only the copy is opened.
+pub struct HistoryCopy {
+    connection: Option<Connection>,
+    folder: PathBuf,
+}
+
+impl Deref for HistoryCopy {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.connection.as_ref().expect("history connection")
+    }
+}
+
+impl Drop for HistoryCopy {
+    fn drop(&mut self) {
+        // Close the database before removing its files.
+        self.connection.take();
+        if let Err(e) = fs::remove_dir_all(&self.folder) {
+            ::log::warn!("cannot remove the history copy {:?}: {}", self.folder, e);
+        }
+    }
+}
+
+// Create the folder readable only by the current user, it fails if the folder exists.
+fn create_private_dir(folder: &Path) -> Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(folder)?;
+    Ok(())
+}
+
+// Copy the file with the suffix, when present, to the folder.
+fn copy_with_suffix(path: &Path, suffix: &str, folder: &Path) -> Result<()> {
+    let mut source = path.as_os_str().to_owned();
+    source.push(suffix);
+    let source = PathBuf::from(source);
+    if source.is_file() {
+        fs::copy(&source, folder.join(format!("History{}", suffix)))
+            .map_err(|e| WeaverError::from(format!("cannot copy {:?}: {}", source, e)))?;
+    }
+    Ok(())
+}
+
+/// Open a copy of a browser history database, the original files are only read.
+pub fn open(path: &Path) -> Result<HistoryCopy> {
+    use diesel::Connection as DieselConnection;
+
+    if !path.is_file() {
+        return Err(WeaverError::from(format!(
+            "no history database at {:?}",
+            path
+        )));
+    }
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    let folder = env::temp_dir().join(format!("weaver-history-{}-{}", process::id(), stamp));
+    create_private_dir(&folder)?;
+    let mut copy = HistoryCopy {
+        connection: None,
+        folder,
+    };
+    copy_with_suffix(path, "", &copy.folder)?;
+    copy_with_suffix(path, "-wal", &copy.folder)?;
+    let copied = copy.folder.join("History");
+    let connection = Connection::establish(&copied.to_string_lossy())
+        .map_err(|e| WeaverError::from(format!("cannot open {:?}: {}", path, e)))?;
+    diesel::sql_query("PRAGMA query_only = 1").execute(&connection)?;
+    copy.connection = Some(connection);
+    Ok(copy)
+}
+
+/// Read the visits, oldest first, the visits which are not pages are counted in `ignored`.
+pub fn read_visits(
+    history: &Connection,
+    browser: Browser,
+    ignored: &mut usize,
+) -> Result<Vec<Visit>> {
+    let sql = match browser {
+        Browser::Chrome => {
+            "SELECT v.id AS visit_id, NULL AS from_visit, u.url AS url, u.title AS title, \
+             v.visit_time AS visit_time, v.transition AS transition \
+             FROM visits v JOIN urls u ON v.url = u.id ORDER BY v.visit_time"
+        }
+        Browser::Firefox => {
+            "SELECT v.id AS visit_id, v.from_visit AS from_visit, p.url AS url, \
+             p.title AS title, v.visit_date AS visit_time, v.visit_type AS transition \
+             FROM moz_historyvisits v JOIN moz_places p ON v.place_id = p.id \
+             ORDER BY v.visit_date"
+        }
+    };
+    let mut rows = diesel::sql_query(sql).load::<Row>(history).map_err(|e| {
+        WeaverError::from(format!(
+            "cannot read the {:?} history, close the browser if the database is locked: {}",
+            browser, e
+        ))
+    })?;
+    if browser == Browser::Firefox {
+        let before = rows.len();
+        collapse_redirects(&mut rows);
+        *ignored += before - rows.len();
+    }
+    let mut visits = Vec::with_capacity(rows.len());
+    for row in rows {
+        let transition = match browser {
+            Browser::Chrome => chrome_transition(row.transition),
+            Browser::Firefox => firefox_transition(row.transition),
+        };
+        let executed = match (browser, row.visit_time) {
+            (Browser::Chrome, Some(t)) if t > CHROME_EPOCH_OFFSET => {
+                Some(to_rfc3339(t - CHROME_EPOCH_OFFSET))
+            }
+            (Browser::Firefox, Some(t)) if t > 0 => Some(to_rfc3339(t)),
+            _ => None,
+        };
+        let is_web = row.url.starts_with("http://") || row.url.starts_with("https://");
+        match (transition, executed) {
+            (Some(transition), Some(executed)) if is_web => visits.push(Visit {
+                url: row.url,
+                title: row.title.filter(|t| !t.is_empty()),
+                executed,
+                transition: transition.into(),
+            }),
+            _ => *ignored += 1,
+        }
+    }
+    Ok(visits)
+}
+
+fn parse_time(executed: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(executed)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+// The times of the existing actions for the url.
+fn existing_times(connection: &Connection, url: &str) -> Result<Vec<DateTime<Utc>>> {
+    match commands::fetch_id(connection, "url", url)? {
+        Some(command_id) => Ok(actions2::executed_for_command(connection, command_id)?
+            .iter()
+            .filter_map(|e| parse_time(e))
+            .collect()),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Save the visits as url actions, skipping the ones already present.
+pub fn import(
+    connection: &Connection,
+    visits: &[Visit],
+    restrictions: &Restrictions,
+) -> Result<ImportReport> {
+    use diesel::Connection as DieselConnection;
+
+    let mut report = ImportReport::default();
+    let mut by_url: BTreeMap<&str, Vec<&Visit>> = BTreeMap::new();
+    for visit in visits {
+        by_url
+            .entry(&visit.url)
+            .or_insert_with(Vec::new)
+            .push(visit);
+    }
+    connection.transaction::<_, WeaverError, _>(|| {
+        for (url, visits) in by_url {
+            if !restrictions.should_log_url(url) {
+                report.not_logged += visits.len();
+                continue;
+            }
+            let mut known = existing_times(connection, url)?;
+            // The most recent title known by the browser.
+            let title = visits.iter().rev().find_map(|v| v.title.as_ref());
+            let mut imported = false;
+            for visit in &visits {
+                let time = match parse_time(&visit.executed) {
+                    Some(t) => t,
+                    None => {
+                        report.ignored += 1;
+                        continue;
+                    }
+                };
+                if known
+                    .iter()
+                    .any(|k| (*k - time).num_seconds().abs() <= SAME_VISIT_SECS)
+                {
+                    report.existing += 1;
+                    continue;
+                }
+                // The page is created first, the command is linked to it when inserted.
+                if !imported && restrictions.should_index_url(url) {
+                    pages::fetch_or_create_id(connection, url, title.map(|t| t.as_str()))?;
+                    report.pages += 1;
+                }
+                imported = true;
+                let mut action = NewAction::build_from_url(url, None)?;
+                action.executed = visit.executed.clone();
+                sessions::insert_visit(connection, &action, Some(&visit.transition), None, None)?;
+                known.push(time);
+                report.imported += 1;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store_policies::DocumentMatcher;
+    use crate::test_helpers::SqlStoreInMemory;
+    use crate::SqlProvider;
+    use diesel::Connection as DieselConnection;
+    use regex::Regex;
+
+    // A Chrome history with a typed visit, a reload, a sub frame and a non web url.
+    fn chrome_history() -> Connection {
+        let history = Connection::establish(":memory:").expect("history");
+        for sql in &[
+            "CREATE TABLE urls (id INTEGER PRIMARY KEY, url TEXT NOT NULL, title TEXT)",
+            "CREATE TABLE visits (id INTEGER PRIMARY KEY, url INTEGER NOT NULL, \
+             visit_time INTEGER NOT NULL, transition INTEGER NOT NULL)",
+            "INSERT INTO urls VALUES (1, 'https://example.com/', 'Example'), \
+             (2, 'https://ads.example.com/frame', ''), (3, 'chrome://settings', 'Settings'), \
+             (4, 'https://private.example.com/', 'Private')",
+            // 2019-01-01T00:00:00Z and ten minutes later, the high bits are qualifiers.
+            "INSERT INTO visits VALUES (1, 1, 13190774400000000, 805306369), \
+             (2, 1, 13190775000000000, 8), (3, 2, 13190774400000000, 3), \
+             (4, 3, 13190774400000000, 1), (5, 4, 13190774400000000, 0)",
+        ] {
+            diesel::sql_query(*sql).execute(&history).expect(sql);
+        }
+        history
+    }
+
+    #[test]
+    fn test_read_chrome_visits() {
+        let mut ignored = 0;
+        let visits = read_visits(&chrome_history(), Browser::Chrome, &mut ignored).expect("read");
+        assert_eq!(ignored, 2);
+        assert_eq!(visits.len(), 3);
+        let typed = visits
+            .iter()
+            .find(|v| v.transition == "typed")
+            .expect("typed");
+        assert_eq!(typed.url, "https://example.com/");
+        assert_eq!(typed.title, Some("Example".into()));
+        assert_eq!(typed.executed, "2019-01-01T00:00:00+00:00");
+        let reload = visits
+            .iter()
+            .find(|v| v.transition == "reload")
+            .expect("reload");
+        assert_eq!(reload.executed, "2019-01-01T00:10:00+00:00");
+    }
+
+    #[test]
+    fn test_read_firefox_visits() {
+        let history = Connection::establish(":memory:").expect("history");
+        for sql in &[
+            "CREATE TABLE moz_places (id INTEGER PRIMARY KEY, url TEXT, title TEXT)",
+            "CREATE TABLE moz_historyvisits (id INTEGER PRIMARY KEY, from_visit INTEGER, \
+             place_id INTEGER, visit_date INTEGER, visit_type INTEGER)",
+            "INSERT INTO moz_places VALUES (1, 'http://short.example/a', NULL), \
+             (2, 'https://long.example/a', 'Long'), (3, 'https://files.example/a.zip', NULL), \
+             (4, 'https://example.com/', 'Example')",
+            // 2019-01-01T00:00:00Z, a link redirected twice, a download and a typed visit.
+            "INSERT INTO moz_historyvisits VALUES (1, 0, 1, 1546300800000000, 1), \
+             (2, 1, 2, 1546300800100000, 5), (3, 2, 2, 1546300800200000, 6), \
+             (4, 0, 3, 1546300900000000, 7), (5, 0, 4, 1546301000000000, 2)",
+        ] {
+            diesel::sql_query(*sql).execute(&history).expect(sql);
+        }
+
+        let mut ignored = 0;
+        let visits = read_visits(&history, Browser::Firefox, &mut ignored).expect("read");
+        assert_eq!(ignored, 3);
+        let found = visits
+            .iter()
+            .map(|v| (v.url.as_str(), v.transition.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                ("https://long.example/a", "link"),
+                ("https://example.com/", "typed")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_open_copy() {
+        let folder = env::temp_dir().join(format!("weaver-browser-{}", process::id()));
+        fs::create_dir_all(&folder).expect("create folder");
+        let path = folder.join("History");
+        {
+            let history = Connection::establish(&path.to_string_lossy()).expect("history");
+            diesel::sql_query("CREATE TABLE urls (id INTEGER PRIMARY KEY, url TEXT NOT NULL)")
+                .execute(&history)
+                .expect("create");
+        }
+
+        let copy = open(&path).expect("open");
+        let copied = copy.folder.clone();
+        assert!(copied.join("History").is_file());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&copied).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+        diesel::sql_query("SELECT * FROM urls")
+            .execute(&*copy)
+            .expect("select");
+        // The copy is read-only.
+        assert!(diesel::sql_query("DELETE FROM urls").execute(&*copy).is_err());
+        drop(copy);
+        assert!(!copied.exists());
+
+        let names = fs::read_dir(&folder)
+            .expect("list")
+            .map(|e| e.expect("entry").file_name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![std::ffi::OsString::from("History")]);
+        fs::remove_dir_all(&folder).expect("cleanup");
+    }
+
+    #[test]
+    fn test_import_skips_existing_and_not_logged() {
+        let mut ignored = 0;
+        let visits = read_visits(&chrome_history(), Browser::Chrome, &mut ignored).expect("read");
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        let restrictions = Restrictions {
+            do_not_log: vec![DocumentMatcher {
+                url: Some(Regex::new("private").unwrap()),
+                title: None,
+                body: None,
+            }],
+            ..Restrictions::default()
+        };
+
+        let report = import(&connection, &visits, &restrictions).expect("import");
+        assert_eq!(report.imported, 2);
+        assert_eq!(report.not_logged, 1);
+        assert_eq!(report.pages, 1);
+        assert_eq!(actions2::count(&connection).expect("count"), 2);
+        assert!(pages::fetch_id(&connection, "https://example.com/")
+            .expect("page")
+            .is_some());
+
+        // Importing the same history again adds nothing.
+        let report = import(&connection, &visits, &restrictions).expect("import");
+        assert_eq!(report.imported, 0);
+        assert_eq!(report.existing, 2);
+        assert_eq!(actions2::count(&connection).expect("count"), 2);
+    }
+}
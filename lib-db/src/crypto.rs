@@ -0,0 +1,419 @@
+//! Optional field level encryption of the sensitive columns: `commands.command`,
+//! `pages.title`, `pages.normalized_url` and `actions2.annotation`.
+//!
+//! The cipher is provided by the document repo, which owns the key, and set once on the
+//! `SqlStore` at startup when the database is marked as encrypted. The connections of the
+//! store carry its cipher, the values are sealed and opened with the cipher of the connection
+//! used for the query. Equality lookups go through keyed blind index columns since the
+//! encrypted values are randomized. The searches go through the keyed hashes of the words
+//! of the commands and titles in `blind_words`, they match whole words.
+#![allow(proc_macro_derive_resolution_fallback)]
+use crate::backends::schema::*;
+use crate::{Connection, SqlProvider, SqlStore};
+use diesel;
+use diesel::prelude::*;
+use lib_error::*;
+use std::collections::BTreeSet;
+
+/// Prefix of the encrypted values, allows to mix plain and encrypted rows during migration.
+pub const ENCRYPTED_PREFIX: &str = "enc1:";
+
+// Name of the setting marking the database as encrypted.
+const ENCRYPTED_SETTING: &str = "encrypted_columns";
+
+/// Kind of the `blind_words` rows of the commands.
+pub(crate) const COMMAND_WORDS: &str = "command";
+/// Kind of the `blind_words` rows of the page titles.
+pub(crate) const TITLE_WORDS: &str = "title";
+
+/// Encrypt and decrypt the column values.
+pub trait FieldCipher: Send + Sync {
+    /// Encrypt the value, the output should start with `ENCRYPTED_PREFIX`.
+    fn encrypt(&self, plain: &str) -> String;
+    /// Decrypt a value produced by encrypt.
+    fn decrypt(&self, stored: &str) -> Result<String>;
+    /// Keyed hash of the value, used for equality lookups.
+    fn blind_index(&self, plain: &str) -> String;
+}
+
+/// Check if the database was marked as encrypted.
+pub fn is_enabled(connection: &Connection) -> Result<bool> {
+    let values = settings::dsl::settings
+        .filter(settings::dsl::name.eq(ENCRYPTED_SETTING))
+        .select(settings::dsl::value)
+        .load::<String>(connection)?;
+    Ok(values.iter().any(|v| v == "1"))
+}
+
+/// Set the cipher of the store if the database is encrypted, returns true when set. The
+/// connections opened before do not use it.
+pub fn install_if_enabled<F>(store: &SqlStore, build: F) -> Result<bool>
+where
+    F: FnOnce() -> Result<Box<FieldCipher>>,
+{
+    if !is_enabled(&store.connection()?)? {
+        return Ok(false);
+    }
+    if !store.has_cipher()? {
+        store.set_cipher(build()?)?;
+    }
+    Ok(true)
+}
+
+/// Encrypt all the sensitive columns of an existing database and mark it as encrypted.
+/// The connection must come from a store with a cipher. Returns the number of updated rows.
+pub fn encrypt_database(connection: &Connection) -> Result<usize> {
+    use diesel::Connection as DieselConnection;
+
+    let cipher = match connection.cipher() {
+        Some(c) => c,
+        None => return Err("the field cipher is not installed".into()),
+    };
+    connection.transaction::<usize, WeaverError, _>(|| {
+        let mut count = 0;
+        for (id, command) in commands::dsl::commands
+            .select((commands::dsl::id, commands::dsl::command))
+            .load::<(Option<i32>, String)>(connection)?
+        {
+            if is_encrypted(&command) {
+                continue;
+            }
+            count += diesel::update(commands::table.filter(commands::dsl::id.eq(id)))
+                .set((
+                    commands::dsl::command.eq(cipher.encrypt(&command)),
+                    commands::dsl::command_bidx.eq(cipher.blind_index(&command)),
+                ))
+                .execute(connection)?;
+            if let Some(id) = id {
+                index_words_with(connection, cipher, COMMAND_WORDS, id, &command)?;
+            }
+        }
+        for (id, normalized_url, title) in pages::dsl::pages
+            .select((pages::dsl::id, pages::dsl::normalized_url, pages::dsl::title))
+            .load::<(Option<i32>, String, Option<String>)>(connection)?
+        {
+            if is_encrypted(&normalized_url) {
+                continue;
+            }
+            count += diesel::update(pages::table.filter(pages::dsl::id.eq(id)))
+                .set((
+                    pages::dsl::normalized_url.eq(cipher.encrypt(&normalized_url)),
+                    pages::dsl::normalized_url_bidx.eq(cipher.blind_index(&normalized_url)),
+                    pages::dsl::title.eq(title.as_ref().map(|t| seal_with(Some(cipher), t))),
+                ))
+                .execute(connection)?;
+            if let (Some(id), Some(title)) = (id, title) {
+                index_words_with(connection, cipher, TITLE_WORDS, id, &title)?;
+            }
+        }
+        for (id, annotation) in actions2::dsl::actions2
+            .select((actions2::dsl::id, actions2::dsl::annotation))
+            .filter(actions2::dsl::annotation.ne(""))
+            .load::<(Option<i32>, Option<String>)>(connection)?
+        {
+            if let Some(annotation) = annotation {
+                if is_encrypted(&annotation) {
+                    continue;
+                }
+                count += diesel::update(actions2::table.filter(actions2::dsl::id.eq(id)))
+                    .set(actions2::dsl::annotation.eq(cipher.encrypt(&annotation)))
+                    .execute(connection)?;
+            }
+        }
+        diesel::replace_into(settings::table)
+            .values((
+                settings::dsl::name.eq(ENCRYPTED_SETTING),
+                settings::dsl::value.eq("1"),
+            ))
+            .execute(connection)?;
+        Ok(count)
+    })
+}
+
+fn is_encrypted(stored: &str) -> bool {
+    stored.starts_with(ENCRYPTED_PREFIX)
+}
+
+// Empty values are kept as is, they are used as markers in queries.
+fn seal_with(cipher: Option<&FieldCipher>, plain: &str) -> String {
+    match cipher {
+        Some(c) if !plain.is_empty() => c.encrypt(plain),
+        _ => plain.to_owned(),
+    }
+}
+
+fn open_with(cipher: Option<&FieldCipher>, stored: String) -> Result<String> {
+    if !is_encrypted(&stored) {
+        return Ok(stored);
+    }
+    match cipher {
+        Some(c) => c.decrypt(&stored),
+        None => Err("encrypted value and no field cipher installed".into()),
+    }
+}
+
+// The lowercase words of the value.
+fn words(plain: &str) -> BTreeSet<String> {
+    plain
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+// The words are hashed apart from the whole values.
+fn word_index(cipher: &FieldCipher, word: &str) -> String {
+    cipher.blind_index(&format!("word:{}", word))
+}
+
+fn index_words_with(
+    connection: &Connection,
+    cipher: &FieldCipher,
+    kind: &str,
+    row_id: i32,
+    plain: &str,
+) -> Result<()> {
+    diesel::delete(
+        blind_words::table
+            .filter(blind_words::dsl::kind.eq(kind))
+            .filter(blind_words::dsl::row_id.eq(row_id)),
+    )
+    .execute(connection)?;
+    for word in words(plain) {
+        diesel::insert_into(blind_words::table)
+            .values((
+                blind_words::dsl::kind.eq(kind),
+                blind_words::dsl::row_id.eq(row_id),
+                blind_words::dsl::word_bidx.eq(word_index(cipher, &word)),
+            ))
+            .execute(connection)?;
+    }
+    Ok(())
+}
+
+/// Save the hashes of the words of the value of the row, when the connection encrypts the
+/// columns. `kind` is `COMMAND_WORDS` or `TITLE_WORDS`.
+pub(crate) fn index_words(
+    connection: &Connection,
+    kind: &str,
+    row_id: i32,
+    plain: &str,
+) -> Result<()> {
+    match connection.cipher() {
+        Some(cipher) => index_words_with(connection, cipher, kind, row_id, plain),
+        None => Ok(()),
+    }
+}
+
+/// The hashes of the words of the search, None when the connection does not encrypt the
+/// columns.
+pub(crate) fn search_words(connection: &Connection, search: &str) -> Option<Vec<String>> {
+    connection
+        .cipher()
+        .map(|cipher| words(search).iter().map(|w| word_index(cipher, w)).collect())
+}
+
+/// Prepare a value to be saved in an encrypted column.
+pub(crate) fn seal(connection: &Connection, plain: &str) -> String {
+    seal_with(connection.cipher(), plain)
+}
+
+/// Decrypt a value loaded from an encrypted column, plain values are returned as is.
+pub(crate) fn open(connection: &Connection, stored: String) -> Result<String> {
+    open_with(connection.cipher(), stored)
+}
+
+/// Decrypt an optional value loaded from an encrypted column.
+pub(crate) fn open_option(
+    connection: &Connection,
+    stored: Option<String>,
+) -> Result<Option<String>> {
+    match stored {
+        Some(s) => open(connection, s).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// The blind index of the value, when the connection encrypts the columns.
+pub(crate) fn blind_index(connection: &Connection, plain: &str) -> Option<String> {
+    connection.cipher().map(|c| c.blind_index(plain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{actions2, forget};
+    use crate::test_helpers::SqlStoreInMemory;
+    use crate::{embedded_migrations, SqlProvider};
+    use diesel::connection::SimpleConnection;
+    use lib_goo::config::net::{ForgetReport, Pagination};
+    use lib_goo::entities::NewAction;
+    use std::sync::Arc;
+
+    // Reversible cipher good enough to test the plumbing, the values carry the name of the key.
+    struct TestCipher(&'static str);
+
+    impl FieldCipher for TestCipher {
+        fn encrypt(&self, plain: &str) -> String {
+            format!(
+                "{}{}:{}",
+                ENCRYPTED_PREFIX,
+                self.0,
+                plain.chars().rev().collect::<String>()
+            )
+        }
+
+        fn decrypt(&self, stored: &str) -> Result<String> {
+            let key = format!("{}{}:", ENCRYPTED_PREFIX, self.0);
+            if !stored.starts_with(&key) {
+                return Err("encrypted with another key".into());
+            }
+            Ok(stored[key.len()..].chars().rev().collect())
+        }
+
+        fn blind_index(&self, plain: &str) -> String {
+            format!("{}-{}", self.0, plain)
+        }
+    }
+
+    fn encrypted(key: &'static str) -> Connection {
+        let connection = Connection::open(":memory:", Some(Arc::new(TestCipher(key))))
+            .expect("in memory database");
+        embedded_migrations::run(&connection).expect("create tables");
+        connection
+    }
+
+    fn search(connection: &Connection, text: &str) -> Vec<String> {
+        actions2::fetch(connection, Some(text), None, &Pagination::default())
+            .expect("fetch")
+            .into_iter()
+            .map(|a| a.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let sealed = seal_with(Some(&TestCipher("a")), "secret");
+        assert_eq!(&sealed, "enc1:a:terces");
+        assert_eq!(open_with(Some(&TestCipher("a")), sealed.clone()).unwrap(), "secret");
+
+        // no cipher
+        assert_eq!(&seal_with(None, "plain"), "plain");
+        assert_eq!(open_with(None, "plain".into()).unwrap(), "plain");
+        assert!(open_with(None, sealed).is_err());
+
+        // empty values stay empty
+        assert_eq!(&seal_with(Some(&TestCipher("a")), ""), "");
+    }
+
+    #[test]
+    fn test_connection_cipher() {
+        // The connections of two stores used from the same thread keep their own cipher.
+        let first = encrypted("a");
+        let second = encrypted("b");
+        let sealed = seal(&first, "secret");
+        assert_eq!(&seal(&second, "secret"), "enc1:b:terces");
+        assert_eq!(&seal(&first, "secret"), &sealed);
+        assert_eq!(open(&first, sealed.clone()).unwrap(), "secret");
+        assert!(open(&second, sealed).is_err());
+        assert_eq!(blind_index(&second, "ls"), Some("b-ls".into()));
+    }
+
+    #[test]
+    fn test_search_words() {
+        let connection = encrypted("a");
+        for command in &["ls -la", "cargo build", "cargo test"] {
+            let action = NewAction {
+                command: command.to_string(),
+                ..NewAction::default()
+            };
+            actions2::insert(&connection, &action).expect("insert");
+        }
+        assert_eq!(search(&connection, "LS"), vec!["ls -la".to_string()]);
+        assert_eq!(search(&connection, "build cargo"), vec!["cargo build".to_string()]);
+        assert_eq!(search(&connection, "cargo").len(), 2);
+        // Only whole words match.
+        assert!(search(&connection, "carg").is_empty());
+
+        let page = actions2::fetch(
+            &connection,
+            Some("cargo"),
+            None,
+            &Pagination {
+                start: Some(1),
+                length: Some(1),
+            },
+        )
+        .expect("fetch");
+        assert_eq!(page.len(), 1);
+    }
+
+    #[test]
+    fn test_encrypt_database() {
+        let connection = encrypted("a");
+        connection
+            .batch_execute(
+                "INSERT INTO commands (id, kind, command) VALUES (1, 'shell', 'git status');
+                 INSERT INTO actions2 (command_id, executed) VALUES (1, '2019-02-10T10:00:00Z');",
+            )
+            .expect("plain rows");
+        assert!(!is_enabled(&connection).unwrap());
+        assert_eq!(encrypt_database(&connection).unwrap(), 1);
+        assert!(is_enabled(&connection).unwrap());
+        let stored = commands::dsl::commands
+            .select(commands::dsl::command)
+            .load::<String>(&connection)
+            .unwrap();
+        assert_eq!(stored, vec!["enc1:a:sutats tig".to_string()]);
+        assert_eq!(search(&connection, "status"), vec!["git status".to_string()]);
+
+        // The words of the deleted commands are removed with them.
+        connection
+            .batch_execute("DELETE FROM actions2; DELETE FROM commands;")
+            .expect("delete");
+        let words: i64 = blind_words::table.count().get_result(&connection).unwrap();
+        assert_eq!(words, 0);
+    }
+
+    #[test]
+    fn test_forget_encrypted() {
+        let connection = encrypted("a");
+        // A plain row left from before the encryption is matched by sqlite.
+        connection
+            .batch_execute(
+                "INSERT INTO commands (kind, command) VALUES ('shell', 'cat secret.txt');",
+            )
+            .expect("plain row");
+        for command in &["https://secret.example/a", "ls"] {
+            let action = NewAction {
+                kind: "url".into(),
+                command: command.to_string(),
+                ..NewAction::default()
+            };
+            actions2::insert(&connection, &action).expect("insert");
+        }
+        let mut report = ForgetReport::default();
+        forget::forget(&connection, "secret", &mut report).expect("forget");
+        assert_eq!(report.actions, 1);
+        assert_eq!(report.commands, 2);
+        assert_eq!(report.urls, vec!["https://secret.example/a".to_string()]);
+        assert_eq!(search(&connection, "ls"), vec!["ls".to_string()]);
+    }
+
+    #[test]
+    fn test_not_enabled_by_default() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        assert!(!is_enabled(&connection).unwrap());
+    }
+
+    #[test]
+    fn test_set_cipher_once() {
+        let store = SqlStore::build().expect("store");
+        assert!(!store.has_cipher().unwrap());
+        store.set_cipher(Box::new(TestCipher("a"))).expect("first cipher");
+        assert!(store.has_cipher().unwrap());
+        assert!(store.set_cipher(Box::new(TestCipher("a"))).is_err());
+    }
+}
@@ -1,14 +1,20 @@
 #![allow(proc_macro_derive_resolution_fallback)]
 use crate::backends::schema::*;
-use crate::db;
+use crate::crypto;
+use crate::db::{self, CHUNK};
 use diesel;
 use diesel::prelude::*;
 use lib_error::*;
-use lib_goo::config::net::Pagination;
+use chrono::{DateTime, FixedOffset};
+use lib_goo::config::net::{ActionFilter, DeleteReport, Pagination};
 use lib_goo::date;
+use lib_goo::normalize;
 use lib_goo::entities::{ActionId, FormattedAction, NewAction, RecommendReason};
 use crate::Connection;
 
+// The id of the row inserted last by this connection.
+no_arg_sql_function!(last_insert_rowid, diesel::sql_types::BigInt);
+
 #[derive(Queryable, Debug)]
 #[allow(dead_code)]
 struct Action2 {
@@ -21,6 +27,7 @@ struct Action2 {
     annotation: Option<String>,
     host_id: Option<i32>,
     status: Option<i32>,
+    parent_id: Option<String>,
 }
 
 #[derive(Queryable, Debug)]
@@ -43,6 +50,7 @@ struct Page {
     id: Option<i32>,
     normalized_url: String,
     title: Option<String>,
+    normalized_url_bidx: Option<String>,
 }
 
 #[derive(Queryable, Debug)]
@@ -52,6 +60,7 @@ struct Command {
     kind: String,
     command: String,
     page_id: Option<i32>,
+    command_bidx: Option<String>,
 }
 
 /// Count the number of actions.
@@ -64,9 +73,27 @@ pub fn count(connection: &Connection) -> Result<usize> {
 type Backend = diesel::sqlite::Sqlite;
 
 /// Fetch all actions as FormattedActions, use the pagination settings for the range. If present
+/// only the actions matching the search term and having the tag are returned.
 pub fn fetch(
     connection: &Connection,
     search: Option<&str>,
+    tag: Option<&str>,
+    pagination: &Pagination,
+) -> Result<Vec<FormattedAction>> {
+    fetch_filtered(connection, None, search, tag, pagination)
+}
+
+/// Fetch one action as a FormattedAction.
+pub fn fetch_by_id(connection: &Connection, id: u64) -> Result<Option<FormattedAction>> {
+    let mut found = fetch_filtered(connection, Some(id as i32), None, None, &Pagination::default())?;
+    Ok(found.pop())
+}
+
+fn fetch_filtered(
+    connection: &Connection,
+    id: Option<i32>,
+    search: Option<&str>,
+    tag: Option<&str>,
     pagination: &Pagination,
 ) -> Result<Vec<FormattedAction>> {
     // setup the table joins, need to use into_boxed() to handle conditional code.
@@ -74,27 +101,77 @@ pub fn fetch(
         .inner_join(commands::table.left_join(pages::table))
         .left_join(locations::table)
         .into_boxed();
-
-    // Apply an optional filter
-    if let Some(txt) = search {
-        let like_clause = format!("%{}%", txt);
-        ::log::info!("like clause '{}'", like_clause);
+    if let Some(id) = id {
+        joined = joined.filter(actions2::dsl::id.eq(id));
+    }
+    if let Some(tag) = tag {
+        let tag_id = match db::tags::fetch_id(connection, &db::tags::normalize_name(tag)?)? {
+            Some(tag_id) => tag_id,
+            None => return Ok(Vec::new()),
+        };
         joined = joined.filter(
-            commands::dsl::command
-                .like(like_clause.clone())
-                .or(pages::dsl::title.like(like_clause)),
+            actions2::dsl::id.eq_any(
+                action_tags::table
+                    .filter(action_tags::dsl::tag_id.eq(tag_id))
+                    .select(action_tags::dsl::action_id.nullable()),
+            ),
         );
+    }
+
+    // Apply an optional filter, the encrypted columns are searched by the hashes of their words.
+    if let Some(txt) = search {
+        match crypto::search_words(connection, txt) {
+            Some(words) => {
+                for word_bidx in words {
+                    let with_word = |kind: &'static str| {
+                        blind_words::table
+                            .filter(blind_words::dsl::kind.eq(kind))
+                            .filter(blind_words::dsl::word_bidx.eq(word_bidx.clone()))
+                            .select(blind_words::dsl::row_id.nullable())
+                    };
+                    joined = joined.filter(
+                        commands::dsl::id
+                            .eq_any(with_word(crypto::COMMAND_WORDS))
+                            .or(pages::dsl::id.eq_any(with_word(crypto::TITLE_WORDS))),
+                    );
+                }
+            }
+            None => {
+                let like_clause = format!("%{}%", txt);
+                ::log::info!("like clause '{}'", like_clause);
+                joined = joined.filter(
+                    commands::dsl::command
+                        .like(like_clause.clone())
+                        .or(pages::dsl::title.like(like_clause)),
+                );
+            }
+        }
     };
 
     // info!("sql {:?}", diesel::debug_query::<Backend, _>(&joined));
     // Note: in sqlite3 you cannot pass offset without limit.
-    let loaded = joined
+    joined = joined
         .limit(pagination.length.unwrap_or(-1))
-        .offset(pagination.start.unwrap_or(0))
-        .load::<(Action2, (Command, Option<Page>), Option<Location>)>(connection)?;
+        .offset(pagination.start.unwrap_or(0));
+    let loaded = joined.load::<(Action2, (Command, Option<Page>), Option<Location>)>(connection)?;
+    let starred = db::bookmarks::starred_page_ids(connection)?;
     let mut out = Vec::new();
-    for (action2, (command, page_rec), location_rec) in loaded {
+    for (mut action2, (mut command, page_rec), location_rec) in loaded {
+        command.command = crypto::open(connection, command.command)?;
+        action2.annotation = crypto::open_option(connection, action2.annotation)?;
+        let page_rec = match page_rec {
+            Some(mut page) => {
+                page.title = crypto::open_option(connection, page.title)?;
+                Some(page)
+            }
+            None => None,
+        };
         let when = date::Date::parse(&action2.executed).ok();
+        let is_starred = page_rec
+            .as_ref()
+            .and_then(|p| p.id)
+            .map(|id| starred.contains(&id))
+            .unwrap_or(false);
 
         let (name, location) = if let Some(page) = page_rec {
             // If we have a matching page and it has a title then use it's title for the name
@@ -112,38 +189,50 @@ pub fn fetch(
             };
             (command.command, clean)
         };
+        let raw_id = action2.id.unwrap_or_default();
         let formatted = FormattedAction {
             annotation: action2.annotation,
-            id: action2
-                .id
-                .map(|a| ActionId::new(a as usize))
-                .unwrap_or_default(),
+            id: ActionId::new(raw_id as usize),
             epic: None,
             kind: command.kind,
             name,
             location,
             reason: RecommendReason::default(),
+            starred: is_starred,
+            tags: Vec::new(),
             when,
         };
-        out.push(formatted);
+        out.push((raw_id, formatted));
     }
 
-    Ok(out)
+    let ids = out.iter().map(|(id, _)| *id).collect::<Vec<i32>>();
+    let mut tags = db::tags::for_actions(connection, &ids)?;
+    Ok(out
+        .into_iter()
+        .map(|(id, mut action)| {
+            action.tags = tags.remove(&id).unwrap_or_default();
+            action
+        })
+        .collect())
 }
 
 // Return the last access time for the given command
 pub fn last_access(connection: &Connection, command: &str) -> Result<Option<String>> {
-    let entries = actions2::dsl::actions2
+    let query = actions2::dsl::actions2
         .inner_join(commands::dsl::commands)
         .select(actions2::dsl::executed)
-        .filter(commands::dsl::command.eq(command))
         .order(actions2::dsl::id.desc())
         .limit(1)
-        .load::<(String)>(connection)?;
+        .into_boxed();
+    let query = match crypto::blind_index(connection, command) {
+        Some(bidx) => query.filter(commands::dsl::command_bidx.eq(bidx)),
+        None => query.filter(commands::dsl::command.eq(command)),
+    };
+    let entries = query.load::<(String)>(connection)?;
     Ok(entries.first().cloned())
 }
 
-/// Insert a new action in the database.
+/// Insert a new action in the database, return its id.
 pub fn insert(connection: &Connection, action: &NewAction) -> Result<u64> {
     use diesel::Connection as DieselConnection;
 
@@ -161,6 +250,7 @@ pub fn insert(connection: &Connection, action: &NewAction) -> Result<u64> {
         let command_id =
             db::commands::fetch_or_create_id(connection, &action.kind, &action.command)?;
         let host_id = db::hosts::fetch_or_create_id(connection, &action.host)?;
+        let status = action.status_code.as_ref().and_then(|s| s.parse::<i32>().ok());
         let entry = (
             actions2::dsl::command_id.eq(command_id),
             actions2::dsl::executed.eq(&action.executed),
@@ -169,6 +259,8 @@ pub fn insert(connection: &Connection, action: &NewAction) -> Result<u64> {
             actions2::dsl::sent.eq(false),
             actions2::dsl::annotation.eq(String::new()),
             actions2::dsl::host_id.eq(host_id),
+            actions2::dsl::status.eq(status),
+            actions2::dsl::parent_id.eq(&action.parent_id),
         );
         let count = diesel::insert_into(actions2::table)
             .values(entry)
@@ -176,23 +268,200 @@ pub fn insert(connection: &Connection, action: &NewAction) -> Result<u64> {
         if count != 1 {
             return Err(WeaverError::from(format!("bad insert count {} during migration", count)));
         }
-        Ok(1)
+        let id = diesel::select(last_insert_rowid).get_result::<i64>(connection)?;
+        Ok(id as u64)
     })
 }
 
 pub fn set_annotation(connection: &Connection, id: u64, annotation: &str) -> Result<(u64)> {
     let find_clause = actions2::dsl::actions2.filter(actions2::dsl::id.eq(id as i32));
     let id = diesel::update(find_clause)
-        .set(actions2::dsl::annotation.eq(annotation))
+        .set(actions2::dsl::annotation.eq(crypto::seal(connection, annotation)))
         .execute(connection)?;
     Ok(id as u64)
 }
 
+/// Change the epic of the action, None or an empty name removes it from its epic.
+pub fn set_epic(connection: &Connection, id: u64, epic: Option<&str>) -> Result<u64> {
+    let epic_id = match epic {
+        Some(name) if !name.is_empty() => Some(db::epics::fetch_or_create_id(connection, name)?),
+        _ => None,
+    };
+    let find_clause = actions2::dsl::actions2.filter(actions2::dsl::id.eq(id as i32));
+    let count = diesel::update(find_clause)
+        .set(actions2::dsl::epic_id.eq(epic_id))
+        .execute(connection)?;
+    Ok(count as u64)
+}
+
+fn parse_time(value: &Option<String>) -> Result<Option<DateTime<FixedOffset>>> {
+    match value {
+        Some(v) => DateTime::parse_from_rfc3339(v)
+            .map(Some)
+            .map_err(|_| WeaverError::from(format!("cannot parse time {}", v))),
+        None => Ok(None),
+    }
+}
+
+/// Fetch the ids of the actions matching all the criteria of the filter.
+/// The command is matched in memory since it may be encrypted.
+pub fn matching(connection: &Connection, filter: &ActionFilter) -> Result<Vec<i32>> {
+    if filter.is_empty() {
+        return Err("the filter needs at least one criteria".into());
+    }
+    let since = parse_time(&filter.since)?;
+    let until = parse_time(&filter.until)?;
+    let entries = actions2::table
+        .inner_join(commands::table)
+        .left_join(hosts::table)
+        .select((
+            actions2::dsl::id,
+            commands::dsl::command,
+            actions2::dsl::executed,
+            hosts::dsl::name.nullable(),
+        ))
+        .load::<(Option<i32>, String, String, Option<String>)>(connection)?;
+    let mut out = Vec::new();
+    for (id, command, executed, host) in entries {
+        if let Some(ref pattern) = filter.command {
+            if !crypto::open(connection, command)?.contains(pattern.as_str()) {
+                continue;
+            }
+        }
+        if filter.host.is_some() && filter.host != host {
+            continue;
+        }
+        if since.is_some() || until.is_some() {
+            let when = match DateTime::parse_from_rfc3339(&executed) {
+                Ok(w) => w,
+                Err(_) => continue,
+            };
+            if since.map(|s| when < s).unwrap_or(false) || until.map(|u| when >= u).unwrap_or(false)
+            {
+                continue;
+            }
+        }
+        if let Some(id) = id {
+            out.push(id);
+        }
+    }
+    Ok(out)
+}
+
+/// Delete the commands and locations which are not used by any action.
+/// Return the number of deleted commands and locations.
+pub fn collect_garbage(connection: &Connection) -> Result<(usize, usize)> {
+    let commands = diesel::sql_query(
+        "DELETE FROM commands WHERE id NOT IN \
+         (SELECT command_id FROM actions2 WHERE command_id IS NOT NULL)",
+    )
+    .execute(connection)?;
+    let locations = diesel::sql_query(
+        "DELETE FROM locations WHERE id NOT IN \
+         (SELECT location_id FROM actions2 WHERE location_id IS NOT NULL)",
+    )
+    .execute(connection)?;
+    Ok((commands, locations))
+}
+
+// The given ids of the actions which exist.
+fn existing_ids(connection: &Connection, ids: &[i32]) -> Result<Vec<i32>> {
+    let mut out = Vec::with_capacity(ids.len());
+    for chunk in ids.chunks(CHUNK) {
+        let found = actions2::table
+            .select(actions2::dsl::id)
+            .filter(actions2::dsl::id.eq_any(chunk))
+            .load::<Option<i32>>(connection)?;
+        out.extend(found.into_iter().filter_map(|id| id));
+    }
+    Ok(out)
+}
+
+/// Delete the actions and then the commands and locations left unused.
+/// Returns the ids of the deleted actions, the unknown ids are left out.
+pub fn delete_and_collect(
+    connection: &Connection,
+    ids: &[i32],
+) -> Result<(Vec<i32>, DeleteReport)> {
+    use diesel::Connection as DieselConnection;
+
+    connection.transaction::<_, WeaverError, _>(|| {
+        let deleted = existing_ids(connection, ids)?;
+        let actions = delete(connection, &deleted)?;
+        let (commands, locations) = collect_garbage(connection)?;
+        let report = DeleteReport {
+            actions,
+            commands,
+            locations,
+        };
+        Ok((deleted, report))
+    })
+}
+
+/// Fetch the ids of the actions of the given kind older than max_age seconds.
+/// Actions with an unparsable execution time are never returned.
+pub fn expired(connection: &Connection, kind: &str, max_age: i64) -> Result<Vec<i32>> {
+    let entries = actions2::table
+        .inner_join(commands::table)
+        .select((actions2::dsl::id, actions2::dsl::executed))
+        .filter(commands::dsl::kind.eq(kind))
+        .load::<(Option<i32>, String)>(connection)?;
+    Ok(entries
+        .into_iter()
+        .filter(|(_, executed)| date::age(executed).map(|a| a > max_age).unwrap_or(false))
+        .filter_map(|(id, _)| id)
+        .collect())
+}
+
+/// Fetch the execution times of the actions of the given command.
+pub fn executed_for_command(connection: &Connection, command_id: i32) -> Result<Vec<String>> {
+    Ok(actions2::table
+        .select(actions2::dsl::executed)
+        .filter(actions2::dsl::command_id.eq(command_id))
+        .load::<String>(connection)?)
+}
+
+/// Delete the actions with the given ids, return the number of deleted actions.
+pub fn delete(connection: &Connection, ids: &[i32]) -> Result<usize> {
+    let mut count = 0;
+    for chunk in ids.chunks(CHUNK) {
+        count += diesel::delete(actions2::table.filter(actions2::dsl::id.eq_any(chunk)))
+            .execute(connection)?;
+    }
+    db::tags::delete_for_actions(connection, ids)?;
+    db::sessions::delete_for_actions(connection, ids)?;
+    Ok(count)
+}
+
+/// Fetch the normalized urls which have at least one annotated action.
+pub fn annotated_urls(connection: &Connection) -> Result<Vec<String>> {
+    let entries = actions2::table
+        .inner_join(commands::table)
+        .select(commands::dsl::command)
+        .filter(commands::dsl::kind.eq("url"))
+        .filter(actions2::dsl::annotation.ne(""))
+        .load::<String>(connection)?;
+    let mut out = Vec::with_capacity(entries.len());
+    for url in entries {
+        let url = crypto::open(connection, url)?;
+        out.push(
+            normalize::normalize_url(&url)
+                .map(|u| u.into_owned())
+                .unwrap_or_else(|_| url.clone()),
+        );
+    }
+    out.sort();
+    out.dedup();
+    Ok(out)
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::backends::schema::actions2;
+    use diesel::prelude::*;
     use lib_goo::config::net::*;
     use lib_goo::date::now;
-    use lib_goo::entities::NewAction;
+    use lib_goo::entities::{ActionId, NewAction};
     use crate::test_helpers::SqlStoreInMemory;
     use crate::SqlProvider;
 
@@ -205,13 +474,39 @@ mod tests {
         let res = super::insert(&connection, &NewAction::default());
         assert!(res.is_ok(), format!("insert failed {:?}", res));
 
-        let all = super::fetch(&connection, None, &Pagination::default());
+        let all = super::fetch(&connection, None, None, &Pagination::default());
         assert!(res.is_ok(), format!("fetch_all failed {:?}", res));
 
         let actions = all.unwrap();
         assert_eq!(actions.len(), 1);
     }
 
+    #[test]
+    fn test_insert_and_fetch_by_id() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+
+        for command in &["ls", "pwd"] {
+            let action = NewAction {
+                command: command.to_string(),
+                ..NewAction::default()
+            };
+            super::insert(&connection, &action).expect("insert");
+        }
+        let action = NewAction {
+            command: "ls".into(),
+            ..NewAction::default()
+        };
+        let id = super::insert(&connection, &action).expect("insert");
+        assert_eq!(id, 3);
+
+        let found = super::fetch_by_id(&connection, 2).unwrap().expect("found");
+        assert_eq!(found.name, "pwd");
+        assert_eq!(found.id, ActionId::new(2));
+        assert!(super::fetch_by_id(&connection, 4).unwrap().is_none());
+    }
+
     #[test]
     fn test_insert_and_count() {
         let connection = SqlStoreInMemory::build(|_| Ok(()))
@@ -238,7 +533,7 @@ mod tests {
         let update = super::set_annotation(&connection, 1, "ha-not-ate");
         assert!(update.is_ok(), format!("update failed {:?}", update));
 
-        let all = super::fetch(&connection, None, &Pagination::default());
+        let all = super::fetch(&connection, None, None, &Pagination::default());
         assert!(res.is_ok(), format!("fetch_all failed {:?}", res));
 
         assert_eq!(
@@ -262,7 +557,7 @@ mod tests {
         for i in vec!["foo", "bar", "baz"] {
             super::insert(&connection, &new_action(i)).expect("insert");
         }
-        let all = super::fetch(&connection, Some("ba"), &Pagination::default()).expect("fetch");
+        let all = super::fetch(&connection, Some("ba"), None, &Pagination::default()).expect("fetch");
         assert_eq!(all.len(), 2);
         assert_eq!(
             all.iter().map(|a| a.name.as_str()).collect::<Vec<&str>>(),
@@ -270,6 +565,154 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_fetch_by_tag() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        for i in vec!["make", "make deploy", "gdb"] {
+            super::insert(&connection, &new_action(i)).expect("insert");
+        }
+        crate::db::tags::add_to_action(&connection, 2, "deploy").expect("tag");
+        let tagged = super::fetch(&connection, None, Some("deploy"), &Pagination::default())
+            .expect("fetch");
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(&tagged[0].name, "make deploy");
+        assert_eq!(tagged[0].tags, vec!["deploy"]);
+
+        let searched = super::fetch(&connection, Some("make"), Some("deploy"), &Pagination::default())
+            .expect("fetch");
+        assert_eq!(searched.len(), 1);
+        assert!(super::fetch(&connection, None, Some("unknown"), &Pagination::default())
+            .expect("fetch")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_expired_and_delete() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        let old = NewAction {
+            kind: "shell".into(),
+            command: "old".into(),
+            executed: "2018-01-01T00:00:00+00:00".into(),
+            ..NewAction::default()
+        };
+        let recent = NewAction {
+            kind: "shell".into(),
+            command: "recent".into(),
+            executed: now(),
+            ..NewAction::default()
+        };
+        super::insert(&connection, &old).expect("insert");
+        super::insert(&connection, &recent).expect("insert");
+
+        let expired = super::expired(&connection, "shell", 24 * 3600).expect("expired");
+        assert_eq!(expired.len(), 1);
+        assert!(super::expired(&connection, "url", 0).expect("expired").is_empty());
+
+        assert_eq!(super::delete(&connection, &expired).expect("delete"), 1);
+        let all = super::fetch(&connection, None, None, &Pagination::default()).expect("fetch");
+        assert_eq!(all.len(), 1);
+        assert_eq!(&all[0].name, "recent");
+    }
+
+    #[test]
+    fn test_matching_and_collect_garbage() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        let actions = vec![
+            ("echo password1", "laptop", "2019-01-01T10:00:00+00:00", "/home"),
+            ("echo password2", "server", "2019-01-02T10:00:00+00:00", "/tmp"),
+            ("ls", "laptop", "2019-01-03T10:00:00+00:00", "/home"),
+        ];
+        for (command, host, executed, location) in actions {
+            let action = NewAction {
+                command: command.into(),
+                host: host.into(),
+                executed: executed.into(),
+                location: Some(location.into()),
+                ..NewAction::default()
+            };
+            super::insert(&connection, &action).expect("insert");
+        }
+
+        let empty = ActionFilter::default();
+        assert!(super::matching(&connection, &empty).is_err());
+
+        let by_command = ActionFilter {
+            command: Some("password".into()),
+            ..ActionFilter::default()
+        };
+        assert_eq!(super::matching(&connection, &by_command).unwrap(), vec![1, 2]);
+
+        let by_host_and_time = ActionFilter {
+            host: Some("laptop".into()),
+            since: Some("2019-01-02T00:00:00+00:00".into()),
+            ..ActionFilter::default()
+        };
+        assert_eq!(super::matching(&connection, &by_host_and_time).unwrap(), vec![3]);
+
+        let until = ActionFilter {
+            until: Some("2019-01-02T10:00:00+00:00".into()),
+            ..ActionFilter::default()
+        };
+        assert_eq!(super::matching(&connection, &until).unwrap(), vec![1]);
+
+        // Only the second command and location are left unused.
+        super::delete(&connection, &[2]).expect("delete");
+        assert_eq!(super::collect_garbage(&connection).unwrap(), (1, 1));
+        assert_eq!(super::collect_garbage(&connection).unwrap(), (0, 0));
+
+        // The unknown and already deleted ids are not reported.
+        let (deleted, report) = super::delete_and_collect(&connection, &[2, 3, 42]).unwrap();
+        assert_eq!(deleted, vec![3]);
+        assert_eq!(report.actions, 1);
+    }
+
+    #[test]
+    fn test_set_epic() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        super::insert(&connection, &new_action("ls")).expect("insert");
+
+        assert_eq!(super::set_epic(&connection, 1, Some("cleanup")).unwrap(), 1);
+        let epic_id = actions2::dsl::actions2
+            .select(actions2::dsl::epic_id)
+            .first::<Option<i32>>(&connection)
+            .unwrap();
+        assert!(epic_id.is_some());
+
+        assert_eq!(super::set_epic(&connection, 1, Some("")).unwrap(), 1);
+        let epic_id = actions2::dsl::actions2
+            .select(actions2::dsl::epic_id)
+            .first::<Option<i32>>(&connection)
+            .unwrap();
+        assert!(epic_id.is_none());
+    }
+
+    #[test]
+    fn test_annotated_urls() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        for url in vec!["https://foo/a?b", "https://bar/c"] {
+            let action = NewAction {
+                kind: "url".into(),
+                command: url.into(),
+                ..NewAction::default()
+            };
+            super::insert(&connection, &action).expect("insert");
+        }
+        super::set_annotation(&connection, 1, "keep").expect("annotate");
+
+        let annotated = super::annotated_urls(&connection).expect("annotated");
+        assert_eq!(annotated, vec![String::from("https://foo/a")]);
+    }
+
     #[test]
     fn test_last_access() {
         let connection = SqlStoreInMemory::build(|_| Ok(()))
@@ -0,0 +1,140 @@
+//! Tokens used by the clients to authenticate with the api.
+//! Only the sha256 hash of the token is saved, the token is shown once when created.
+#![allow(proc_macro_derive_resolution_fallback)]
+use crate::backends::schema::api_tokens;
+use crate::Connection;
+use diesel;
+use diesel::prelude::*;
+use lib_error::*;
+use lib_goo::date;
+use rand::{self, Rng};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+/// Prefix of the generated tokens, makes them easy to spot in config files.
+pub const TOKEN_PREFIX: &str = "wvr_";
+
+/// Description of a token, as saved in the database.
+#[derive(Queryable, Debug)]
+pub struct ApiToken {
+    pub id: Option<i32>,
+    pub name: String,
+    pub token_hash: String,
+    pub created: String,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        write!(out, "{:02x}", b).expect("write to string");
+    }
+    out
+}
+
+/// Hash the token for storage and lookups.
+pub fn hash(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+/// Generate a new token for the named client, return the token in clear.
+pub fn create(connection: &Connection, name: &str) -> Result<String> {
+    if name.is_empty() {
+        return Err("the token name cannot be empty".into());
+    }
+    let existing = api_tokens::dsl::api_tokens
+        .filter(api_tokens::dsl::name.eq(name))
+        .count()
+        .get_result::<i64>(connection)?;
+    if existing > 0 {
+        return Err(WeaverError::from(format!("token {} already exists", name)));
+    }
+    let mut random = [0u8; 32];
+    rand::thread_rng().fill(&mut random);
+    let token = format!("{}{}", TOKEN_PREFIX, to_hex(&random));
+    insert(connection, name, &token)?;
+    Ok(token)
+}
+
+/// Save the hash of a token generated elsewhere.
+pub fn insert(connection: &Connection, name: &str, token: &str) -> Result<()> {
+    diesel::insert_into(api_tokens::table)
+        .values((
+            api_tokens::dsl::name.eq(name),
+            api_tokens::dsl::token_hash.eq(hash(token)),
+            api_tokens::dsl::created.eq(date::now()),
+        ))
+        .execute(connection)?;
+    Ok(())
+}
+
+/// Delete the named token, returns false if it did not exist.
+pub fn revoke(connection: &Connection, name: &str) -> Result<bool> {
+    let deleted = diesel::delete(api_tokens::table.filter(api_tokens::dsl::name.eq(name)))
+        .execute(connection)?;
+    Ok(deleted > 0)
+}
+
+/// List all the tokens, by name.
+pub fn fetch_all(connection: &Connection) -> Result<Vec<ApiToken>> {
+    let entries = api_tokens::dsl::api_tokens
+        .order(api_tokens::dsl::name.asc())
+        .load::<ApiToken>(connection)?;
+    Ok(entries)
+}
+
+/// Count the tokens, the api is closed while there are none unless `server.open_api` is set.
+pub fn count(connection: &Connection) -> Result<usize> {
+    let count: i64 = api_tokens::table.count().get_result(connection)?;
+    Ok(count as usize)
+}
+
+/// Check if the token was created and not revoked.
+pub fn is_valid(connection: &Connection, token: &str) -> Result<bool> {
+    let found: i64 = api_tokens::table
+        .filter(api_tokens::dsl::token_hash.eq(hash(token)))
+        .count()
+        .get_result(connection)?;
+    Ok(found > 0)
+}
+
+/// Name of the valid token, used as the tenant by the multi-tenant server.
+pub fn fetch_name(connection: &Connection, token: &str) -> Result<Option<String>> {
+    let found = api_tokens::table
+        .filter(api_tokens::dsl::token_hash.eq(hash(token)))
+        .select(api_tokens::dsl::name)
+        .first::<String>(connection)
+        .optional()?;
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::SqlStoreInMemory;
+    use crate::SqlProvider;
+
+    #[test]
+    fn test_create_and_revoke() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+
+        let token = create(&connection, "laptop").unwrap();
+        assert!(token.starts_with(TOKEN_PREFIX));
+        assert_eq!(1, count(&connection).unwrap());
+        assert!(is_valid(&connection, &token).unwrap());
+        assert!(!is_valid(&connection, "wvr_other").unwrap());
+        assert_eq!(Some("laptop".into()), fetch_name(&connection, &token).unwrap());
+        assert_eq!(None, fetch_name(&connection, "wvr_other").unwrap());
+
+        // Only the hash is saved.
+        let all = fetch_all(&connection).unwrap();
+        assert_eq!("laptop", all[0].name);
+        assert_eq!(hash(&token), all[0].token_hash);
+
+        assert!(create(&connection, "laptop").is_err());
+        assert!(revoke(&connection, "laptop").unwrap());
+        assert!(!revoke(&connection, "laptop").unwrap());
+        assert!(!is_valid(&connection, &token).unwrap());
+    }
+}
@@ -0,0 +1,327 @@
+//! Starred pages and named reading lists, both pointing to the pages table.
+//!
+//! The pages are created when needed, they do not need to be indexed. Starred pages and
+//! the pages of the reading lists are kept by the retention rules.
+#![allow(proc_macro_derive_resolution_fallback)]
+use crate::backends::schema::*;
+use crate::crypto;
+use crate::db::{self, CHUNK};
+use crate::Connection;
+use diesel;
+use diesel::prelude::*;
+use lib_error::*;
+use lib_goo::config::net::{BookmarkedPage, ReadingList};
+use lib_goo::date;
+use lib_goo::entities::PageContent;
+use std::collections::HashSet;
+
+/// Longest name of a reading list.
+const MAX_NAME_LEN: usize = 100;
+
+/// Reading list names are trimmed, any other character is allowed.
+pub fn normalize_list_name(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("the reading list name cannot be empty".into());
+    }
+    if name.chars().count() > MAX_NAME_LEN {
+        return Err(WeaverError::from(format!(
+            "the reading list name is longer than {} characters",
+            MAX_NAME_LEN
+        )));
+    }
+    Ok(name.to_string())
+}
+
+// Decrypt the url and title of the pages, in the order they were loaded.
+fn to_bookmarked(
+    connection: &Connection,
+    entries: Vec<(String, Option<String>, String)>,
+) -> Result<Vec<BookmarkedPage>> {
+    let mut out = Vec::with_capacity(entries.len());
+    for (url, title, added) in entries {
+        out.push(BookmarkedPage {
+            url: crypto::open(connection, url)?,
+            title: crypto::open_option(connection, title)?,
+            added,
+        });
+    }
+    Ok(out)
+}
+
+/// Star the page with the given url, the page is created if needed.
+/// Starring it again moves it to the top of the starred pages.
+pub fn star(connection: &Connection, url: &str) -> Result<()> {
+    let page_id = db::pages::fetch_or_create_id(connection, url, None)?;
+    diesel::replace_into(stars::table)
+        .values((
+            stars::dsl::page_id.eq(page_id),
+            stars::dsl::created.eq(date::now()),
+        ))
+        .execute(connection)?;
+    Ok(())
+}
+
+/// Remove the star of the page, returns false if the page was not starred.
+pub fn unstar(connection: &Connection, url: &str) -> Result<bool> {
+    let page_id = match db::pages::fetch_id(connection, url)? {
+        Some(id) => id,
+        None => return Ok(false),
+    };
+    let deleted =
+        diesel::delete(stars::table.filter(stars::dsl::page_id.eq(page_id))).execute(connection)?;
+    Ok(deleted > 0)
+}
+
+/// The starred pages, most recent first.
+pub fn fetch_starred(connection: &Connection) -> Result<Vec<BookmarkedPage>> {
+    let entries = stars::table
+        .inner_join(pages::table)
+        .select((
+            pages::dsl::normalized_url,
+            pages::dsl::title,
+            stars::dsl::created,
+        ))
+        .order(stars::dsl::created.desc())
+        .load::<(String, Option<String>, String)>(connection)?;
+    to_bookmarked(connection, entries)
+}
+
+/// The ids of the starred pages.
+pub fn starred_page_ids(connection: &Connection) -> Result<HashSet<i32>> {
+    Ok(stars::table
+        .select(stars::dsl::page_id)
+        .load::<i32>(connection)?
+        .into_iter()
+        .collect())
+}
+
+/// The normalized urls of the starred pages, to be checked with `tags::contains_url`.
+pub fn starred_urls(connection: &Connection) -> Result<HashSet<String>> {
+    let entries = stars::table
+        .inner_join(pages::table)
+        .select(pages::dsl::normalized_url)
+        .load::<String>(connection)?;
+    let mut out = HashSet::with_capacity(entries.len());
+    for url in entries {
+        out.insert(crypto::open(connection, url)?);
+    }
+    Ok(out)
+}
+
+/// The normalized urls of the pages starred or in a reading list, never pruned.
+pub fn kept_urls(connection: &Connection) -> Result<HashSet<String>> {
+    let mut out = starred_urls(connection)?;
+    let listed = reading_list_pages::table
+        .inner_join(pages::table)
+        .select(pages::dsl::normalized_url)
+        .load::<String>(connection)?;
+    for url in listed {
+        out.insert(crypto::open(connection, url)?);
+    }
+    Ok(out)
+}
+
+/// Move the starred pages ahead of the other search results, keeping their order.
+pub fn boost(matches: &mut Vec<PageContent>, starred: &HashSet<String>) {
+    matches.sort_by_key(|m| !db::tags::contains_url(starred, &m.url));
+}
+
+/// Fetch the id of the reading list, if present.
+pub fn fetch_list_id(connection: &Connection, name: &str) -> Result<Option<i32>> {
+    let existing = reading_lists::table
+        .filter(reading_lists::dsl::name.eq(name))
+        .select(reading_lists::dsl::id)
+        .load::<Option<i32>>(connection)?;
+    Ok(existing.into_iter().next().and_then(|a| a))
+}
+
+fn fetch_or_create_list_id(connection: &Connection, name: &str) -> Result<i32> {
+    let name = normalize_list_name(name)?;
+    match fetch_list_id(connection, &name)? {
+        Some(existing) => Ok(existing),
+        None => {
+            diesel::insert_into(reading_lists::table)
+                .values((
+                    reading_lists::dsl::name.eq(&name),
+                    reading_lists::dsl::created.eq(date::now()),
+                ))
+                .execute(connection)?;
+            match fetch_list_id(connection, &name)? {
+                Some(id) => Ok(id),
+                None => Err("did not get id after inserting reading list".into()),
+            }
+        }
+    }
+}
+
+/// All the reading lists with their number of pages, sorted by name.
+pub fn fetch_lists(connection: &Connection) -> Result<Vec<ReadingList>> {
+    let all = reading_lists::table
+        .select((reading_lists::dsl::id, reading_lists::dsl::name))
+        .order(reading_lists::dsl::name.asc())
+        .load::<(Option<i32>, String)>(connection)?;
+    let mut out = Vec::with_capacity(all.len());
+    for (id, name) in all {
+        let pages: i64 = reading_list_pages::table
+            .filter(reading_list_pages::dsl::list_id.eq(id.unwrap_or_default()))
+            .count()
+            .get_result(connection)?;
+        out.push(ReadingList {
+            name,
+            pages: pages as usize,
+        });
+    }
+    Ok(out)
+}
+
+/// Add the page with the given url to the reading list, both are created if needed.
+/// Adding it again moves it to the end of the list.
+pub fn add_to_list(connection: &Connection, name: &str, url: &str) -> Result<()> {
+    let list_id = fetch_or_create_list_id(connection, name)?;
+    let page_id = db::pages::fetch_or_create_id(connection, url, None)?;
+    diesel::replace_into(reading_list_pages::table)
+        .values((
+            reading_list_pages::dsl::list_id.eq(list_id),
+            reading_list_pages::dsl::page_id.eq(page_id),
+            reading_list_pages::dsl::added.eq(date::now()),
+        ))
+        .execute(connection)?;
+    Ok(())
+}
+
+/// Remove the page from the reading list, returns false if it was not in the list.
+pub fn remove_from_list(connection: &Connection, name: &str, url: &str) -> Result<bool> {
+    let list_id = match fetch_list_id(connection, &normalize_list_name(name)?)? {
+        Some(id) => id,
+        None => return Ok(false),
+    };
+    let page_id = match db::pages::fetch_id(connection, url)? {
+        Some(id) => id,
+        None => return Ok(false),
+    };
+    let deleted = diesel::delete(
+        reading_list_pages::table
+            .filter(reading_list_pages::dsl::list_id.eq(list_id))
+            .filter(reading_list_pages::dsl::page_id.eq(page_id)),
+    )
+    .execute(connection)?;
+    Ok(deleted > 0)
+}
+
+/// Delete the reading list and its entries, the pages are kept.
+/// Returns false if there was no such list.
+pub fn delete_list(connection: &Connection, name: &str) -> Result<bool> {
+    use diesel::Connection as DieselConnection;
+
+    let list_id = match fetch_list_id(connection, &normalize_list_name(name)?)? {
+        Some(id) => id,
+        None => return Ok(false),
+    };
+    connection.transaction::<_, WeaverError, _>(|| {
+        diesel::delete(
+            reading_list_pages::table.filter(reading_list_pages::dsl::list_id.eq(list_id)),
+        )
+        .execute(connection)?;
+        diesel::delete(reading_lists::table.filter(reading_lists::dsl::id.eq(list_id)))
+            .execute(connection)?;
+        Ok(true)
+    })
+}
+
+/// The pages of the reading list, oldest first, none if there is no such list.
+pub fn list_pages(connection: &Connection, name: &str) -> Result<Option<Vec<BookmarkedPage>>> {
+    let list_id = match fetch_list_id(connection, &normalize_list_name(name)?)? {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+    let entries = reading_list_pages::table
+        .inner_join(pages::table)
+        .filter(reading_list_pages::dsl::list_id.eq(list_id))
+        .select((
+            pages::dsl::normalized_url,
+            pages::dsl::title,
+            reading_list_pages::dsl::added,
+        ))
+        .order(reading_list_pages::dsl::added.asc())
+        .load::<(String, Option<String>, String)>(connection)?;
+    to_bookmarked(connection, entries).map(Some)
+}
+
+/// Delete the stars and reading list entries of the deleted pages.
+pub(crate) fn delete_for_pages(connection: &Connection, ids: &[i32]) -> Result<usize> {
+    let mut count = 0;
+    for chunk in ids.chunks(CHUNK) {
+        count += diesel::delete(stars::table.filter(stars::dsl::page_id.eq_any(chunk)))
+            .execute(connection)?;
+        count += diesel::delete(
+            reading_list_pages::table.filter(reading_list_pages::dsl::page_id.eq_any(chunk)),
+        )
+        .execute(connection)?;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::SqlStoreInMemory;
+    use crate::SqlProvider;
+
+    #[test]
+    fn test_star() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        star(&connection, "https://wiki/runbook").unwrap();
+        star(&connection, "https://wiki/runbook").unwrap();
+        let starred = fetch_starred(&connection).unwrap();
+        assert_eq!(starred.len(), 1);
+        assert!(db::tags::contains_url(
+            &starred_urls(&connection).unwrap(),
+            "https://wiki/runbook"
+        ));
+
+        let mut matches = vec!["https://wiki/other", "https://wiki/runbook"]
+            .into_iter()
+            .map(|url| PageContent {
+                url: url.into(),
+                ..PageContent::default()
+            })
+            .collect::<Vec<PageContent>>();
+        boost(&mut matches, &starred_urls(&connection).unwrap());
+        assert_eq!(matches[0].url, "https://wiki/runbook");
+
+        assert!(unstar(&connection, "https://wiki/runbook").unwrap());
+        assert!(!unstar(&connection, "https://wiki/runbook").unwrap());
+        assert!(fetch_starred(&connection).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reading_lists() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        add_to_list(&connection, " Later ", "https://blog/a").unwrap();
+        add_to_list(&connection, "Later", "https://blog/b").unwrap();
+        assert!(add_to_list(&connection, " ", "https://blog/c").is_err());
+
+        assert_eq!(
+            fetch_lists(&connection).unwrap(),
+            vec![ReadingList {
+                name: "Later".into(),
+                pages: 2
+            }]
+        );
+        let pages = list_pages(&connection, "Later").unwrap().unwrap();
+        assert_eq!(pages.len(), 2);
+        assert!(list_pages(&connection, "Other").unwrap().is_none());
+        assert_eq!(kept_urls(&connection).unwrap().len(), 2);
+
+        assert!(remove_from_list(&connection, "Later", "https://blog/a").unwrap());
+        assert!(!remove_from_list(&connection, "Later", "https://blog/a").unwrap());
+        assert!(delete_list(&connection, "Later").unwrap());
+        assert!(fetch_lists(&connection).unwrap().is_empty());
+        assert!(kept_urls(&connection).unwrap().is_empty());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::backends::schema::commands;
+use crate::crypto;
 use crate::db;
 use diesel;
 use diesel::prelude::*;
@@ -7,11 +8,15 @@ use crate::Connection;
 
 /// Fetch the id for the given epic, if present.
 pub fn fetch_id(connection: &Connection, kind: &str, command: &str) -> Result<Option<i32>> {
-    let existing = commands::dsl::commands
-        .filter(commands::dsl::command.eq(&command))
+    let mut query = commands::dsl::commands
         .filter(commands::dsl::kind.eq(&kind))
         .select(commands::dsl::id)
-        .load::<Option<i32>>(connection)?;
+        .into_boxed();
+    query = match crypto::blind_index(connection, command) {
+        Some(bidx) => query.filter(commands::dsl::command_bidx.eq(bidx)),
+        None => query.filter(commands::dsl::command.eq(command)),
+    };
+    let existing = query.load::<Option<i32>>(connection)?;
     Ok(existing.iter().next().map(|a| a.expect("must have id")))
 }
 
@@ -30,13 +35,17 @@ pub fn fetch_or_create_id(connection: &Connection, kind: &str, command: &str) ->
             let page_id = db::pages::fetch_id(connection, command)?;
             diesel::insert_into(commands::table)
                 .values((
-                    commands::dsl::command.eq(command),
+                    commands::dsl::command.eq(crypto::seal(connection, command)),
+                    commands::dsl::command_bidx.eq(crypto::blind_index(connection, command)),
                     commands::dsl::kind.eq(kind),
                     commands::dsl::page_id.eq(page_id),
                 )).execute(connection)?;
             match fetch_id(connection, kind, command) {
                 Err(e) => Err(e),
-                Ok(Some(id)) => Ok(id),
+                Ok(Some(id)) => {
+                    crypto::index_words(connection, crypto::COMMAND_WORDS, id, command)?;
+                    Ok(id)
+                }
                 Ok(None) => Err("did not get id after inserting location".into()),
             }
         }
@@ -49,7 +58,10 @@ pub fn fetch_commands(connection: &Connection) -> Result<Vec<String>> {
     let entries = commands::dsl::commands
         .select(commands::dsl::command)
         .load::<String>(connection)?;
-    Ok(entries)
+    entries
+        .into_iter()
+        .map(|e| crypto::open(connection, e))
+        .collect()
 }
 
 // Re-link all the `commands` that are urls to their optional entry in `pages`.
@@ -62,6 +74,7 @@ pub fn link_pages(connection: &Connection) -> Result<()> {
         )).filter(commands::dsl::kind.eq(&"url"))
         .load::<(String, Option<i32>, Option<i32>)>(connection)?
     {
+        let url = crypto::open(connection, url)?;
         println!("looking for page of {}", url);
         if let Some(connect_id) = db::pages::fetch_id(connection, &url)? {
             if page_id.is_none() || page_id.unwrap() != connect_id {
@@ -0,0 +1,228 @@
+//! Remove every trace of the commands and pages matching a pattern.
+#![allow(proc_macro_derive_resolution_fallback)]
+use crate::backends::schema::*;
+use crate::crypto;
+use crate::db::{bookmarks, replication, sessions, tags, CHUNK};
+use crate::Connection;
+use diesel;
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Text};
+use lib_error::*;
+use lib_goo::config::net::ForgetReport;
+use lib_goo::normalize;
+
+sql_function!(fn instr(haystack: Text, needle: Text) -> Integer);
+
+type CommandRow = (Option<i32>, String, String, Option<i32>);
+
+// The commands containing the pattern, with their plain value. Sqlite matches the plain
+// values, the encrypted ones are randomized and are read by chunks to be matched once opened.
+fn matching_commands(connection: &Connection, pattern: &str) -> Result<Vec<CommandRow>> {
+    let columns = (
+        commands::dsl::id,
+        commands::dsl::kind,
+        commands::dsl::command,
+        commands::dsl::page_id,
+    );
+    let mut out = commands::table
+        .select(columns)
+        .filter(instr(commands::dsl::command, pattern).gt(0))
+        .filter(instr(commands::dsl::command, crypto::ENCRYPTED_PREFIX).ne(1))
+        .load::<CommandRow>(connection)?;
+    if connection.cipher().is_none() {
+        return Ok(out);
+    }
+    let mut last = 0;
+    loop {
+        let chunk = commands::table
+            .select(columns)
+            .filter(instr(commands::dsl::command, crypto::ENCRYPTED_PREFIX).eq(1))
+            .filter(commands::dsl::id.gt(last))
+            .order(commands::dsl::id)
+            .limit(CHUNK as i64)
+            .load::<CommandRow>(connection)?;
+        let done = chunk.len() < CHUNK;
+        for (id, kind, command, page_id) in chunk {
+            last = id.unwrap_or(last);
+            let command = crypto::open(connection, command)?;
+            if command.contains(pattern) {
+                out.push((id, kind, command, page_id));
+            }
+        }
+        if done {
+            return Ok(out);
+        }
+    }
+}
+
+// The pages whose normalized url contains the pattern, with their plain url, matched the
+// same way as the commands.
+fn matching_pages(connection: &Connection, pattern: &str) -> Result<Vec<(Option<i32>, String)>> {
+    let columns = (pages::dsl::id, pages::dsl::normalized_url);
+    let mut out = pages::table
+        .select(columns)
+        .filter(instr(pages::dsl::normalized_url, pattern).gt(0))
+        .filter(instr(pages::dsl::normalized_url, crypto::ENCRYPTED_PREFIX).ne(1))
+        .load::<(Option<i32>, String)>(connection)?;
+    if connection.cipher().is_none() {
+        return Ok(out);
+    }
+    let mut last = 0;
+    loop {
+        let chunk = pages::table
+            .select(columns)
+            .filter(instr(pages::dsl::normalized_url, crypto::ENCRYPTED_PREFIX).eq(1))
+            .filter(pages::dsl::id.gt(last))
+            .order(pages::dsl::id)
+            .limit(CHUNK as i64)
+            .load::<(Option<i32>, String)>(connection)?;
+        let done = chunk.len() < CHUNK;
+        for (id, normalized_url) in chunk {
+            last = id.unwrap_or(last);
+            let normalized_url = crypto::open(connection, normalized_url)?;
+            if normalized_url.contains(pattern) {
+                out.push((id, normalized_url));
+            }
+        }
+        if done {
+            return Ok(out);
+        }
+    }
+}
+
+/// Delete the actions, commands and pages matching the pattern. The pattern is matched
+/// as a substring of the command or of the normalized url of the page.
+///
+/// The counts are added to the report, together with the urls which should also be
+/// removed from the text index.
+pub fn forget(connection: &Connection, pattern: &str, report: &mut ForgetReport) -> Result<()> {
+    use diesel::Connection as DieselConnection;
+
+    if pattern.is_empty() {
+        return Err("refusing to forget with an empty pattern".into());
+    }
+
+    connection.transaction::<(), WeaverError, _>(|| {
+        let matching = matching_commands(connection, pattern)?;
+        let command_ids = matching
+            .iter()
+            .filter_map(|(id, _, _, _)| *id)
+            .collect::<Vec<i32>>();
+        let linked_ids = matching
+            .iter()
+            .filter_map(|(_, _, _, page_id)| *page_id)
+            .collect::<Vec<i32>>();
+        for (_, kind, command, _) in &matching {
+            if kind == "url" {
+                let url = normalize::normalize_url(command)
+                    .map(|u| u.into_owned())
+                    .unwrap_or_else(|_| command.clone());
+                report.urls.push(url);
+            }
+        }
+
+        // The matching pages and the pages of the matching commands.
+        let mut matching_pages = matching_pages(connection, pattern)?;
+        for chunk in linked_ids.chunks(CHUNK) {
+            for (id, normalized_url) in pages::table
+                .select((pages::dsl::id, pages::dsl::normalized_url))
+                .filter(pages::dsl::id.eq_any(chunk))
+                .load::<(Option<i32>, String)>(connection)?
+            {
+                matching_pages.push((id, crypto::open(connection, normalized_url)?));
+            }
+        }
+        let mut page_ids = Vec::new();
+        for (id, normalized_url) in matching_pages {
+            page_ids.extend(id);
+            report.urls.push(normalized_url);
+        }
+        page_ids.sort();
+        page_ids.dedup();
+
+        // Delete the actions first, then the rows they were referencing.
+        for chunk in command_ids.chunks(CHUNK) {
+            report.actions += diesel::delete(
+                actions2::table.filter(actions2::dsl::command_id.eq_any(chunk)),
+            )
+            .execute(connection)?;
+            report.commands +=
+                diesel::delete(commands::table.filter(commands::dsl::id.eq_any(chunk)))
+                    .execute(connection)?;
+        }
+        for chunk in page_ids.chunks(CHUNK) {
+            // Other commands may still point to the page, unlink them.
+            diesel::update(commands::table.filter(commands::dsl::page_id.eq_any(chunk)))
+                .set(commands::dsl::page_id.eq(None::<i32>))
+                .execute(connection)?;
+            bookmarks::delete_for_pages(connection, chunk)?;
+            replication::delete_page_copies(connection, chunk)?;
+            report.pages += diesel::delete(pages::table.filter(pages::dsl::id.eq_any(chunk)))
+                .execute(connection)?;
+        }
+
+        tags::delete_orphans(connection)?;
+        sessions::delete_orphans(connection)?;
+
+        report.urls.sort();
+        report.urls.dedup();
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{actions2, pages};
+    use crate::test_helpers::SqlStoreInMemory;
+    use crate::SqlProvider;
+    use lib_goo::config::net::Pagination;
+    use lib_goo::entities::NewAction;
+
+    fn new_action(kind: &str, command: &str) -> NewAction {
+        NewAction {
+            kind: kind.into(),
+            command: command.into(),
+            ..NewAction::default()
+        }
+    }
+
+    #[test]
+    fn test_forget() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        pages::fetch_or_create_id(&connection, "https://secret.example/a", Some("a"))
+            .expect("insert page");
+        for (kind, command) in vec![
+            ("url", "https://secret.example/a"),
+            ("url", "https://secret.example/a"),
+            ("url", "https://public.example/b"),
+            ("shell", "ls"),
+        ] {
+            actions2::insert(&connection, &new_action(kind, command)).expect("insert");
+        }
+
+        let mut report = ForgetReport::default();
+        forget(&connection, "secret", &mut report).expect("forget");
+        assert_eq!(report.actions, 2);
+        assert_eq!(report.commands, 1);
+        assert_eq!(report.pages, 1);
+        assert_eq!(report.urls, vec![String::from("https://secret.example/a")]);
+
+        let left = actions2::fetch(&connection, None, None, &Pagination::default()).expect("fetch");
+        assert_eq!(
+            left.iter().map(|a| a.name.as_str()).collect::<Vec<&str>>(),
+            vec!["https://public.example/b", "ls"]
+        );
+    }
+
+    #[test]
+    fn test_forget_empty_pattern() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        let mut report = ForgetReport::default();
+        assert!(forget(&connection, "", &mut report).is_err());
+    }
+}
@@ -1,9 +1,20 @@
 //! Actual database read and write functions.
 
 pub mod actions2;
+pub mod api_tokens;
+pub mod bookmarks;
 pub mod commands;
 pub mod epics;
+pub mod forget;
 pub mod pages;
+pub mod replication;
+pub mod sessions;
+pub mod stats;
+pub mod suggestions;
+pub mod tags;
 mod hosts;
 pub mod locations;
 pub mod url_restrictions;
+
+/// Sqlite limits the number of variables in one statement, large deletes are done in chunks.
+pub(crate) const CHUNK: usize = 500;
@@ -1,4 +1,5 @@
 use crate::backends::schema::pages;
+use crate::crypto;
 use diesel;
 use diesel::prelude::*;
 use lib_error::*;
@@ -8,10 +9,12 @@ use crate::Connection;
 /// Fetch the id for the given page, if present.
 pub fn fetch_id(connection: &Connection, url: &str) -> Result<Option<i32>> {
     let normalized_url = normalize::normalize_url(url)?;
-    let existing = pages::dsl::pages
-        .filter(pages::dsl::normalized_url.eq(&normalized_url))
-        .select(pages::dsl::id)
-        .load::<Option<i32>>(connection)?;
+    let query = pages::dsl::pages.select(pages::dsl::id).into_boxed();
+    let query = match crypto::blind_index(connection, &normalized_url) {
+        Some(bidx) => query.filter(pages::dsl::normalized_url_bidx.eq(bidx)),
+        None => query.filter(pages::dsl::normalized_url.eq(&*normalized_url)),
+    };
+    let existing = query.load::<Option<i32>>(connection)?;
     Ok(existing.iter().next().map(|a| a.expect("must have id")))
 }
 
@@ -23,13 +26,20 @@ pub fn fetch_or_create_id(connection: &Connection, url: &str, title: Option<&str
         None => {
             diesel::insert_into(pages::table)
                 .values((
-                    pages::dsl::normalized_url.eq(&normalized_url),
-                    pages::dsl::title.eq(title),
+                    pages::dsl::normalized_url.eq(crypto::seal(connection, &normalized_url)),
+                    pages::dsl::normalized_url_bidx
+                        .eq(crypto::blind_index(connection, &normalized_url)),
+                    pages::dsl::title.eq(title.map(|t| crypto::seal(connection, t))),
                 ))
                 .execute(connection)?;
             match fetch_id(connection, &normalized_url) {
                 Err(e) => Err(e),
-                Ok(Some(id)) => Ok(id),
+                Ok(Some(id)) => {
+                    if let Some(title) = title {
+                        crypto::index_words(connection, crypto::TITLE_WORDS, id, title)?;
+                    }
+                    Ok(id)
+                }
                 Ok(None) => Err(WeaverErrorKind::Generic("did not get id after inserting location").into()),
             }
         }
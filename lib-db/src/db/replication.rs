@@ -0,0 +1,412 @@
+//! Replication between servers, the `sent` flag of the actions marks the ones pushed to the peer.
+//!
+//! The actions received from a peer are saved as sent, they are not pushed back. The receiving
+//! side skips the actions it already has, with the same host, execution time and command.
+//! The page contents have their own `sent` flag in `page_copies`, next to the repo document
+//! holding the newest content of each page.
+#![allow(proc_macro_derive_resolution_fallback)]
+use crate::backends::schema::*;
+use crate::crypto;
+use crate::db::{self, CHUNK};
+use crate::store_policies::Restrictions;
+use crate::Connection;
+use diesel;
+use diesel::prelude::*;
+use lib_error::*;
+use lib_goo::config::net::SyncAction;
+use lib_goo::entities::NewAction;
+
+/// The actions and page contents waiting to be pushed to the peer.
+#[derive(Debug, Default, PartialEq)]
+pub struct Backlog {
+    pub actions: usize,
+    /// Page contents saved in the repo, pushed when enabled.
+    pub pages: usize,
+    /// Execution time of the oldest unsent action.
+    pub oldest: Option<String>,
+}
+
+/// What was done with an action pushed by a peer.
+#[derive(Debug, PartialEq)]
+pub enum Received {
+    /// The action was saved with this id.
+    Inserted(u64),
+    /// The same action was already saved.
+    Duplicate,
+    /// The url of the action matches a do not log restriction.
+    Restricted,
+}
+
+/// Fetch the unsent actions, oldest first, with their ids, annotations and tags.
+pub fn unsent(connection: &Connection, limit: usize) -> Result<Vec<(i32, SyncAction)>> {
+    let entries = actions2::table
+        .inner_join(commands::table)
+        .left_join(locations::table)
+        .left_join(epics::table)
+        .left_join(hosts::table)
+        .filter(
+            actions2::dsl::sent
+                .eq(false)
+                .or(actions2::dsl::sent.is_null()),
+        )
+        .order(actions2::dsl::id.asc())
+        .limit(limit as i64)
+        .select((
+            (
+                actions2::dsl::id,
+                actions2::dsl::executed,
+                commands::dsl::kind,
+                commands::dsl::command,
+                locations::dsl::location.nullable(),
+                epics::dsl::name.nullable(),
+                hosts::dsl::name.nullable(),
+            ),
+            (
+                actions2::dsl::annotation,
+                actions2::dsl::status,
+                actions2::dsl::parent_id,
+            ),
+        ))
+        .load::<(
+            (
+                Option<i32>,
+                String,
+                String,
+                String,
+                Option<String>,
+                Option<String>,
+                Option<String>,
+            ),
+            (Option<String>, Option<i32>, Option<String>),
+        )>(connection)?;
+    let ids: Vec<i32> = entries.iter().filter_map(|((id, ..), _)| *id).collect();
+    let mut tags = db::tags::for_actions(connection, &ids)?;
+    let mut transitions = db::sessions::transitions(connection, &ids)?;
+    let mut out = Vec::with_capacity(entries.len());
+    for ((id, executed, kind, command, location, epic, host), (annotation, status, parent_id)) in
+        entries
+    {
+        let id = match id {
+            Some(id) => id,
+            None => continue,
+        };
+        let action = NewAction {
+            executed,
+            kind,
+            command: crypto::open(connection, command)?,
+            location,
+            epic,
+            host: host.unwrap_or_default(),
+            parent_id,
+            status_code: status.map(|s| s.to_string()),
+        };
+        out.push((
+            id,
+            SyncAction {
+                action,
+                annotation: crypto::open_option(connection, annotation)?.unwrap_or_default(),
+                tags: tags.remove(&id).unwrap_or_default(),
+                transition: transitions.remove(&id),
+            },
+        ));
+    }
+    Ok(out)
+}
+
+/// Record the repo document with the newest content of the page of the url.
+///
+/// The pages received from the peer are saved as sent, they are not pushed back.
+pub fn save_page_copy(connection: &Connection, url: &str, repo_id: &str, sent: bool) -> Result<()> {
+    let page_id = db::pages::fetch_or_create_id(connection, url, None)?;
+    diesel::replace_into(page_copies::table)
+        .values((
+            page_copies::dsl::page_id.eq(page_id),
+            page_copies::dsl::repo_id.eq(repo_id),
+            page_copies::dsl::sent.eq(sent),
+        ))
+        .execute(connection)?;
+    Ok(())
+}
+
+/// Check if the content of the page of the url is saved in the repo.
+pub fn has_page_copy(connection: &Connection, url: &str) -> Result<bool> {
+    let page_id = match db::pages::fetch_id(connection, url)? {
+        Some(id) => id,
+        None => return Ok(false),
+    };
+    let found: i64 = page_copies::table
+        .filter(page_copies::dsl::page_id.eq(page_id))
+        .count()
+        .get_result(connection)?;
+    Ok(found > 0)
+}
+
+/// Fetch the page ids and repo documents of the unsent page contents, oldest pages first.
+pub fn unsent_pages(connection: &Connection, limit: usize) -> Result<Vec<(i32, String)>> {
+    let entries = page_copies::table
+        .filter(page_copies::dsl::sent.eq(false))
+        .order(page_copies::dsl::page_id.asc())
+        .limit(limit as i64)
+        .select((page_copies::dsl::page_id, page_copies::dsl::repo_id))
+        .load::<(Option<i32>, String)>(connection)?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|(id, repo_id)| id.map(|id| (id, repo_id)))
+        .collect())
+}
+
+/// Delete the page copies of the forgotten pages.
+pub(crate) fn delete_page_copies(connection: &Connection, page_ids: &[i32]) -> Result<usize> {
+    let mut count = 0;
+    for chunk in page_ids.chunks(CHUNK) {
+        count += diesel::delete(page_copies::table.filter(page_copies::dsl::page_id.eq_any(chunk)))
+            .execute(connection)?;
+    }
+    Ok(count)
+}
+
+/// Mark the page contents as pushed to the peer, return the number of updated pages.
+pub fn mark_pages_sent(connection: &Connection, page_ids: &[i32]) -> Result<usize> {
+    let mut count = 0;
+    for chunk in page_ids.chunks(CHUNK) {
+        count += diesel::update(page_copies::table.filter(page_copies::dsl::page_id.eq_any(chunk)))
+            .set(page_copies::dsl::sent.eq(true))
+            .execute(connection)?;
+    }
+    Ok(count)
+}
+
+/// Mark the actions as pushed to the peer, return the number of updated actions.
+pub fn mark_sent(connection: &Connection, ids: &[i32]) -> Result<usize> {
+    let mut count = 0;
+    for chunk in ids.chunks(CHUNK) {
+        count += diesel::update(actions2::table.filter(actions2::dsl::id.eq_any(chunk)))
+            .set(actions2::dsl::sent.eq(true))
+            .execute(connection)?;
+    }
+    Ok(count)
+}
+
+/// Describe the actions and pages waiting to be pushed.
+pub fn backlog(connection: &Connection) -> Result<Backlog> {
+    let pending = actions2::dsl::sent
+        .eq(false)
+        .or(actions2::dsl::sent.is_null());
+    let actions: i64 = actions2::table
+        .filter(pending.clone())
+        .count()
+        .get_result(connection)?;
+    let pages: i64 = page_copies::table
+        .filter(page_copies::dsl::sent.eq(false))
+        .count()
+        .get_result(connection)?;
+    let oldest = actions2::table
+        .filter(pending)
+        .select(actions2::dsl::executed)
+        .order(actions2::dsl::executed.asc())
+        .first::<String>(connection)
+        .optional()?;
+    Ok(Backlog {
+        actions: actions as usize,
+        pages: pages as usize,
+        oldest,
+    })
+}
+
+/// Check if an action with the same host, execution time and command was already saved.
+pub fn is_duplicate(connection: &Connection, action: &NewAction) -> Result<bool> {
+    let command_id = match db::commands::fetch_id(connection, &action.kind, &action.command)? {
+        Some(id) => id,
+        None => return Ok(false),
+    };
+    let host_id = match db::hosts::fetch_id(connection, &action.host)? {
+        Some(id) => id,
+        None => return Ok(false),
+    };
+    let found: i64 = actions2::table
+        .filter(actions2::dsl::command_id.eq(command_id))
+        .filter(actions2::dsl::host_id.eq(host_id))
+        .filter(actions2::dsl::executed.eq(&action.executed))
+        .count()
+        .get_result(connection)?;
+    Ok(found > 0)
+}
+
+/// Save an action pushed by a peer with its annotation and tags, unless already present or
+/// restricted by the url policies of this server.
+pub fn receive(
+    connection: &Connection,
+    restrictions: &Restrictions,
+    received: &SyncAction,
+) -> Result<Received> {
+    use diesel::Connection as DieselConnection;
+
+    let action = &received.action;
+    if action.kind == "url" && !restrictions.should_log_url(&action.command) {
+        return Ok(Received::Restricted);
+    }
+    if is_duplicate(connection, action)? {
+        return Ok(Received::Duplicate);
+    }
+    connection.transaction::<_, WeaverError, _>(|| {
+        let id = db::actions2::insert(connection, action)?;
+        if !received.annotation.is_empty() {
+            db::actions2::set_annotation(connection, id, &received.annotation)?;
+        }
+        for tag in &received.tags {
+            db::tags::add_to_action(connection, id, tag)?;
+        }
+        let transition = received.transition.as_ref().map(|t| t.as_str());
+        db::sessions::record_navigation(connection, id, transition, None, None)?;
+        mark_sent(connection, &[id as i32])?;
+        Ok(Received::Inserted(id))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store_policies::DocumentMatcher;
+    use crate::test_helpers::SqlStoreInMemory;
+    use crate::SqlProvider;
+    use regex::Regex;
+
+    fn action(command: &str, executed: &str) -> NewAction {
+        NewAction {
+            kind: "shell".into(),
+            command: command.into(),
+            executed: executed.into(),
+            host: "laptop".into(),
+            epic: Some("sync".into()),
+            status_code: Some("2".into()),
+            parent_id: Some("42".into()),
+            ..NewAction::default()
+        }
+    }
+
+    fn synced(action: NewAction) -> SyncAction {
+        SyncAction {
+            action,
+            ..SyncAction::default()
+        }
+    }
+
+    #[test]
+    fn test_push_and_receive() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        let restrictions = Restrictions::default();
+
+        let id = db::actions2::insert(&connection, &action("ls", "2019-01-01T10:00:00Z")).unwrap();
+        db::actions2::set_annotation(&connection, id, "list").unwrap();
+        db::tags::add_to_action(&connection, id, "files").unwrap();
+        db::actions2::insert(&connection, &action("pwd", "2019-01-02T10:00:00Z")).unwrap();
+        let waiting = backlog(&connection).unwrap();
+        assert_eq!(waiting.actions, 2);
+        assert_eq!(waiting.pages, 0);
+        assert_eq!(waiting.oldest, Some("2019-01-01T10:00:00Z".into()));
+
+        let pending = unsent(&connection, 1).unwrap();
+        assert_eq!(pending.len(), 1);
+        let (id, ref first) = pending[0];
+        assert_eq!(first.action.command, "ls");
+        assert_eq!(first.action.host, "laptop");
+        assert_eq!(first.action.epic, Some("sync".into()));
+        assert_eq!(first.action.status_code, Some("2".into()));
+        assert_eq!(first.action.parent_id, Some("42".into()));
+        assert_eq!(first.annotation, "list");
+        assert_eq!(first.tags, vec!["files".to_string()]);
+        assert_eq!(mark_sent(&connection, &[id]).unwrap(), 1);
+        assert_eq!(backlog(&connection).unwrap().actions, 1);
+
+        // The same action is only saved once, the received actions are not pushed back.
+        assert_eq!(
+            receive(
+                &connection,
+                &restrictions,
+                &synced(action("ls", "2019-01-01T10:00:00Z"))
+            )
+            .unwrap(),
+            Received::Duplicate
+        );
+        let received = SyncAction {
+            action: action("ls", "2019-01-03T10:00:00Z"),
+            annotation: "again".into(),
+            tags: vec!["files".into()],
+            transition: None,
+        };
+        let id = match receive(&connection, &restrictions, &received).unwrap() {
+            Received::Inserted(id) => id,
+            other => panic!("not inserted {:?}", other),
+        };
+        let saved = db::actions2::fetch_by_id(&connection, id)
+            .unwrap()
+            .expect("saved");
+        assert_eq!(saved.annotation, Some("again".into()));
+        assert_eq!(
+            db::tags::for_actions(&connection, &[id as i32]).unwrap()[&(id as i32)],
+            vec!["files".to_string()]
+        );
+        let pending = unsent(&connection, 10).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].1.action.command, "pwd");
+    }
+
+    #[test]
+    fn test_receive_restricted() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        let restrictions = Restrictions {
+            do_not_log: vec![DocumentMatcher {
+                url: Some(Regex::new("private").unwrap()),
+                title: None,
+                body: None,
+            }],
+            ..Restrictions::default()
+        };
+
+        let visit = NewAction::build_from_url("https://private.example/", None).unwrap();
+        assert_eq!(
+            receive(&connection, &restrictions, &synced(visit)).unwrap(),
+            Received::Restricted
+        );
+        assert_eq!(db::actions2::count(&connection).unwrap(), 0);
+
+        let visit = SyncAction {
+            action: NewAction::build_from_url("https://public.example/", None).unwrap(),
+            transition: Some("typed".into()),
+            ..SyncAction::default()
+        };
+        let id = match receive(&connection, &restrictions, &visit).unwrap() {
+            Received::Inserted(id) => id as i32,
+            other => panic!("not inserted {:?}", other),
+        };
+        let transitions = db::sessions::transitions(&connection, &[id]).unwrap();
+        assert_eq!(transitions[&id], "typed");
+    }
+
+    #[test]
+    fn test_page_copies() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        // A starred page has a row without a saved content.
+        db::pages::fetch_or_create_id(&connection, "https://starred.example/", Some("starred"))
+            .unwrap();
+        assert!(!has_page_copy(&connection, "https://starred.example/").unwrap());
+
+        save_page_copy(&connection, "https://saved.example/", "first", false).unwrap();
+        save_page_copy(&connection, "https://saved.example/", "second", false).unwrap();
+        save_page_copy(&connection, "https://received.example/", "peer", true).unwrap();
+        assert!(has_page_copy(&connection, "https://saved.example/").unwrap());
+        assert_eq!(backlog(&connection).unwrap().pages, 1);
+
+        let pages = unsent_pages(&connection, 10).unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].1, "second");
+        assert_eq!(mark_pages_sent(&connection, &[pages[0].0]).unwrap(), 1);
+        assert!(unsent_pages(&connection, 10).unwrap().is_empty());
+    }
+}
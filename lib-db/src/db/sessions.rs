@@ -0,0 +1,377 @@
+//! Browsing sessions: the transition, tab and referrer of the url actions and the focus of the
+//! pages.
+//!
+//! The rows are keyed by action id, they are deleted with the actions. Referrers are stored
+//! as the most recent earlier action for the referrer url, urls which are not logged leave
+//! a hole in the trail.
+#![allow(proc_macro_derive_resolution_fallback)]
+use crate::backends::schema::*;
+use crate::db::{self, CHUNK};
+use crate::Connection;
+use chrono::DateTime;
+use diesel;
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Nullable, Text};
+use lib_error::*;
+use lib_goo::config::net::TrailStep;
+use lib_goo::date;
+use lib_goo::entities::NewAction;
+use std::collections::{HashMap, HashSet};
+
+/// A page focused longer than this without any other event was most likely left open.
+const MAX_DWELL_SECS: i64 = 30 * 60;
+
+/// Longest trail returned, the referrers can form long chains in a busy tab.
+const MAX_TRAIL: usize = 50;
+
+// Latest action for the url, before the given action when set.
+fn latest_action(connection: &Connection, url: &str, before: Option<i32>) -> Result<Option<i32>> {
+    let command_id = match db::commands::fetch_id(connection, "url", url)? {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+    let mut query = actions2::table
+        .select(actions2::dsl::id)
+        .filter(actions2::dsl::command_id.eq(command_id))
+        .order(actions2::dsl::id.desc())
+        .limit(1)
+        .into_boxed();
+    if let Some(before) = before {
+        query = query.filter(actions2::dsl::id.lt(before));
+    }
+    Ok(query
+        .load::<Option<i32>>(connection)?
+        .into_iter()
+        .next()
+        .and_then(|a| a))
+}
+
+/// Save the transition type, the tab and the referrer, or opener, of a url action.
+pub fn record_navigation(
+    connection: &Connection,
+    action_id: u64,
+    transition: Option<&str>,
+    tab_id: Option<i32>,
+    referrer: Option<&str>,
+) -> Result<()> {
+    let action_id = action_id as i32;
+    let referrer_action_id = match referrer.filter(|r| !r.is_empty()) {
+        Some(url) => latest_action(connection, url, Some(action_id))?,
+        None => None,
+    };
+    let transition = transition.filter(|t| !t.is_empty());
+    if transition.is_none() && tab_id.is_none() && referrer_action_id.is_none() {
+        return Ok(());
+    }
+    diesel::replace_into(navigations::table)
+        .values((
+            navigations::dsl::action_id.eq(action_id),
+            navigations::dsl::tab_id.eq(tab_id),
+            navigations::dsl::referrer_action_id.eq(referrer_action_id),
+            navigations::dsl::transition.eq(transition),
+        ))
+        .execute(connection)?;
+    Ok(())
+}
+
+/// Insert a url action with its transition type, tab and referrer, in one transaction.
+pub fn insert_visit(
+    connection: &Connection,
+    action: &NewAction,
+    transition: Option<&str>,
+    tab_id: Option<i32>,
+    referrer: Option<&str>,
+) -> Result<u64> {
+    use diesel::Connection as DieselConnection;
+
+    connection.transaction::<u64, WeaverError, _>(|| {
+        let id = db::actions2::insert(connection, action)?;
+        record_navigation(connection, id, transition, tab_id, referrer)?;
+        Ok(id)
+    })
+}
+
+/// Save a focus or blur event of the url displayed in the tab.
+/// Returns the action the event was attached to, none when the url was not logged.
+pub fn record_focus(
+    connection: &Connection,
+    url: &str,
+    tab_id: i32,
+    kind: &str,
+) -> Result<Option<i32>> {
+    if kind != "focus" && kind != "blur" {
+        return Err(WeaverError::from(format!(
+            "unknown event {}, expected focus or blur",
+            kind
+        )));
+    }
+    let in_tab = match db::commands::fetch_id(connection, "url", url)? {
+        Some(command_id) => navigations::table
+            .inner_join(actions2::table)
+            .select(navigations::dsl::action_id)
+            .filter(navigations::dsl::tab_id.eq(tab_id))
+            .filter(actions2::dsl::command_id.eq(command_id))
+            .order(navigations::dsl::action_id.desc())
+            .limit(1)
+            .load::<i32>(connection)?
+            .into_iter()
+            .next(),
+        None => None,
+    };
+    let action_id = match in_tab {
+        Some(id) => id,
+        None => match latest_action(connection, url, None)? {
+            Some(id) => id,
+            None => return Ok(None),
+        },
+    };
+    diesel::insert_into(focus_events::table)
+        .values((
+            focus_events::dsl::action_id.eq(action_id),
+            focus_events::dsl::kind.eq(kind),
+            focus_events::dsl::happened.eq(date::now()),
+        ))
+        .execute(connection)?;
+    Ok(Some(action_id))
+}
+
+fn seconds_between(start: &str, end: &str) -> Option<i64> {
+    let start = DateTime::parse_from_rfc3339(start).ok()?;
+    let end = DateTime::parse_from_rfc3339(end).ok()?;
+    Some((end - start).num_seconds())
+}
+
+// A focus event with the time of the event following it, on any page.
+#[derive(QueryableByName)]
+struct Focused {
+    #[sql_type = "Integer"]
+    action_id: i32,
+    #[sql_type = "Text"]
+    happened: String,
+    #[sql_type = "Nullable<Text>"]
+    next: Option<String>,
+}
+
+/// Seconds each action was focused: from a focus event to the next event of any page,
+/// capped to ignore the pages left open.
+pub fn dwell_times(connection: &Connection, ids: &[i32]) -> Result<HashMap<i32, i64>> {
+    let mut out = HashMap::new();
+    for chunk in ids.chunks(CHUNK) {
+        let listed = chunk
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+        // The events are stamped by the server, the ids follow the time.
+        let focused = diesel::sql_query(format!(
+            "SELECT f.action_id, f.happened, \
+             (SELECT n.happened FROM focus_events n WHERE n.id > f.id ORDER BY n.id LIMIT 1) \
+             AS next \
+             FROM focus_events f WHERE f.kind = 'focus' AND f.action_id IN ({}) ORDER BY f.id",
+            listed
+        ))
+        .load::<Focused>(connection)?;
+        for event in focused {
+            let happened = event.happened;
+            let seconds = event
+                .next
+                .and_then(|end| seconds_between(&happened, &end))
+                .map(|s| s.max(0).min(MAX_DWELL_SECS))
+                .unwrap_or_default();
+            *out.entry(event.action_id).or_insert(0) += seconds;
+        }
+    }
+    Ok(out)
+}
+
+// The transition, tab and referrer of the action, if known.
+fn navigation(
+    connection: &Connection,
+    action_id: i32,
+) -> Result<Option<(Option<String>, Option<i32>, Option<i32>)>> {
+    Ok(navigations::table
+        .select((
+            navigations::dsl::transition,
+            navigations::dsl::tab_id,
+            navigations::dsl::referrer_action_id,
+        ))
+        .filter(navigations::dsl::action_id.eq(action_id))
+        .load::<(Option<String>, Option<i32>, Option<i32>)>(connection)?
+        .into_iter()
+        .next())
+}
+
+/// The transition types of the url actions, when known.
+pub(crate) fn transitions(connection: &Connection, ids: &[i32]) -> Result<HashMap<i32, String>> {
+    let mut out = HashMap::new();
+    for chunk in ids.chunks(CHUNK) {
+        let entries = navigations::table
+            .select((navigations::dsl::action_id, navigations::dsl::transition))
+            .filter(navigations::dsl::action_id.eq_any(chunk))
+            .filter(navigations::dsl::transition.is_not_null())
+            .load::<(i32, Option<String>)>(connection)?;
+        out.extend(
+            entries
+                .into_iter()
+                .filter_map(|(id, transition)| transition.map(|t| (id, t))),
+        );
+    }
+    Ok(out)
+}
+
+/// The pages leading to the action through the referrers, oldest first, ending with the action.
+pub fn trail(connection: &Connection, action_id: u64) -> Result<Vec<TrailStep>> {
+    let mut chain = Vec::new();
+    let mut seen = HashSet::new();
+    let mut current = Some(action_id as i32);
+    while let Some(id) = current {
+        if chain.len() >= MAX_TRAIL || !seen.insert(id) {
+            break;
+        }
+        let (transition, tab_id, referrer) =
+            navigation(connection, id)?.unwrap_or((None, None, None));
+        chain.push((id, transition, tab_id));
+        current = referrer;
+    }
+
+    let ids = chain.iter().map(|(id, _, _)| *id).collect::<Vec<i32>>();
+    let dwell = dwell_times(connection, &ids)?;
+    let mut out = Vec::with_capacity(chain.len());
+    for (id, transition, tab_id) in chain.into_iter().rev() {
+        // The referrer may have been deleted since.
+        if let Some(action) = db::actions2::fetch_by_id(connection, id as u64)? {
+            out.push(TrailStep {
+                action,
+                transition,
+                tab_id,
+                dwell: dwell.get(&id).cloned().unwrap_or_default(),
+            });
+        }
+    }
+    Ok(out)
+}
+
+/// Delete the navigations and focus events of the actions.
+pub(crate) fn delete_for_actions(connection: &Connection, ids: &[i32]) -> Result<usize> {
+    let mut count = 0;
+    for chunk in ids.chunks(CHUNK) {
+        count +=
+            diesel::delete(navigations::table.filter(navigations::dsl::action_id.eq_any(chunk)))
+                .execute(connection)?;
+        count +=
+            diesel::delete(focus_events::table.filter(focus_events::dsl::action_id.eq_any(chunk)))
+                .execute(connection)?;
+    }
+    Ok(count)
+}
+
+/// Remove the navigations and focus events of actions which do not exist anymore.
+pub(crate) fn delete_orphans(connection: &Connection) -> Result<usize> {
+    let navigations = diesel::sql_query(
+        "DELETE FROM navigations WHERE action_id NOT IN \
+         (SELECT id FROM actions2 WHERE id IS NOT NULL)",
+    )
+    .execute(connection)?;
+    let events = diesel::sql_query(
+        "DELETE FROM focus_events WHERE action_id NOT IN \
+         (SELECT id FROM actions2 WHERE id IS NOT NULL)",
+    )
+    .execute(connection)?;
+    Ok(navigations + events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::actions2;
+    use crate::test_helpers::SqlStoreInMemory;
+    use crate::SqlProvider;
+    use lib_goo::entities::ActionId;
+
+    fn visit(connection: &Connection, url: &str, tab_id: i32, referrer: Option<&str>) -> u64 {
+        let action = NewAction::build_from_url(url, None).expect("action");
+        insert_visit(connection, &action, Some("link"), Some(tab_id), referrer).expect("visit")
+    }
+
+    #[test]
+    fn test_trail_and_dwell() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        let search = visit(&connection, "https://search.example.com/?q=rust", 1, None);
+        let docs = visit(
+            &connection,
+            "https://doc.rust-lang.org/",
+            1,
+            Some("https://search.example.com/?q=rust"),
+        );
+        // Opened in a new tab.
+        let book = visit(
+            &connection,
+            "https://doc.rust-lang.org/book/",
+            2,
+            Some("https://doc.rust-lang.org/"),
+        );
+
+        let steps = trail(&connection, book).expect("trail");
+        let ids = steps
+            .iter()
+            .map(|s| s.action.id.clone())
+            .collect::<Vec<ActionId>>();
+        let expected = [search, docs, book]
+            .iter()
+            .map(|id| ActionId::new(*id as usize))
+            .collect::<Vec<ActionId>>();
+        assert_eq!(ids, expected);
+        assert_eq!(steps[2].tab_id, Some(2));
+        assert_eq!(steps[2].transition, Some("link".into()));
+        assert_eq!(steps[2].action.location, None);
+
+        assert_eq!(
+            record_focus(&connection, "https://doc.rust-lang.org/", 1, "focus").expect("focus"),
+            Some(docs as i32)
+        );
+        record_focus(&connection, "https://doc.rust-lang.org/", 1, "blur").expect("blur");
+        assert_eq!(
+            record_focus(&connection, "https://unknown.example.com/", 1, "focus").expect("focus"),
+            None
+        );
+        assert!(record_focus(&connection, "https://doc.rust-lang.org/", 1, "scroll").is_err());
+
+        actions2::delete(&connection, &[docs as i32]).expect("delete");
+        let steps = trail(&connection, book).expect("trail");
+        assert_eq!(steps.len(), 1);
+        assert_eq!(delete_orphans(&connection).expect("orphans"), 0);
+    }
+
+    fn event(connection: &Connection, action_id: i32, kind: &str, happened: &str) {
+        diesel::insert_into(focus_events::table)
+            .values((
+                focus_events::dsl::action_id.eq(action_id),
+                focus_events::dsl::kind.eq(kind),
+                focus_events::dsl::happened.eq(happened),
+            ))
+            .execute(connection)
+            .expect("insert event");
+    }
+
+    #[test]
+    fn test_dwell_times() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        event(&connection, 1, "focus", "2019-01-01T10:00:00Z");
+        event(&connection, 1, "blur", "2019-01-01T10:02:30Z");
+        // Ended by the focus of another page, then left open.
+        event(&connection, 2, "focus", "2019-01-01T11:00:00Z");
+        event(&connection, 1, "focus", "2019-01-01T11:00:10Z");
+        event(&connection, 3, "focus", "2019-01-01T14:00:00Z");
+        // Still focused.
+        event(&connection, 2, "focus", "2019-01-01T15:00:00Z");
+
+        let dwell = dwell_times(&connection, &[1, 2, 4]).expect("dwell");
+        assert_eq!(dwell[&1], 150 + MAX_DWELL_SECS);
+        assert_eq!(dwell[&2], 10);
+        assert!(!dwell.contains_key(&4));
+    }
+}
@@ -0,0 +1,334 @@
+//! Activity statistics, aggregated by sqlite.
+//!
+//! The encrypted commands are grouped by id and only the labels are decrypted,
+//! the domains are extracted from the urls after decryption.
+#![allow(proc_macro_derive_resolution_fallback)]
+use crate::backends::schema::*;
+use crate::crypto;
+use crate::Connection;
+use diesel;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Integer, Text};
+use lib_error::*;
+use lib_goo::config::net::{NamedCount, Stats};
+use std::collections::HashMap;
+use url::Url;
+
+/// Default number of entries in the top lists.
+pub const DEFAULT_LIMIT: usize = 20;
+
+#[derive(QueryableByName)]
+struct LabelCount {
+    #[sql_type = "Text"]
+    name: String,
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
+#[derive(QueryableByName)]
+struct SlotCount {
+    #[sql_type = "Integer"]
+    slot: i32,
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
+#[derive(QueryableByName)]
+struct Total {
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
+// Restrict the actions, aliased as `a`, to the window.
+fn window_clause(days: Option<u32>) -> String {
+    match days {
+        Some(d) => format!("julianday(a.executed) >= julianday('now', '-{} days')", d),
+        None => "1".into(),
+    }
+}
+
+fn named(connection: &Connection, sql: &str) -> Result<Vec<NamedCount>> {
+    let rows = diesel::sql_query(sql).load::<LabelCount>(connection)?;
+    let mut out = Vec::with_capacity(rows.len());
+    for row in rows {
+        out.push(NamedCount {
+            name: crypto::open(connection, row.name)?,
+            count: row.count as usize,
+        });
+    }
+    Ok(out)
+}
+
+fn slots(connection: &Connection, sql: &str, size: usize) -> Result<Vec<usize>> {
+    let mut out = vec![0; size];
+    for row in diesel::sql_query(sql).load::<SlotCount>(connection)? {
+        if let Some(entry) = out.get_mut(row.slot as usize) {
+            *entry += row.count as usize;
+        }
+    }
+    Ok(out)
+}
+
+// Sum the counts of the urls by domain, keep the top entries.
+fn domains(urls: Vec<NamedCount>, limit: usize) -> Vec<NamedCount> {
+    let mut by_domain: HashMap<String, usize> = HashMap::new();
+    for url in urls {
+        if let Some(domain) = Url::parse(&url.name)
+            .ok()
+            .and_then(|u| u.host_str().map(String::from))
+        {
+            *by_domain.entry(domain).or_insert(0) += url.count;
+        }
+    }
+    top(
+        by_domain
+            .into_iter()
+            .map(|(name, count)| NamedCount { name, count })
+            .collect(),
+        limit,
+    )
+}
+
+// Sort by decreasing count then name, keep the first entries.
+fn top(mut all: Vec<NamedCount>, limit: usize) -> Vec<NamedCount> {
+    all.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    all.truncate(limit);
+    all
+}
+
+/// Compute the statistics over the actions of the last days, or all of them.
+pub fn compute(connection: &Connection, days: Option<u32>, limit: usize) -> Result<Stats> {
+    let window = window_clause(days);
+
+    let total = diesel::sql_query(format!(
+        "SELECT COUNT(*) AS count FROM actions2 a WHERE {}",
+        window
+    ))
+    .get_result::<Total>(connection)?;
+
+    let commands = named(
+        connection,
+        &format!(
+            "SELECT c.command AS name, COUNT(*) AS count FROM actions2 a \
+             JOIN commands c ON a.command_id = c.id \
+             WHERE c.kind = 'shell' AND {} \
+             GROUP BY c.id ORDER BY count DESC LIMIT {}",
+            window, limit
+        ),
+    )?;
+
+    let urls = named(
+        connection,
+        &format!(
+            "SELECT c.command AS name, COUNT(*) AS count FROM actions2 a \
+             JOIN commands c ON a.command_id = c.id \
+             WHERE c.kind = 'url' AND {} \
+             GROUP BY c.id",
+            window
+        ),
+    )?;
+
+    // The times are saved in RFC3339 with the offset of the client, use the local hour and day.
+    let hours = slots(
+        connection,
+        &format!(
+            "SELECT CAST(substr(a.executed, 12, 2) AS INTEGER) AS slot, COUNT(*) AS count \
+             FROM actions2 a WHERE length(a.executed) >= 13 AND {} GROUP BY slot",
+            window
+        ),
+        24,
+    )?;
+    let weekdays = slots(
+        connection,
+        &format!(
+            "SELECT CAST(strftime('%w', substr(a.executed, 1, 10)) AS INTEGER) AS slot, \
+             COUNT(*) AS count FROM actions2 a \
+             WHERE strftime('%w', substr(a.executed, 1, 10)) IS NOT NULL AND {} GROUP BY slot",
+            window
+        ),
+        7,
+    )?;
+
+    let directories = named(
+        connection,
+        &format!(
+            "SELECT l.location AS name, COUNT(*) AS count FROM actions2 a \
+             JOIN locations l ON a.location_id = l.id \
+             WHERE {} GROUP BY l.id ORDER BY count DESC LIMIT {}",
+            window, limit
+        ),
+    )?;
+    let epics = named(
+        connection,
+        &format!(
+            "SELECT e.name AS name, COUNT(*) AS count FROM actions2 a \
+             JOIN epics e ON a.epic_id = e.id \
+             WHERE {} GROUP BY e.id ORDER BY count DESC LIMIT {}",
+            window, limit
+        ),
+    )?;
+
+    Ok(Stats {
+        days,
+        total: total.count as usize,
+        commands: top(commands, limit),
+        domains: domains(urls, limit),
+        hours,
+        weekdays,
+        directories,
+        epics,
+    })
+}
+
+/// Number of rows in each table, used for monitoring.
+pub fn row_counts(connection: &Connection) -> Result<Vec<(&'static str, usize)>> {
+    let counts: Vec<(&'static str, i64)> = vec![
+        (
+            "action_tags",
+            action_tags::table.count().get_result(connection)?,
+        ),
+        ("actions2", actions2::table.count().get_result(connection)?),
+        (
+            "api_tokens",
+            api_tokens::table.count().get_result(connection)?,
+        ),
+        ("commands", commands::table.count().get_result(connection)?),
+        ("epics", epics::table.count().get_result(connection)?),
+        (
+            "focus_events",
+            focus_events::table.count().get_result(connection)?,
+        ),
+        ("hosts", hosts::table.count().get_result(connection)?),
+        (
+            "locations",
+            locations::table.count().get_result(connection)?,
+        ),
+        (
+            "navigations",
+            navigations::table.count().get_result(connection)?,
+        ),
+        (
+            "page_copies",
+            page_copies::table.count().get_result(connection)?,
+        ),
+        ("page_tags", page_tags::table.count().get_result(connection)?),
+        ("pages", pages::table.count().get_result(connection)?),
+        (
+            "reading_list_pages",
+            reading_list_pages::table.count().get_result(connection)?,
+        ),
+        (
+            "reading_lists",
+            reading_lists::table.count().get_result(connection)?,
+        ),
+        ("settings", settings::table.count().get_result(connection)?),
+        ("stars", stars::table.count().get_result(connection)?),
+        ("tags", tags::table.count().get_result(connection)?),
+        (
+            "url_restrictions",
+            url_restrictions::table.count().get_result(connection)?,
+        ),
+    ];
+    Ok(counts.into_iter().map(|(t, c)| (t, c as usize)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::actions2;
+    use crate::test_helpers::SqlStoreInMemory;
+    use crate::SqlProvider;
+    use lib_goo::date::now;
+    use lib_goo::entities::NewAction;
+
+    fn insert(connection: &Connection, kind: &str, command: &str, executed: &str) {
+        let action = NewAction {
+            kind: kind.into(),
+            command: command.into(),
+            executed: executed.into(),
+            location: Some("/home/weaver".into()),
+            epic: Some("stats".into()),
+            ..NewAction::default()
+        };
+        actions2::insert(connection, &action).expect("insert");
+    }
+
+    #[test]
+    fn test_compute() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        // 2019-01-06 is a Sunday.
+        insert(&connection, "shell", "ls", "2019-01-06T10:00:00-08:00");
+        insert(&connection, "shell", "ls", "2019-01-07T10:30:00-08:00");
+        insert(&connection, "shell", "make", "2019-01-07T23:00:00-08:00");
+        insert(
+            &connection,
+            "url",
+            "https://docs.rs/diesel",
+            "2019-01-07T11:00:00-08:00",
+        );
+        insert(
+            &connection,
+            "url",
+            "https://docs.rs/actix",
+            "2019-01-07T11:00:00-08:00",
+        );
+        insert(&connection, "url", "https://github.com/", &now());
+
+        let stats = compute(&connection, None, DEFAULT_LIMIT).expect("stats");
+        assert_eq!(stats.total, 6);
+        assert_eq!(
+            stats.commands,
+            vec![
+                NamedCount {
+                    name: "ls".into(),
+                    count: 2
+                },
+                NamedCount {
+                    name: "make".into(),
+                    count: 1
+                },
+            ]
+        );
+        assert_eq!(
+            stats.domains[0],
+            NamedCount {
+                name: "docs.rs".into(),
+                count: 2
+            }
+        );
+        // The last action is now, it may fall in any slot.
+        assert!(stats.hours[10] >= 2);
+        assert!(stats.hours[23] >= 1);
+        assert_eq!(stats.hours.iter().sum::<usize>(), 6);
+        assert!(stats.weekdays[0] >= 1);
+        assert!(stats.weekdays[1] >= 4);
+        assert_eq!(stats.weekdays.iter().sum::<usize>(), 6);
+        assert_eq!(stats.directories[0].count, 6);
+        assert_eq!(stats.epics[0].name, "stats");
+
+        let recent = compute(&connection, Some(7), 1).expect("stats");
+        assert_eq!(recent.total, 1);
+        assert!(recent.commands.is_empty());
+        assert_eq!(
+            recent.domains,
+            vec![NamedCount {
+                name: "github.com".into(),
+                count: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn test_row_counts() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        insert(&connection, "shell", "ls", &now());
+        let counts = row_counts(&connection).expect("counts");
+        assert!(counts.contains(&("actions2", 1)));
+        assert!(counts.contains(&("pages", 0)));
+        assert!(counts.contains(&("api_tokens", 0)));
+    }
+}
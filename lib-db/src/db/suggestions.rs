@@ -0,0 +1,272 @@
+//! Suggestions for the address bar, from the past searches, the page titles and the urls.
+//!
+//! The searches are the visits of the search page of the server, logged by the extension
+//! like any other url. The most visited urls are decrypted and matched in memory.
+#![allow(proc_macro_derive_resolution_fallback)]
+use crate::crypto;
+use crate::store_policies::Restrictions;
+use crate::Connection;
+use diesel;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Nullable, Text};
+use lib_error::*;
+use lib_goo::entities::PageContent;
+use std::collections::HashSet;
+use url::Url;
+
+/// Default number of suggestions.
+pub const DEFAULT_LIMIT: usize = 8;
+
+/// Only the most visited urls are candidates, the others are unlikely to be suggested.
+const MAX_CANDIDATES: usize = 2000;
+
+/// Hosts of the search page on this machine, the configured hosts are matched too.
+const LOCAL_HOSTS: &[&str] = &["localhost", "127.0.0.1", "[::1]"];
+
+/// Where a suggestion comes from, in the order they are suggested.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum Source {
+    Search,
+    Title,
+    Url,
+}
+
+/// One entry suggested for the typed text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub source: Source,
+    /// Completion of the typed text.
+    pub text: String,
+    pub description: String,
+    /// Page of the suggestion, none for the past searches.
+    pub url: Option<String>,
+}
+
+#[derive(QueryableByName)]
+struct Candidate {
+    #[sql_type = "Text"]
+    url: String,
+    #[sql_type = "Nullable<Text>"]
+    title: Option<String>,
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
+/// The term of a visit of the search page of the server reached at one of the hosts, local or
+/// configured, and one of the ports, under its base url.
+pub fn search_term(url: &str, hosts: &[String], ports: &[u16], base_url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let server = url
+        .host_str()
+        .map(|h| LOCAL_HOSTS.contains(&h) || hosts.iter().any(|s| s.eq_ignore_ascii_case(h)))
+        .unwrap_or(false);
+    let port = url.port_or_known_default().unwrap_or_default();
+    let search_page = url.path().trim_end_matches('/') == base_url.trim_end_matches('/');
+    if !server || !ports.contains(&port) || !search_page {
+        return None;
+    }
+    url.query_pairs()
+        .find(|(name, _)| name == "term")
+        .map(|(_, value)| value.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .filter(|term| !term.is_empty())
+}
+
+fn candidates(connection: &Connection) -> Result<Vec<Candidate>> {
+    let sql = format!(
+        "SELECT c.command AS url, p.title AS title, COUNT(*) AS count FROM actions2 a \
+         JOIN commands c ON a.command_id = c.id LEFT JOIN pages p ON c.page_id = p.id \
+         WHERE c.kind = 'url' GROUP BY c.id ORDER BY count DESC LIMIT {}",
+        MAX_CANDIDATES
+    );
+    let mut rows = diesel::sql_query(sql).load::<Candidate>(connection)?;
+    for row in &mut rows {
+        row.url = crypto::open(connection, row.url.clone())?;
+        row.title = crypto::open_option(connection, row.title.take())?.filter(|t| !t.is_empty());
+    }
+    Ok(rows)
+}
+
+/// Suggest the past searches starting with the text, then the titles and the urls containing
+/// it, the most visited first. The hidden pages are never suggested.
+pub fn suggest(
+    connection: &Connection,
+    text: &str,
+    search_hosts: &[String],
+    search_ports: &[u16],
+    base_url: &str,
+    limit: usize,
+) -> Result<Vec<Suggestion>> {
+    let needle = text.trim().to_lowercase();
+    if needle.is_empty() {
+        return Ok(Vec::new());
+    }
+    let restrictions = Restrictions::fetch(connection)?;
+    let mut out = Vec::new();
+    for candidate in candidates(connection)? {
+        if let Some(term) = search_term(&candidate.url, search_hosts, search_ports, base_url) {
+            if term.to_lowercase().starts_with(&needle) {
+                out.push((
+                    candidate.count,
+                    Suggestion {
+                        source: Source::Search,
+                        text: term,
+                        description: String::from("weaver search"),
+                        url: None,
+                    },
+                ));
+            }
+            continue;
+        }
+        let page = PageContent {
+            url: candidate.url,
+            title: candidate.title.clone().unwrap_or_default(),
+            ..PageContent::default()
+        };
+        if !restrictions.should_display(&page) {
+            continue;
+        }
+        let suggestion = match candidate.title {
+            Some(title) if title.to_lowercase().contains(&needle) => Suggestion {
+                source: Source::Title,
+                text: title,
+                description: page.url.clone(),
+                url: Some(page.url),
+            },
+            title => {
+                if !page.url.to_lowercase().contains(&needle) {
+                    continue;
+                }
+                Suggestion {
+                    source: Source::Url,
+                    text: page.url.clone(),
+                    description: title.unwrap_or_default(),
+                    url: Some(page.url),
+                }
+            }
+        };
+        out.push((candidate.count, suggestion));
+    }
+
+    out.sort_by_key(|(count, s)| (s.source, -count));
+    let mut seen = HashSet::new();
+    Ok(out
+        .into_iter()
+        .map(|(_, s)| s)
+        .filter(|s| seen.insert(s.text.to_lowercase()))
+        .take(limit)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{actions2, pages};
+    use crate::test_helpers::SqlStoreInMemory;
+    use crate::SqlProvider;
+    use lib_goo::entities::NewAction;
+
+    fn visit(connection: &Connection, url: &str, times: usize) {
+        for _ in 0..times {
+            let action = NewAction::build_from_url(url, None).expect("action");
+            actions2::insert(connection, &action).expect("insert");
+        }
+    }
+
+    #[test]
+    fn test_search_term() {
+        let ports = [8466, 8467];
+        assert_eq!(
+            search_term("http://localhost:8466/?term=rust+borrow", &[], &ports, "/"),
+            Some("rust borrow".into())
+        );
+        assert_eq!(
+            search_term("https://127.0.0.1:8467/?term=x", &[], &ports, "/"),
+            Some("x".into())
+        );
+        assert_eq!(
+            search_term("http://localhost:8466/history?term=x", &[], &ports, "/"),
+            None
+        );
+        assert_eq!(
+            search_term("http://localhost:9000/?term=x", &[], &ports, "/"),
+            None
+        );
+        assert_eq!(
+            search_term("https://example.com/?term=x", &[], &ports, "/"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_search_term_configured_host() {
+        let hosts = vec![String::from("Weaver.example.com")];
+        assert_eq!(
+            search_term(
+                "https://weaver.example.com/wr/?term=x",
+                &hosts,
+                &[443],
+                "/wr"
+            ),
+            Some("x".into())
+        );
+        assert_eq!(
+            search_term("https://localhost:8467/wr/?term=x", &hosts, &[8467], "/wr"),
+            Some("x".into())
+        );
+        assert_eq!(
+            search_term("https://example.com/wr/?term=x", &hosts, &[443], "/wr"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_search_term_base_url() {
+        let ports = [8466];
+        assert_eq!(
+            search_term("http://localhost:8466/wr/?term=x", &[], &ports, "/wr"),
+            Some("x".into())
+        );
+        assert_eq!(
+            search_term("http://localhost:8466/wr?term=x", &[], &ports, "/wr/"),
+            Some("x".into())
+        );
+        assert_eq!(
+            search_term("http://localhost:8466/?term=x", &[], &ports, "/wr"),
+            None
+        );
+        assert_eq!(
+            search_term(
+                "http://localhost:8466/wr/history?term=x",
+                &[],
+                &ports,
+                "/wr"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_suggest() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        pages::fetch_or_create_id(&connection, "https://doc.rust-lang.org/", Some("Rust docs"))
+            .expect("page");
+        visit(&connection, "https://doc.rust-lang.org/", 1);
+        visit(&connection, "https://rust.example.com/", 3);
+        visit(&connection, "http://localhost:8466/?term=rust%20traits", 1);
+        visit(&connection, "https://example.com/", 5);
+
+        let found =
+            suggest(&connection, "Rust", &[], &[8466], "/", DEFAULT_LIMIT).expect("suggest");
+        let texts = found.iter().map(|s| s.text.as_str()).collect::<Vec<&str>>();
+        assert_eq!(
+            texts,
+            vec!["rust traits", "Rust docs", "https://rust.example.com/"]
+        );
+        assert_eq!(found[1].url, Some("https://doc.rust-lang.org/".into()));
+        assert!(suggest(&connection, " ", &[], &[8466], "/", DEFAULT_LIMIT)
+            .expect("suggest")
+            .is_empty());
+    }
+}
@@ -0,0 +1,298 @@
+//! Tags on actions and pages, many to many.
+#![allow(proc_macro_derive_resolution_fallback)]
+use crate::backends::schema::*;
+use crate::crypto;
+use crate::db::{self, CHUNK};
+use crate::Connection;
+use diesel;
+use diesel::prelude::*;
+use lib_error::*;
+use lib_goo::config::net::TagCount;
+use lib_goo::normalize;
+use std::collections::{HashMap, HashSet};
+
+/// Tags are lower case words, dashes and underscores are allowed.
+pub fn normalize_name(name: &str) -> Result<String> {
+    let name = name.trim().to_lowercase();
+    if name.is_empty() {
+        return Err("the tag cannot be empty".into());
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(WeaverError::from(format!(
+            "invalid tag {}, use letters, digits, - and _",
+            name
+        )));
+    }
+    Ok(name)
+}
+
+/// Fetch the id for the given tag, if present.
+pub fn fetch_id(connection: &Connection, name: &str) -> Result<Option<i32>> {
+    let existing = tags::dsl::tags
+        .filter(tags::dsl::name.eq(name))
+        .select(tags::dsl::id)
+        .load::<Option<i32>>(connection)?;
+    Ok(existing.into_iter().next().and_then(|a| a))
+}
+
+/// Fetch or create the tag with the given name.
+pub fn fetch_or_create_id(connection: &Connection, name: &str) -> Result<i32> {
+    let name = normalize_name(name)?;
+    match fetch_id(connection, &name)? {
+        Some(existing) => Ok(existing),
+        None => {
+            diesel::insert_into(tags::table)
+                .values(tags::dsl::name.eq(&name))
+                .execute(connection)?;
+            match fetch_id(connection, &name)? {
+                Some(id) => Ok(id),
+                None => Err("did not get id after inserting tag".into()),
+            }
+        }
+    }
+}
+
+/// Tag the action.
+pub fn add_to_action(connection: &Connection, action_id: u64, tag: &str) -> Result<()> {
+    let exists = actions2::table
+        .filter(actions2::dsl::id.eq(action_id as i32))
+        .count()
+        .get_result::<i64>(connection)?;
+    if exists == 0 {
+        return Err(WeaverError::from(format!("no action with id {}", action_id)));
+    }
+    let tag_id = fetch_or_create_id(connection, tag)?;
+    diesel::replace_into(action_tags::table)
+        .values((
+            action_tags::dsl::action_id.eq(action_id as i32),
+            action_tags::dsl::tag_id.eq(tag_id),
+        ))
+        .execute(connection)?;
+    Ok(())
+}
+
+/// Remove the tag from the action, returns false if the action did not have it.
+pub fn remove_from_action(connection: &Connection, action_id: u64, tag: &str) -> Result<bool> {
+    let tag_id = match fetch_id(connection, &normalize_name(tag)?)? {
+        Some(id) => id,
+        None => return Ok(false),
+    };
+    let deleted = diesel::delete(
+        action_tags::table
+            .filter(action_tags::dsl::action_id.eq(action_id as i32))
+            .filter(action_tags::dsl::tag_id.eq(tag_id)),
+    )
+    .execute(connection)?;
+    Ok(deleted > 0)
+}
+
+/// Tag the page with the given url, the page is created if needed.
+pub fn add_to_page(connection: &Connection, url: &str, tag: &str) -> Result<()> {
+    let page_id = db::pages::fetch_or_create_id(connection, url, None)?;
+    let tag_id = fetch_or_create_id(connection, tag)?;
+    diesel::replace_into(page_tags::table)
+        .values((
+            page_tags::dsl::page_id.eq(page_id),
+            page_tags::dsl::tag_id.eq(tag_id),
+        ))
+        .execute(connection)?;
+    Ok(())
+}
+
+/// Remove the tag from the page, returns false if the page did not have it.
+pub fn remove_from_page(connection: &Connection, url: &str, tag: &str) -> Result<bool> {
+    let page_id = match db::pages::fetch_id(connection, url)? {
+        Some(id) => id,
+        None => return Ok(false),
+    };
+    let tag_id = match fetch_id(connection, &normalize_name(tag)?)? {
+        Some(id) => id,
+        None => return Ok(false),
+    };
+    let deleted = diesel::delete(
+        page_tags::table
+            .filter(page_tags::dsl::page_id.eq(page_id))
+            .filter(page_tags::dsl::tag_id.eq(tag_id)),
+    )
+    .execute(connection)?;
+    Ok(deleted > 0)
+}
+
+/// Fetch the tags of the given actions, sorted by name.
+pub fn for_actions(connection: &Connection, ids: &[i32]) -> Result<HashMap<i32, Vec<String>>> {
+    let mut out: HashMap<i32, Vec<String>> = HashMap::new();
+    for chunk in ids.chunks(CHUNK) {
+        let entries = action_tags::table
+            .inner_join(tags::table)
+            .filter(action_tags::dsl::action_id.eq_any(chunk))
+            .select((action_tags::dsl::action_id, tags::dsl::name))
+            .load::<(i32, String)>(connection)?;
+        for (id, name) in entries {
+            out.entry(id).or_insert_with(Vec::new).push(name);
+        }
+    }
+    for names in out.values_mut() {
+        names.sort();
+    }
+    Ok(out)
+}
+
+/// Fetch the tags of the page with the given url, sorted by name.
+pub fn for_page(connection: &Connection, url: &str) -> Result<Vec<String>> {
+    let page_id = match db::pages::fetch_id(connection, url)? {
+        Some(id) => id,
+        None => return Ok(Vec::new()),
+    };
+    let mut names = page_tags::table
+        .inner_join(tags::table)
+        .filter(page_tags::dsl::page_id.eq(page_id))
+        .select(tags::dsl::name)
+        .load::<String>(connection)?;
+    names.sort();
+    Ok(names)
+}
+
+/// Fetch the normalized urls of the pages with the given tag.
+pub fn page_urls(connection: &Connection, tag: &str) -> Result<HashSet<String>> {
+    let tag_id = match fetch_id(connection, &normalize_name(tag)?)? {
+        Some(id) => id,
+        None => return Ok(HashSet::new()),
+    };
+    let entries = page_tags::table
+        .inner_join(pages::table)
+        .filter(page_tags::dsl::tag_id.eq(tag_id))
+        .select(pages::dsl::normalized_url)
+        .load::<String>(connection)?;
+    let mut out = HashSet::with_capacity(entries.len());
+    for url in entries {
+        out.insert(crypto::open(connection, url)?);
+    }
+    Ok(out)
+}
+
+/// Check if the page with the given url is in the set returned by `page_urls`.
+pub fn contains_url(urls: &HashSet<String>, url: &str) -> bool {
+    match normalize::normalize_url(url) {
+        Ok(normalized) => urls.contains(&*normalized),
+        Err(_) => urls.contains(url),
+    }
+}
+
+/// List all the tags with the number of tagged actions and pages.
+pub fn fetch_all(connection: &Connection) -> Result<Vec<TagCount>> {
+    let all = tags::dsl::tags
+        .select((tags::dsl::id, tags::dsl::name))
+        .order(tags::dsl::name.asc())
+        .load::<(Option<i32>, String)>(connection)?;
+    let mut out = Vec::with_capacity(all.len());
+    for (id, name) in all {
+        let id = id.unwrap_or_default();
+        let actions: i64 = action_tags::table
+            .filter(action_tags::dsl::tag_id.eq(id))
+            .count()
+            .get_result(connection)?;
+        let pages: i64 = page_tags::table
+            .filter(page_tags::dsl::tag_id.eq(id))
+            .count()
+            .get_result(connection)?;
+        out.push(TagCount {
+            name,
+            actions: actions as usize,
+            pages: pages as usize,
+        });
+    }
+    Ok(out)
+}
+
+/// Remove the tags of the deleted actions.
+pub(crate) fn delete_for_actions(connection: &Connection, ids: &[i32]) -> Result<usize> {
+    let mut count = 0;
+    for chunk in ids.chunks(CHUNK) {
+        count += diesel::delete(action_tags::table.filter(action_tags::dsl::action_id.eq_any(chunk)))
+            .execute(connection)?;
+    }
+    Ok(count)
+}
+
+/// Remove the tags pointing to actions or pages which do not exist anymore.
+pub(crate) fn delete_orphans(connection: &Connection) -> Result<usize> {
+    let actions = diesel::sql_query(
+        "DELETE FROM action_tags WHERE action_id NOT IN \
+         (SELECT id FROM actions2 WHERE id IS NOT NULL)",
+    )
+    .execute(connection)?;
+    let pages = diesel::sql_query(
+        "DELETE FROM page_tags WHERE page_id NOT IN \
+         (SELECT id FROM pages WHERE id IS NOT NULL)",
+    )
+    .execute(connection)?;
+    Ok(actions + pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::actions2 as actions;
+    use crate::test_helpers::SqlStoreInMemory;
+    use crate::SqlProvider;
+    use lib_goo::entities::NewAction;
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name(" Deploy ").unwrap(), "deploy");
+        assert_eq!(normalize_name("on-call_2").unwrap(), "on-call_2");
+        assert!(normalize_name("").is_err());
+        assert!(normalize_name("two words").is_err());
+    }
+
+    #[test]
+    fn test_action_tags() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        for command in &["make deploy", "gdb"] {
+            let action = NewAction {
+                command: command.to_string(),
+                ..NewAction::default()
+            };
+            actions::insert(&connection, &action).expect("insert");
+        }
+        add_to_action(&connection, 1, "deploy").unwrap();
+        add_to_action(&connection, 1, "oncall").unwrap();
+        add_to_action(&connection, 1, "Deploy").unwrap();
+        add_to_action(&connection, 2, "debug").unwrap();
+        assert!(add_to_action(&connection, 3, "debug").is_err());
+
+        let found = for_actions(&connection, &[1, 2]).unwrap();
+        assert_eq!(found[&1], vec!["deploy", "oncall"]);
+        assert_eq!(found[&2], vec!["debug"]);
+
+        assert!(remove_from_action(&connection, 1, "oncall").unwrap());
+        assert!(!remove_from_action(&connection, 1, "oncall").unwrap());
+
+        let all = fetch_all(&connection).unwrap();
+        assert_eq!(
+            all.iter().map(|t| (t.name.as_str(), t.actions)).collect::<Vec<_>>(),
+            vec![("debug", 1), ("deploy", 1), ("oncall", 0)]
+        );
+    }
+
+    #[test]
+    fn test_page_tags() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        add_to_page(&connection, "https://wiki/runbook?utm_source=x", "oncall").unwrap();
+        assert_eq!(for_page(&connection, "https://wiki/runbook").unwrap(), vec!["oncall"]);
+
+        let urls = page_urls(&connection, "oncall").unwrap();
+        assert!(contains_url(&urls, "https://wiki/runbook"));
+        assert!(!contains_url(&urls, "https://wiki/other"));
+
+        assert!(remove_from_page(&connection, "https://wiki/runbook", "oncall").unwrap());
+        assert!(page_urls(&connection, "oncall").unwrap().is_empty());
+    }
+}
@@ -4,6 +4,7 @@ use crate::Connection;
 use diesel;
 use diesel::prelude::*;
 use lib_error::{Result as WResult, WeaverError};
+use regex::Regex;
 use std::str::FromStr;
 use std::string::ToString;
 
@@ -51,11 +52,113 @@ impl ToString for StorePolicy {
     }
 }
 
+// Url pattern with `*` wildcards, matching the whole url.
+pub const GLOB: &str = "glob";
+
+// Url regular expression, matching anywhere in the url.
+pub const REGEX: &str = "regex";
+
+// Domain name, matching the urls of the domain and of its sub domains.
+pub const DOMAIN: &str = "domain";
+
+// Start of the url.
+pub const PREFIX: &str = "prefix";
+
+/// How the `url_expr` of a restriction is written, every kind is compiled to a regex.
+pub enum PatternKind {
+    Glob,
+    Regex,
+    Domain,
+    Prefix,
+}
+
+impl FromStr for PatternKind {
+    type Err = WeaverError;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            GLOB => Ok(PatternKind::Glob),
+            REGEX => Ok(PatternKind::Regex),
+            DOMAIN => Ok(PatternKind::Domain),
+            PREFIX => Ok(PatternKind::Prefix),
+            _ => Err(WeaverError::from(format!(
+                "unknown url pattern kind {:?}, expected one of {}, {}, {} or {}",
+                s, GLOB, REGEX, DOMAIN, PREFIX
+            ))),
+        }
+    }
+}
+
+impl ToString for PatternKind {
+    fn to_string(&self) -> String {
+        match *self {
+            PatternKind::Glob => GLOB.into(),
+            PatternKind::Regex => REGEX.into(),
+            PatternKind::Domain => DOMAIN.into(),
+            PatternKind::Prefix => PREFIX.into(),
+        }
+    }
+}
+
+// A domain name is made of non empty labels of letters, digits and dashes.
+fn is_domain(name: &str) -> bool {
+    name.split('.').all(|label| {
+        !label.is_empty()
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    })
+}
+
+impl PatternKind {
+    fn source(&self, pattern: &str) -> WResult<String> {
+        if pattern.is_empty() {
+            return Err(WeaverError::from("empty url pattern"));
+        }
+        match *self {
+            PatternKind::Glob => {
+                let parts: Vec<String> = pattern.split('*').map(regex::escape).collect();
+                Ok(format!("^{}$", parts.join(".*")))
+            }
+            PatternKind::Regex => Ok(pattern.to_owned()),
+            PatternKind::Domain => {
+                if !is_domain(pattern) {
+                    return Err(WeaverError::from(format!(
+                        "invalid domain {:?}, expected a name like example.com",
+                        pattern
+                    )));
+                }
+                // No inline flags, the extension runs these as javascript regexes. The
+                // browsers give the hosts in lower case.
+                Ok(format!(
+                    "^[a-z][a-z0-9+.-]*://([^/?#@]*@)?([^/?#@]*\\.)?{}(:[0-9]+)?([/?#]|$)",
+                    regex::escape(&pattern.to_lowercase())
+                ))
+            }
+            PatternKind::Prefix => Ok(format!("^{}", regex::escape(pattern))),
+        }
+    }
+
+    /// Compile the url pattern, fails with the reason when it is not valid for this kind.
+    pub fn compile(&self, pattern: &str) -> WResult<Regex> {
+        let source = self.source(pattern)?;
+        Regex::new(&source).map_err(|e| {
+            WeaverError::from(format!(
+                "invalid {} url pattern {:?}: {}",
+                self.to_string(),
+                pattern,
+                e
+            ))
+        })
+    }
+}
+
 // Fetch the id of the entry, if it already exists
 fn fetch_id(connection: &Connection, ur: &UrlRestriction) -> WResult<Option<i32>> {
     let existing = url_restrictions::dsl::url_restrictions
         .filter(url_restrictions::dsl::url_expr.eq(&ur.url_expr))
         .filter(url_restrictions::dsl::kind.eq(&ur.kind))
+        .filter(url_restrictions::dsl::pattern_kind.eq(&ur.pattern_kind))
         .filter(url_restrictions::dsl::title_match.eq(&ur.title_match))
         .filter(url_restrictions::dsl::body_match.eq(&ur.body_match))
         .select(url_restrictions::dsl::id)
@@ -63,8 +166,9 @@ fn fetch_id(connection: &Connection, ur: &UrlRestriction) -> WResult<Option<i32>
     Ok(existing.iter().next().map(|a| a.expect("must have id")))
 }
 
-// Insert a new restriction, unless it is already present.
+// Insert a new restriction, unless it is already present. Invalid restrictions are rejected.
 pub fn insert(connection: &Connection, ur: UrlRestriction) -> WResult<()> {
+    ur.validate()?;
     let existing = fetch_id(connection, &ur)?;
     if existing.is_none() {
         diesel::insert_into(url_restrictions::table)
@@ -73,12 +177,17 @@ pub fn insert(connection: &Connection, ur: UrlRestriction) -> WResult<()> {
                 url_restrictions::dsl::url_expr.eq(ur.url_expr),
                 url_restrictions::dsl::title_match.eq(ur.title_match),
                 url_restrictions::dsl::body_match.eq(ur.body_match),
+                url_restrictions::dsl::pattern_kind.eq(ur.pattern_kind),
             )).execute(connection)?;
     }
     Ok(())
 }
 
-#[derive(Debug,::serde::Serialize, ::serde::Deserialize, Queryable, Default)]
+fn default_pattern_kind() -> String {
+    REGEX.into()
+}
+
+#[derive(Debug,::serde::Serialize, ::serde::Deserialize, Queryable)]
 #[allow(dead_code)]
 pub struct UrlRestriction {
     pub id: Option<i32>,
@@ -86,6 +195,32 @@ pub struct UrlRestriction {
     pub url_expr: String,
     pub title_match: Option<String>,
     pub body_match: Option<String>,
+    /// How `url_expr` is written, a regex when not given.
+    #[serde(default = "default_pattern_kind")]
+    pub pattern_kind: String,
+}
+
+impl Default for UrlRestriction {
+    fn default() -> Self {
+        UrlRestriction {
+            id: None,
+            kind: String::new(),
+            url_expr: String::new(),
+            title_match: None,
+            body_match: None,
+            pattern_kind: default_pattern_kind(),
+        }
+    }
+}
+
+// The title and body matches are always regular expressions.
+fn compile_match(field: &str, expr: &Option<String>) -> WResult<Option<Regex>> {
+    match expr {
+        Some(expr) => Regex::new(expr).map(Some).map_err(|e| {
+            WeaverError::from(format!("invalid {} regex {:?}: {}", field, expr, e))
+        }),
+        None => Ok(None),
+    }
 }
 
 impl UrlRestriction {
@@ -96,6 +231,39 @@ impl UrlRestriction {
             ..UrlRestriction::default()
         }
     }
+
+    pub fn with_pattern(
+        kind: &StorePolicy,
+        pattern_kind: &PatternKind,
+        url: &str,
+    ) -> UrlRestriction {
+        UrlRestriction {
+            pattern_kind: pattern_kind.to_string(),
+            ..UrlRestriction::with_url(kind, url)
+        }
+    }
+
+    /// Compile the url pattern.
+    pub fn url(&self) -> WResult<Regex> {
+        self.pattern_kind.parse::<PatternKind>()?.compile(&self.url_expr)
+    }
+
+    pub fn title(&self) -> WResult<Option<Regex>> {
+        compile_match("title", &self.title_match)
+    }
+
+    pub fn body(&self) -> WResult<Option<Regex>> {
+        compile_match("body", &self.body_match)
+    }
+
+    /// Check the policy and compile all the patterns.
+    pub fn validate(&self) -> WResult<()> {
+        self.kind.parse::<StorePolicy>()?;
+        self.url()?;
+        self.title()?;
+        self.body()?;
+        Ok(())
+    }
 }
 
 pub fn fetch_all(connection: &Connection) -> WResult<Vec<UrlRestriction>> {
@@ -160,4 +328,60 @@ mod tests {
         let fetched = fetch_id(&connection, &url).unwrap();
         assert!(fetched.is_none());
     }
+
+    #[test]
+    fn test_pattern_kinds() {
+        let glob = PatternKind::Glob.compile("https://*.wikipedia.org/*").unwrap();
+        assert!(glob.is_match("https://en.wikipedia.org/wiki/Rust"));
+        assert!(!glob.is_match("https://en.wikipedia.com/"));
+
+        let domain = PatternKind::Domain.compile("Example.com").unwrap();
+        assert!(domain.is_match("https://example.com"));
+        assert!(domain.is_match("http://www.example.com:8080/a?b"));
+        assert!(!domain.is_match("https://notexample.com/"));
+        assert!(!domain.is_match("https://example.com.evil.org/"));
+        assert!(!domain.is_match("https://evil.org/example.com"));
+
+        let prefix = PatternKind::Prefix.compile("https://docs.rs/").unwrap();
+        assert!(prefix.is_match("https://docs.rs/regex"));
+        assert!(!prefix.is_match("http://a.b/?u=https://docs.rs/"));
+
+        assert!(PatternKind::Regex.compile("https://(foo").is_err());
+        assert!(PatternKind::Domain.compile("https://example.com").is_err());
+        assert!(PatternKind::Prefix.compile("").is_err());
+        assert!("wildcard".parse::<PatternKind>().is_err());
+    }
+
+    #[test]
+    fn test_invalid_rejected() {
+        let connection = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+
+        let invalid = vec![
+            UrlRestriction::with_url(&StorePolicy::NoLog, "https://(foo"),
+            UrlRestriction::with_pattern(&StorePolicy::NoLog, &PatternKind::Domain, "a b"),
+            UrlRestriction {
+                title_match: Some("[".into()),
+                ..UrlRestriction::with_url(&StorePolicy::Hidden, "a")
+            },
+            UrlRestriction {
+                pattern_kind: "wildcard".into(),
+                ..UrlRestriction::with_url(&StorePolicy::NoLog, "a")
+            },
+            UrlRestriction::with_url(&StorePolicy::NoLog, ""),
+        ];
+        for ur in invalid {
+            assert!(insert(&connection, ur).is_err());
+        }
+
+        insert(
+            &connection,
+            UrlRestriction::with_pattern(&StorePolicy::NoLog, &PatternKind::Domain, "example.com"),
+        )
+        .unwrap();
+        let all = fetch_all(&connection).unwrap();
+        assert_eq!(1, all.len());
+        assert_eq!(all[0].pattern_kind, DOMAIN);
+    }
 }
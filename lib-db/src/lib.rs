@@ -8,24 +8,40 @@ extern crate diesel;
 extern crate diesel_migrations;
 
 pub use crate::db::actions2;
+pub use crate::db::api_tokens;
+pub use crate::db::bookmarks;
+pub use crate::db::forget;
 pub use crate::db::pages;
+pub use crate::db::replication;
+pub use crate::db::sessions;
+pub use crate::db::stats;
+pub use crate::db::suggestions;
+pub use crate::db::tags;
 pub use crate::db::url_restrictions;
-use diesel::sqlite::SqliteConnection;
+use crate::crypto::FieldCipher;
 use lib_error::*;
 use lib_goo::config::file_utils;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 
 mod backends;
+pub mod browser_import;
+pub mod crypto;
 mod db;
 pub mod setup;
 pub mod store_policies;
 pub mod test_helpers;
 pub mod topics;
 
-pub type Connection = SqliteConnection;
+pub use crate::backends::Connection;
 
-pub struct SqlStore {}
+/// The sqlite database, the default one of the user unless built with a path.
+pub struct SqlStore {
+    path: Option<PathBuf>,
+    // The cipher of the encrypted columns, passed to each connection.
+    cipher: RwLock<Option<Arc<FieldCipher>>>,
+}
 
 embed_migrations!("../migrations");
 
@@ -35,7 +51,50 @@ pub trait SqlProvider {
 
 impl SqlStore {
     pub fn build() -> Result<SqlStore> {
-        Ok(SqlStore {})
+        Ok(SqlStore {
+            path: None,
+            cipher: RwLock::new(None),
+        })
+    }
+
+    /// The database in the given file, created when missing. The pending migrations are
+    /// applied, there is no `weaver-data` command for these databases.
+    pub fn at(path: PathBuf) -> Result<SqlStore> {
+        if !path.exists() {
+            Self::create_database_at(&path)?;
+        }
+        let store = SqlStore {
+            path: Some(path),
+            cipher: RwLock::new(None),
+        };
+        embedded_migrations::run(&store.connection()?).context("migration error".into())?;
+        Ok(store)
+    }
+
+    /// Use the cipher for the encrypted columns, in the connections opened afterwards.
+    /// The cipher can only be set once.
+    pub fn set_cipher(&self, cipher: Box<FieldCipher>) -> Result<()> {
+        let mut current = self
+            .cipher
+            .write()
+            .map_err(|e| WeaverError::from(format!("lock field cipher {:?}", e)))?;
+        if current.is_some() {
+            return Err("the field cipher is already set".into());
+        }
+        *current = Some(Arc::from(cipher));
+        Ok(())
+    }
+
+    /// Check if the cipher of the encrypted columns was set.
+    pub fn has_cipher(&self) -> Result<bool> {
+        Ok(self.cipher()?.is_some())
+    }
+
+    fn cipher(&self) -> Result<Option<Arc<FieldCipher>>> {
+        self.cipher
+            .read()
+            .map(|c| c.clone())
+            .map_err(|e| WeaverError::from(format!("lock field cipher {:?}", e)))
     }
 
     pub fn create_database_if_missing() -> Result<()> {
@@ -65,8 +124,12 @@ impl SqlStore {
     }
 
     pub fn create_database() -> Result<()> {
+        Self::create_database_at(&file_utils::default_database()?)
+    }
+
+    /// Create the database file and run the migrations.
+    pub fn create_database_at(path: &Path) -> Result<()> {
         use diesel::Connection as DieselConnection;
-        let path = file_utils::default_database()?;
         if path.exists() {
             return Err("output file already exists".into());
         }
@@ -74,7 +137,7 @@ impl SqlStore {
         if path_s.is_none() {
             return Err("bad path".into());
         }
-        let connection = SqliteConnection::establish(path_s.unwrap())?;
+        let connection = Connection::establish(path_s.unwrap())?;
         embedded_migrations::run(&connection)
             .context("migration error".into())?;
         Ok(())
@@ -93,8 +156,10 @@ impl SqlStore {
 
 impl SqlProvider for SqlStore {
     fn connection(&self) -> Result<Connection> {
-        use diesel::Connection as DieselConnection;
-        let path = file_utils::default_database()?;
+        let path = match self.path {
+            Some(ref path) => path.clone(),
+            None => file_utils::default_database()?,
+        };
         if !path.exists() {
             return Err("database file does not exists".into());
         }
@@ -104,12 +169,64 @@ impl SqlProvider for SqlStore {
             return Err("no database url".into());
         };
         ::log::debug!("opening database {} ", &db_url);
-        let connection = SqliteConnection::establish(&db_url)?;
-        Ok(connection)
+        Ok(Connection::open(&db_url, self.cipher()?)?)
     }
 }
 
+// The versions of the embedded migrations, sorted, listed by the build script.
+include!(concat!(env!("OUT_DIR"), "/migration_versions.rs"));
+
+/// Check if some of the embedded migrations were not applied yet, from the versions saved by
+/// diesel in `__diesel_schema_migrations`. Nothing is written to the database.
+pub fn has_pending_migrations(connection: &Connection) -> Result<bool> {
+    use diesel::dsl::sql;
+    use diesel::prelude::*;
+    use diesel::sql_types::BigInt;
+    use diesel_migrations::MigrationConnection;
+
+    let tables = diesel::select(sql::<BigInt>(
+        "(SELECT COUNT(*) FROM sqlite_master \
+         WHERE type = 'table' AND name = '__diesel_schema_migrations')",
+    ))
+    .get_result::<i64>(connection)?;
+    if tables == 0 {
+        return Ok(!MIGRATION_VERSIONS.is_empty());
+    }
+    let applied = connection.previously_run_migration_versions()?;
+    Ok(MIGRATION_VERSIONS.iter().any(|v| !applied.contains(*v)))
+}
+
 // Recreate various connections between tables.
 pub fn link_tables(connection: &Connection) -> Result<()> {
     db::commands::link_pages(connection)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::SqlStoreInMemory;
+
+    #[test]
+    fn test_has_pending_migrations() {
+        use diesel::prelude::*;
+        use diesel_migrations::MigrationConnection;
+
+        let empty = Connection::establish(":memory:").expect("in memory database");
+        assert!(has_pending_migrations(&empty).unwrap());
+        // The check did not create the migrations table.
+        assert!(has_pending_migrations(&empty).unwrap());
+
+        let current = SqlStoreInMemory::build(|_| Ok(()))
+            .connection()
+            .expect("test connection");
+        assert!(!has_pending_migrations(&current).unwrap());
+        // The versions listed by the build script are the ones applied by diesel.
+        let applied = current.previously_run_migration_versions().unwrap();
+        assert_eq!(applied.len(), MIGRATION_VERSIONS.len());
+
+        diesel::sql_query("DELETE FROM __diesel_schema_migrations WHERE version = '20190317120000'")
+            .execute(&current)
+            .unwrap();
+        assert!(has_pending_migrations(&current).unwrap());
+    }
+}
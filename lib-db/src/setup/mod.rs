@@ -75,7 +75,11 @@ fn do_index(connection: &Connection) -> Result<()> {
         "https://*.readthedocs.io/*",
     ];
     for u in sites {
-        let ur = UrlRestriction::with_url(&url_restrictions::StorePolicy::DoIndex, u);
+        let ur = UrlRestriction::with_pattern(
+            &url_restrictions::StorePolicy::DoIndex,
+            &url_restrictions::PatternKind::Glob,
+            u,
+        );
         url_restrictions::insert(connection, ur)?;
     }
     Ok(())
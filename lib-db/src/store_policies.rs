@@ -3,7 +3,6 @@ use crate::Connection;
 use lib_error::{Result as WResult};
 use lib_goo::entities::PageContent;
 use regex::Regex;
-use std::convert::From;
 
 // Describe a document matcher to identify documents that belong to a certain class.
 #[derive(Debug)]
@@ -13,17 +12,16 @@ pub struct DocumentMatcher {
     pub body: Option<Regex>,
 }
 
-impl<'a> From<&'a UrlRestriction> for DocumentMatcher {
-    fn from(ur: &UrlRestriction) -> Self {
-        DocumentMatcher {
-            url: Regex::new(&ur.url_expr).ok(),
-            title: ur.title_match.as_ref().and_then(|a| Regex::new(&a).ok()),
-            body: ur.body_match.as_ref().and_then(|a| Regex::new(&a).ok()),
-        }
+impl DocumentMatcher {
+    /// Compile the patterns of the restriction, the url pattern according to its kind.
+    pub fn build(ur: &UrlRestriction) -> WResult<DocumentMatcher> {
+        Ok(DocumentMatcher {
+            url: Some(ur.url()?),
+            title: ur.title()?,
+            body: ur.body()?,
+        })
     }
-}
 
-impl DocumentMatcher {
     #[allow(dead_code)]
     fn with_url(url: &str) -> WResult<DocumentMatcher> {
         let url = Regex::new(url)?;
@@ -88,6 +86,11 @@ impl Restrictions {
             && !matches_any_restriction(url, &self.do_not_log)
     }
 
+    // Check if visits of this URL can be saved in the action log.
+    pub fn should_log_url(&self, url: &str) -> bool {
+        !matches_any_restriction(url, &self.do_not_log)
+    }
+
     pub fn should_index(&self, page_content: &PageContent) -> bool {
         !DocumentMatcher::matches_any(page_content, &self.do_not_index)
             && !DocumentMatcher::matches_any(page_content, &self.do_not_log)
@@ -111,11 +114,19 @@ impl Restrictions {
         let mut hidden = Vec::new();
         for one in all {
             let policy = one.kind.parse::<StorePolicy>()?;
+            // Rows saved before the validation on insert may not compile, keep the others.
+            let matcher = match DocumentMatcher::build(one) {
+                Ok(m) => m,
+                Err(e) => {
+                    ::log::warn!("skipping url restriction {:?}: {}", one.id, e);
+                    continue;
+                }
+            };
             match policy {
-                StorePolicy::NoLog => do_not_log.push(one.into()),
-                StorePolicy::NoIndex => do_not_index.push(one.into()),
-                StorePolicy::DoIndex => do_index.push(one.into()),
-                StorePolicy::Hidden => hidden.push(one.into()),
+                StorePolicy::NoLog => do_not_log.push(matcher),
+                StorePolicy::NoIndex => do_not_index.push(matcher),
+                StorePolicy::DoIndex => do_index.push(matcher),
+                StorePolicy::Hidden => hidden.push(matcher),
             }
         }
         Ok(Restrictions {
@@ -130,6 +141,7 @@ impl Restrictions {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::url_restrictions::PatternKind;
 
     // Build a test PageContent with only the URL.
     fn pc(url: &str) -> PageContent {
@@ -215,4 +227,25 @@ mod tests {
         let display_foo = restrictions.should_display(&pc("https://foo"));
         assert!(!display_foo);
     }
+
+    #[test]
+    fn test_build_pattern_kinds() {
+        let all = vec![
+            UrlRestriction::with_pattern(
+                &StorePolicy::NoIndex,
+                &PatternKind::Glob,
+                "https://*.wikipedia.org/*",
+            ),
+            UrlRestriction::with_pattern(&StorePolicy::NoLog, &PatternKind::Domain, "bank.com"),
+            // not a valid regex, skipped
+            UrlRestriction::with_url(&StorePolicy::NoLog, "https://(foo"),
+        ];
+
+        let restrictions = Restrictions::build(&all).unwrap();
+        assert_eq!(restrictions.do_not_log.len(), 1);
+        assert!(!restrictions.should_index_url("https://en.wikipedia.org/wiki/Rust"));
+        assert!(!restrictions.should_log_url("https://www.bank.com/login"));
+        assert!(restrictions.should_log_url("https://foo"));
+        assert!(restrictions.should_index_url("https://wikipedia.com/"));
+    }
 }
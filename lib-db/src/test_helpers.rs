@@ -22,10 +22,9 @@ where
     F: Fn(&Connection) -> Result<()>,
 {
     fn connection(&self) -> Result<Connection> {
-        use diesel::sqlite::SqliteConnection;
         use diesel::Connection as DieselConnection;
 
-        let connection = SqliteConnection::establish(":memory:").expect("in memory database");
+        let connection = Connection::establish(":memory:").expect("in memory database");
         embedded_migrations::run(&connection).expect("create tables");
         (self.initializer)(&connection)?;
 
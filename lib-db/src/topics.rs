@@ -16,6 +16,16 @@ impl TopicStore {
         self.doc2topic.get(url).as_ref().map(|a| a.as_slice())
     }
 
+    // Return all the topics, in index order.
+    pub fn topics(&self) -> &[Topic] {
+        &self.topics
+    }
+
+    // Iterate over the documents and their relevant topics.
+    pub fn documents(&self) -> impl Iterator<Item = (&String, &Vec<RelTopic>)> {
+        self.doc2topic.iter()
+    }
+
     // Return the topic at the given index.
     pub fn topic_at_ndx(&self, ndx: usize) -> &Topic {
         &self.topics[ndx]
@@ -0,0 +1,63 @@
+//! The api token used by the local clients, created with `weaver-data token create`.
+//! Read from the `WEAVER_API_TOKEN` environment variable or from `user-data/api-token`
+//! in the app folder.
+use crate::config::file_utils;
+use lib_error::*;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Name of the environment variable which overrides the token file.
+pub const TOKEN_ENV: &str = "WEAVER_API_TOKEN";
+
+// The token file, in the user-data folder which may not exist yet.
+fn token_path() -> Result<PathBuf> {
+    Ok(file_utils::app_folder()?
+        .join("user-data")
+        .join("api-token"))
+}
+
+/// Load the token to send to the server, if any.
+pub fn load() -> Result<Option<String>> {
+    if let Ok(token) = std::env::var(TOKEN_ENV) {
+        if !token.is_empty() {
+            return Ok(Some(token));
+        }
+    }
+    let path = token_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = file_utils::read_content(&path)?;
+    Ok(content
+        .lines()
+        .next()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(String::from))
+}
+
+/// Save the token for the local clients, only readable by the current user.
+pub fn save(token: &str) -> Result<PathBuf> {
+    let path = token_path()?;
+    if let Some(folder) = path.parent() {
+        fs::create_dir_all(folder).context("create user-data folder".into())?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&path).context("open the token file".into())?;
+    // The mode only applies to a new file, an existing one is restricted before the write.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(format!("{}\n", token).as_bytes())
+        .context("write the token file".into())?;
+    Ok(path)
+}
@@ -1,5 +1,12 @@
+use lib_error::WeaverError;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Prefix used on the command line to read the password from a file.
+pub const KEY_FILE_PREFIX: &str = "key-file:";
+
 /// How to get the password.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum PasswordSource {
     /// Prompt the user for the password
     Prompt,
@@ -8,5 +15,46 @@ pub enum PasswordSource {
     /// Pass in
     PassIn(String),
     /// Read from WEAVER_PASSWORD
-    Environment
+    Environment,
+    /// Read from a file only accessible by the current user
+    KeyFile(PathBuf),
+}
+
+/// Parse the values accepted by the `-P` flag of the command line tools:
+/// `prompt`, `keyring`, `environment` or `key-file:<PATH>`.
+impl FromStr for PasswordSource {
+    type Err = WeaverError;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "prompt" => Ok(PasswordSource::Prompt),
+            "keyring" => Ok(PasswordSource::Keyring),
+            "environment" => Ok(PasswordSource::Environment),
+            _ if s.starts_with(KEY_FILE_PREFIX) && s.len() > KEY_FILE_PREFIX.len() => Ok(
+                PasswordSource::KeyFile(PathBuf::from(&s[KEY_FILE_PREFIX.len()..])),
+            ),
+            _ => Err(WeaverError::from(format!(
+                "cannot parse password source from {}, expected prompt, keyring, environment or {}<PATH>",
+                s, KEY_FILE_PREFIX
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("prompt".parse::<PasswordSource>().unwrap(), PasswordSource::Prompt);
+        assert_eq!(
+            "key-file:/secrets/store-password"
+                .parse::<PasswordSource>()
+                .unwrap(),
+            PasswordSource::KeyFile(PathBuf::from("/secrets/store-password"))
+        );
+        assert!("key-file:".parse::<PasswordSource>().is_err());
+        assert!("other".parse::<PasswordSource>().is_err());
+    }
 }
@@ -1,18 +1,31 @@
 //! File paths, url components and other global configuration information.
 
+pub mod api_token;
 pub mod db;
 mod environment;
 pub mod file_utils;
 pub mod net;
 mod output_kind;
+mod retention;
 mod server_config;
+mod settings;
 
 pub use self::environment::Environment;
 pub use self::output_kind::{Channel, OutputKind};
+pub use self::retention::Retention;
 pub use self::server_config::ServerConfig;
+pub use self::settings::{
+    ClientSettings, IndexSettings, Settings, StoreSettings, SyncSettings, TenantSettings,
+    TlsSettings, UiSettings, ENV_VARS, SETTINGS_FILE,
+};
+
+use std::path::PathBuf;
 
 /// Destination for the `weaver` CLI.
 #[derive(Clone)]
 pub enum Destination {
+    /// Http address of the server.
     Remote(String),
+    /// Https address of the server and the certificate authority trusted for it.
+    Secure(String, PathBuf),
 }
@@ -1,11 +1,21 @@
 //! Url building constants and data structures used over the wire.
 //!
-use crate::entities::{Cycle, FormattedAction};
+use crate::entities::{ActionId, Cycle, FormattedAction, NewAction, PageContent};
 
 pub const API_BASE: &str = "/api";
 pub const ACTIONS2_BASE: &str = "/v2/actions";
 pub const ANNOTATIONS: &str = "/annotations";
 pub const RECOMMENDATIONS: &str = "/recommendations";
+pub const FORGET: &str = "/forget";
+pub const HUD_EVENTS: &str = "/hud/events";
+pub const TAGS: &str = "/tags";
+pub const TRAIL: &str = "/trail";
+pub const STARS: &str = "/stars";
+pub const READING_LISTS: &str = "/reading-lists";
+pub const STATS: &str = "/stats";
+pub const HEALTH: &str = "/health";
+pub const READY: &str = "/ready";
+pub const SYNC: &str = "/sync";
 
 /// A request to change the annotation for a given entry.
 #[derive(::serde::Serialize, ::serde::Deserialize)]
@@ -13,6 +23,54 @@ pub struct Annotation {
     pub annotation: String,
 }
 
+/// Change pushed to the live clients, serialized with a `type` field.
+#[derive(Debug, ::serde::Deserialize, ::serde::Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ActionEvent {
+    /// A new action was saved.
+    Created { action: FormattedAction },
+    /// The annotation of an action was changed.
+    Annotated { id: ActionId, annotation: String },
+    /// The actions were deleted.
+    Deleted { ids: Vec<ActionId> },
+}
+
+/// Select the actions for a bulk operation, all the set criteria must match.
+#[derive(Debug, Default, ::serde::Deserialize, ::serde::Serialize)]
+pub struct ActionFilter {
+    /// Part of the command or url.
+    pub command: Option<String>,
+    /// Host where the action was executed.
+    pub host: Option<String>,
+    /// Executed at or after this RFC3339 time.
+    pub since: Option<String>,
+    /// Executed before this RFC3339 time.
+    pub until: Option<String>,
+}
+
+impl ActionFilter {
+    /// Check if no criteria is set, an empty filter would match all the actions.
+    pub fn is_empty(&self) -> bool {
+        self.command.is_none() && self.host.is_none() && self.since.is_none() && self.until.is_none()
+    }
+}
+
+/// Change some fields of an action, the missing fields are not changed.
+/// An empty epic removes the action from its epic.
+#[derive(Debug, Default, ::serde::Deserialize, ::serde::Serialize)]
+pub struct ActionPatch {
+    pub epic: Option<String>,
+    pub annotation: Option<String>,
+}
+
+/// Summary of the deleted actions and of the rows left unreferenced by them.
+#[derive(Debug, Default, ::serde::Deserialize, ::serde::Serialize)]
+pub struct DeleteReport {
+    pub actions: usize,
+    pub commands: usize,
+    pub locations: usize,
+}
+
 /// A request for paginated data.
 #[derive(Debug, Default, ::serde::Deserialize, ::serde::Serialize)]
 pub struct Pagination {
@@ -36,4 +94,174 @@ pub struct RecommendationQuery {
     pub term: Option<String>,
     pub start: Option<i64>,
     pub length: Option<i64>,
+    /// Only use the actions with this tag.
+    pub tag: Option<String>,
+}
+
+/// Optional tag used to filter actions or search results.
+#[derive(Debug, Default, ::serde::Deserialize, ::serde::Serialize)]
+pub struct TagFilter {
+    pub tag: Option<String>,
+}
+
+/// A tag to add to an action.
+#[derive(::serde::Deserialize, ::serde::Serialize)]
+pub struct TagRequest {
+    pub tag: String,
+}
+
+/// A tag to add to, or remove from, the page with the given url.
+#[derive(::serde::Deserialize, ::serde::Serialize)]
+pub struct PageTagRequest {
+    pub url: String,
+    pub tag: String,
+}
+
+/// Query for the tags of the page with the given url.
+#[derive(::serde::Deserialize, ::serde::Serialize)]
+pub struct PageQuery {
+    pub url: String,
+}
+
+/// A starred page, or a page in a reading list.
+#[derive(Debug, PartialEq, ::serde::Deserialize, ::serde::Serialize)]
+pub struct BookmarkedPage {
+    pub url: String,
+    pub title: Option<String>,
+    /// When the page was starred or added to the list.
+    pub added: String,
+}
+
+/// A reading list and its number of pages.
+#[derive(Debug, PartialEq, ::serde::Deserialize, ::serde::Serialize)]
+pub struct ReadingList {
+    pub name: String,
+    pub pages: usize,
+}
+
+/// Remove the page with the given url from a reading list, or the whole list without url.
+#[derive(::serde::Deserialize, ::serde::Serialize)]
+pub struct ReadingListQuery {
+    pub url: Option<String>,
+}
+
+/// A tag and the number of actions and pages using it.
+#[derive(Debug, ::serde::Deserialize, ::serde::Serialize)]
+pub struct TagCount {
+    pub name: String,
+    pub actions: usize,
+    pub pages: usize,
+}
+
+/// One page in the trail of referrers leading to an action.
+#[derive(Debug, ::serde::Deserialize, ::serde::Serialize)]
+pub struct TrailStep {
+    pub action: FormattedAction,
+    /// How the browser reached the page, `link` or `typed` for example.
+    pub transition: Option<String>,
+    /// Browser tab of the visit, when sent by the extension.
+    pub tab_id: Option<i32>,
+    /// Seconds the page was focused.
+    pub dwell: i64,
+}
+
+/// Request to forget everything matching the pattern, across all the stores.
+#[derive(::serde::Deserialize, ::serde::Serialize)]
+pub struct ForgetRequest {
+    /// Matched as a substring against urls and commands.
+    pub pattern: String,
+}
+
+/// Summary of what was deleted by a forget request.
+#[derive(Debug, Default, ::serde::Deserialize, ::serde::Serialize)]
+pub struct ForgetReport {
+    pub actions: usize,
+    pub commands: usize,
+    pub pages: usize,
+    pub repo_entries: usize,
+    pub index_documents: usize,
+    /// The urls removed from the text index.
+    pub urls: Vec<String>,
+}
+
+/// Request parameters for the activity statistics.
+#[derive(Debug, Default, ::serde::Deserialize, ::serde::Serialize)]
+pub struct StatsQuery {
+    /// Only use the actions from the last days, all the history by default.
+    pub days: Option<u32>,
+    /// Maximum number of entries in the top lists.
+    pub limit: Option<usize>,
+}
+
+/// A name and how many actions use it.
+#[derive(Debug, PartialEq, ::serde::Deserialize, ::serde::Serialize)]
+pub struct NamedCount {
+    pub name: String,
+    pub count: usize,
+}
+
+/// Activity statistics computed from the actions.
+#[derive(Debug, Default, ::serde::Deserialize, ::serde::Serialize)]
+pub struct Stats {
+    pub days: Option<u32>,
+    /// Number of actions in the window.
+    pub total: usize,
+    pub commands: Vec<NamedCount>,
+    pub domains: Vec<NamedCount>,
+    /// Number of actions for each hour of the day, in the local time of the action.
+    pub hours: Vec<usize>,
+    /// Number of actions for each day of the week, starting with Sunday.
+    pub weekdays: Vec<usize>,
+    pub directories: Vec<NamedCount>,
+    pub epics: Vec<NamedCount>,
+}
+
+/// Status of one store in the health reports.
+#[derive(Debug, ::serde::Deserialize, ::serde::Serialize)]
+pub struct StoreStatus {
+    pub name: String,
+    pub ok: bool,
+    /// The server is not ready when a required store fails.
+    pub required: bool,
+    pub message: Option<String>,
+}
+
+/// Outcome of the liveness or readiness checks.
+#[derive(Debug, Default, ::serde::Deserialize, ::serde::Serialize)]
+pub struct HealthReport {
+    pub ok: bool,
+    pub stores: Vec<StoreStatus>,
+}
+
+/// An action pushed by a peer server, with its annotation, tags and transition type.
+#[derive(Default, ::serde::Deserialize, ::serde::Serialize)]
+pub struct SyncAction {
+    pub action: NewAction,
+    #[serde(default)]
+    pub annotation: String,
+    #[serde(default)]
+    pub transition: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Actions and page contents pushed by a peer server.
+#[derive(Default, ::serde::Deserialize, ::serde::Serialize)]
+pub struct SyncBatch {
+    #[serde(default)]
+    pub actions: Vec<SyncAction>,
+    #[serde(default)]
+    pub pages: Vec<PageContent>,
+}
+
+/// What the receiving server did with a sync batch.
+#[derive(Debug, Default, ::serde::Deserialize, ::serde::Serialize)]
+pub struct SyncReport {
+    pub inserted: usize,
+    /// Actions already present, with the same host, time and command.
+    pub duplicates: usize,
+    /// Actions matching a do not log restriction of the receiving server.
+    #[serde(default)]
+    pub restricted: usize,
+    pub pages: usize,
 }
@@ -0,0 +1,92 @@
+//! Age based retention rules, configured in the `[retention]` section of the settings.
+//!
+//! Older setups use `user-data/retention.json` in the app folder, it is read when the settings
+//! have no rules.
+//!
+//! ```json
+//! {
+//!   "shellDays": 365,
+//!   "urlDays": 90,
+//!   "pageDays": 180
+//! }
+//! ```
+use crate::config::{file_utils, Settings};
+use lib_error::*;
+use serde_json as json;
+
+/// How long to keep the data, in days. A missing rule keeps the data forever.
+#[derive(Clone, Debug, Default, PartialEq, ::serde::Deserialize, ::serde::Serialize)]
+pub struct Retention {
+    /// Keep the shell actions this many days.
+    #[serde(alias = "shellDays", skip_serializing_if = "Option::is_none")]
+    pub shell_days: Option<u32>,
+    /// Keep the url actions this many days.
+    #[serde(alias = "urlDays", skip_serializing_if = "Option::is_none")]
+    pub url_days: Option<u32>,
+    /// Keep the page snapshots this many days, unless annotated.
+    #[serde(alias = "pageDays", skip_serializing_if = "Option::is_none")]
+    pub page_days: Option<u32>,
+}
+
+impl Retention {
+    /// Load the retention rules, use the default (keep everything) if not configured.
+    pub fn load() -> Result<Retention> {
+        Settings::load().map(|s| s.retention)
+    }
+
+    /// Load the rules from `retention.json`, used before the settings file existed.
+    pub(crate) fn load_file() -> Result<Retention> {
+        let mut path = file_utils::app_folder()?;
+        path.push("user-data");
+        path.push("retention.json");
+        if !path.exists() {
+            return Ok(Retention::default());
+        }
+        let content = file_utils::read_content(&path)?;
+        let retention = json::from_str(&content).context("reading retention rules".into())?;
+        Ok(retention)
+    }
+
+    /// Check if there is any rule to enforce.
+    pub fn is_empty(&self) -> bool {
+        self.shell_days.is_none() && self.url_days.is_none() && self.page_days.is_none()
+    }
+
+    /// Maximum age in seconds of the actions with the given kind.
+    pub fn max_action_age(&self, kind: &str) -> Option<i64> {
+        let days = match kind {
+            "shell" => self.shell_days,
+            "url" => self.url_days,
+            _ => None,
+        };
+        days.map(days_to_seconds)
+    }
+
+    /// Maximum age in seconds of the page snapshots.
+    pub fn max_page_age(&self) -> Option<i64> {
+        self.page_days.map(days_to_seconds)
+    }
+}
+
+fn days_to_seconds(days: u32) -> i64 {
+    i64::from(days) * 24 * 3600
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let retention = json::from_str::<Retention>(r#"{"shellDays": 2, "pageDays": 1}"#).unwrap();
+        assert_eq!(retention.max_action_age("shell"), Some(2 * 24 * 3600));
+        assert_eq!(retention.max_action_age("url"), None);
+        assert_eq!(retention.max_page_age(), Some(24 * 3600));
+        assert!(!retention.is_empty());
+    }
+
+    #[test]
+    fn test_default_is_empty() {
+        assert!(Retention::default().is_empty());
+    }
+}
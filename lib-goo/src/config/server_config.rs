@@ -1,3 +1,4 @@
+use crate::config::Settings;
 use lib_error::*;
 /// Ports and other information about the server.
 use std::net::{TcpListener, ToSocketAddrs};
@@ -5,11 +6,16 @@ use std::net::{TcpListener, ToSocketAddrs};
 pub const HTTP_PORT: u16 = 8466;
 pub const HTTPS_PORT: u16 = 8467;
 
+#[derive(Clone, Debug, PartialEq, ::serde::Deserialize, ::serde::Serialize)]
+#[serde(default)]
 pub struct ServerConfig {
     pub http_port: u16,
     pub https_port: u16,
     pub address: String,
     pub base_url: String,
+    /// Accept the api requests without a token until the first token is created, the api
+    /// is closed by default.
+    pub open_api: bool,
 }
 
 impl Default for ServerConfig {
@@ -19,13 +25,15 @@ impl Default for ServerConfig {
             https_port: HTTPS_PORT,
             address: String::from("127.0.0.1"),
             base_url: String::from("/"),
+            open_api: false,
         }
     }
 }
 
 impl ServerConfig {
+    /// The server section of the settings, the defaults if the settings cannot be loaded.
     pub fn current() -> ServerConfig {
-        ServerConfig::default()
+        Settings::load_or_default().server
     }
 
     pub fn actix_address(&self) -> String {
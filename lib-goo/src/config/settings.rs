@@ -0,0 +1,826 @@
+//! Layered settings shared by `weaver`, `weaver-server` and `weaver-data`.
+//!
+//! The values are the defaults, overridden by `config.toml` in the app folder, overridden by
+//! the `WEAVER_*` environment variables. The command line flags are applied last by each tool.
+//!
+//! ```toml
+//! [server]
+//! http_port = 8466
+//! https_port = 8467
+//! address = "127.0.0.1"
+//! base_url = "/"
+//! open_api = false
+//!
+//! [client]
+//! server = "weaver.example.com:8467"
+//! https = true
+//!
+//! [tls]
+//! ca = "/home/me/.weaver/server/ca.crt"
+//! cert = "/home/me/.weaver/server/localhost.crt"
+//! key = "/home/me/.weaver/server/localhost.key"
+//! pfx = "/home/me/.weaver/server/localhost.pfx"
+//! pfx_password = "secret"
+//!
+//! [store]
+//! password_source = "key-file:/home/me/.weaver-password"
+//!
+//! [index]
+//! writer_memory = 10000000
+//! search_limit = 40
+//! keep_html = false
+//!
+//! [ui]
+//! templates = "/home/me/.weaver/templates"
+//! assets = "/home/me/.weaver/assets"
+//!
+//! [retention]
+//! shell_days = 365
+//! url_days = 90
+//!
+//! [sync]
+//! peer = "desktop.local:8467"
+//! https = true
+//! token = "wvr_..."
+//! pages = true
+//!
+//! [tenants]
+//! enabled = true
+//! folder = "/data/tenants"
+//! keys = "/secrets/tenants"
+//! ```
+use crate::config::db::PasswordSource;
+use crate::config::{file_utils, Destination, Retention, ServerConfig};
+use lib_error::*;
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Name of the settings file in the app folder.
+pub const SETTINGS_FILE: &str = "config.toml";
+
+/// An environment variable overriding one setting.
+pub struct EnvVar {
+    pub name: &'static str,
+    /// The overridden setting, `section.key`.
+    pub setting: &'static str,
+    set: fn(&mut Settings, String) -> Result<()>,
+}
+
+/// Environment variables overriding the settings.
+pub const ENV_VARS: [EnvVar; 29] = [
+    EnvVar {
+        name: "WEAVER_HTTP_PORT",
+        setting: "server.http_port",
+        set: |s, v| {
+            s.server.http_port = parse_var(&v)?;
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_HTTPS_PORT",
+        setting: "server.https_port",
+        set: |s, v| {
+            s.server.https_port = parse_var(&v)?;
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_ADDRESS",
+        setting: "server.address",
+        set: |s, v| {
+            s.server.address = v;
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_BASE_URL",
+        setting: "server.base_url",
+        set: |s, v| {
+            s.server.base_url = v;
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_OPEN_API",
+        setting: "server.open_api",
+        set: |s, v| {
+            s.server.open_api = parse_var(&v)?;
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_SERVER",
+        setting: "client.server",
+        set: |s, v| {
+            s.client.server = Some(v);
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_HTTPS",
+        setting: "client.https",
+        set: |s, v| {
+            s.client.https = parse_var(&v)?;
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_TLS_CA",
+        setting: "tls.ca",
+        set: |s, v| {
+            s.tls.ca = Some(PathBuf::from(v));
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_TLS_CERT",
+        setting: "tls.cert",
+        set: |s, v| {
+            s.tls.cert = Some(PathBuf::from(v));
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_TLS_KEY",
+        setting: "tls.key",
+        set: |s, v| {
+            s.tls.key = Some(PathBuf::from(v));
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_TLS_PFX",
+        setting: "tls.pfx",
+        set: |s, v| {
+            s.tls.pfx = Some(PathBuf::from(v));
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_TLS_PFX_PASSWORD",
+        setting: "tls.pfx_password",
+        set: |s, v| {
+            s.tls.pfx_password = Some(v);
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_PASSWORD_SOURCE",
+        setting: "store.password_source",
+        set: |s, v| {
+            s.store.password_source = Some(v);
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_INDEX_WRITER_MEMORY",
+        setting: "index.writer_memory",
+        set: |s, v| {
+            s.index.writer_memory = parse_var(&v)?;
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_INDEX_SEARCH_LIMIT",
+        setting: "index.search_limit",
+        set: |s, v| {
+            s.index.search_limit = parse_var(&v)?;
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_INDEX_KEEP_HTML",
+        setting: "index.keep_html",
+        set: |s, v| {
+            s.index.keep_html = parse_var(&v)?;
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_UI_TEMPLATES",
+        setting: "ui.templates",
+        set: |s, v| {
+            s.ui.templates = Some(PathBuf::from(v));
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_UI_ASSETS",
+        setting: "ui.assets",
+        set: |s, v| {
+            s.ui.assets = Some(PathBuf::from(v));
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_RETENTION_SHELL_DAYS",
+        setting: "retention.shell_days",
+        set: |s, v| {
+            s.retention.shell_days = Some(parse_var(&v)?);
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_RETENTION_URL_DAYS",
+        setting: "retention.url_days",
+        set: |s, v| {
+            s.retention.url_days = Some(parse_var(&v)?);
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_RETENTION_PAGE_DAYS",
+        setting: "retention.page_days",
+        set: |s, v| {
+            s.retention.page_days = Some(parse_var(&v)?);
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_SYNC_PEER",
+        setting: "sync.peer",
+        set: |s, v| {
+            s.sync.peer = Some(v);
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_SYNC_HTTPS",
+        setting: "sync.https",
+        set: |s, v| {
+            s.sync.https = parse_var(&v)?;
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_SYNC_TOKEN",
+        setting: "sync.token",
+        set: |s, v| {
+            s.sync.token = Some(v);
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_SYNC_PAGES",
+        setting: "sync.pages",
+        set: |s, v| {
+            s.sync.pages = parse_var(&v)?;
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_SYNC_INTERVAL",
+        setting: "sync.interval_secs",
+        set: |s, v| {
+            s.sync.interval_secs = parse_var(&v)?;
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_TENANTS",
+        setting: "tenants.enabled",
+        set: |s, v| {
+            s.tenants.enabled = parse_var(&v)?;
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_TENANTS_FOLDER",
+        setting: "tenants.folder",
+        set: |s, v| {
+            s.tenants.folder = Some(PathBuf::from(v));
+            Ok(())
+        },
+    },
+    EnvVar {
+        name: "WEAVER_TENANTS_KEYS",
+        setting: "tenants.keys",
+        set: |s, v| {
+            s.tenants.keys = Some(PathBuf::from(v));
+            Ok(())
+        },
+    },
+];
+
+/// Password of the pfx bundle when none is configured, kept for the existing setups.
+const DEFAULT_PFX_PASSWORD: &str = "1234";
+
+/// Certificates used by the server, the paths default to the `server` folder.
+#[derive(Clone, Debug, Default, PartialEq, ::serde::Deserialize, ::serde::Serialize)]
+#[serde(default)]
+pub struct TlsSettings {
+    /// PEM certificate of the authority signing the server certificate, trusted by the clients.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca: Option<PathBuf>,
+    /// PEM certificate chain, used by rustls.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cert: Option<PathBuf>,
+    /// PEM PKCS8 private key, used by rustls.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<PathBuf>,
+    /// PKCS12 bundle, used by native-tls.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pfx: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pfx_password: Option<String>,
+}
+
+impl TlsSettings {
+    fn in_server_folder(path: &Option<PathBuf>, name: &str) -> Result<PathBuf> {
+        match path {
+            Some(p) => Ok(p.clone()),
+            None => file_utils::app_folder().map(|f| f.join("server").join(name)),
+        }
+    }
+
+    pub fn ca_path(&self) -> Result<PathBuf> {
+        Self::in_server_folder(&self.ca, "ca.crt")
+    }
+
+    pub fn cert_path(&self) -> Result<PathBuf> {
+        Self::in_server_folder(&self.cert, "localhost.crt")
+    }
+
+    pub fn key_path(&self) -> Result<PathBuf> {
+        Self::in_server_folder(&self.key, "localhost.key")
+    }
+
+    pub fn pfx_path(&self) -> Result<PathBuf> {
+        Self::in_server_folder(&self.pfx, "localhost.pfx")
+    }
+
+    pub fn pfx_password(&self) -> &str {
+        self.pfx_password
+            .as_ref()
+            .map(|p| p.as_str())
+            .unwrap_or(DEFAULT_PFX_PASSWORD)
+    }
+}
+
+/// Where the command line tools find the server.
+#[derive(Clone, Debug, Default, PartialEq, ::serde::Deserialize, ::serde::Serialize)]
+#[serde(default)]
+pub struct ClientSettings {
+    /// `host:port` of the server, the local server when missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    /// Use https, the server certificate needs to be signed by `tls.ca`.
+    pub https: bool,
+}
+
+/// How to unlock the encrypted stores.
+#[derive(Clone, Debug, Default, PartialEq, ::serde::Deserialize, ::serde::Serialize)]
+#[serde(default)]
+pub struct StoreSettings {
+    /// Same values as the `-P` flag, the keyring is used when missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_source: Option<String>,
+}
+
+impl StoreSettings {
+    pub fn password_source(&self) -> Result<PasswordSource> {
+        match self.password_source {
+            Some(ref source) => source.parse::<PasswordSource>(),
+            None => Ok(PasswordSource::Keyring),
+        }
+    }
+}
+
+/// Tuning of the text index.
+#[derive(Clone, Debug, PartialEq, ::serde::Deserialize, ::serde::Serialize)]
+#[serde(default)]
+pub struct IndexSettings {
+    /// Memory of the index writer, in bytes.
+    pub writer_memory: usize,
+    /// Maximum number of documents returned by a search.
+    pub search_limit: usize,
+    /// Save the raw html sent by the extension in the repo, next to the extracted text.
+    pub keep_html: bool,
+}
+
+impl Default for IndexSettings {
+    fn default() -> Self {
+        IndexSettings {
+            writer_memory: 10_000_000,
+            search_limit: 40,
+            keep_html: false,
+        }
+    }
+}
+
+/// Folders overriding the templates and assets embedded in the server, by file name.
+#[derive(Clone, Debug, Default, PartialEq, ::serde::Deserialize, ::serde::Serialize)]
+#[serde(default)]
+pub struct UiSettings {
+    /// Tera templates, `templates` in the app folder when missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub templates: Option<PathBuf>,
+    /// Stylesheets, scripts and images served under `/assets`, `assets` in the app folder
+    /// when missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assets: Option<PathBuf>,
+}
+
+impl UiSettings {
+    fn in_app_folder(path: &Option<PathBuf>, name: &str) -> Result<PathBuf> {
+        match path {
+            Some(p) => Ok(p.clone()),
+            None => file_utils::app_folder().map(|f| f.join(name)),
+        }
+    }
+
+    pub fn templates_path(&self) -> Result<PathBuf> {
+        Self::in_app_folder(&self.templates, "templates")
+    }
+
+    pub fn assets_path(&self) -> Result<PathBuf> {
+        Self::in_app_folder(&self.assets, "assets")
+    }
+}
+
+/// Replication of the actions to a peer server.
+#[derive(Clone, Debug, PartialEq, ::serde::Deserialize, ::serde::Serialize)]
+#[serde(default)]
+pub struct SyncSettings {
+    /// `host:port` of the peer, nothing is pushed when missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer: Option<String>,
+    /// Use https, the peer certificate needs to be signed by `tls.ca`. Plain http sends the
+    /// token and the history in clear, it is only meant for a peer on the same machine.
+    pub https: bool,
+    /// Api token created on the peer with `weaver-data token create`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Also push the page contents saved in the repo.
+    pub pages: bool,
+    /// Seconds between two pushes.
+    pub interval_secs: u64,
+}
+
+impl Default for SyncSettings {
+    fn default() -> Self {
+        SyncSettings {
+            peer: None,
+            https: true,
+            token: None,
+            pages: false,
+            interval_secs: 300,
+        }
+    }
+}
+
+impl SyncSettings {
+    /// The peer to push to, None when replication is not configured.
+    pub fn destination(&self, tls: &TlsSettings) -> Result<Option<Destination>> {
+        match self.peer {
+            Some(ref peer) if self.https => {
+                Ok(Some(Destination::Secure(peer.clone(), tls.ca_path()?)))
+            }
+            Some(ref peer) => {
+                if !is_local(peer) {
+                    ::log::warn!(
+                        "sync.https is off, the token and the history are sent in clear to {}",
+                        peer
+                    );
+                }
+                Ok(Some(Destination::Remote(peer.clone())))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+// Check if the `host:port` address is on this machine.
+fn is_local(address: &str) -> bool {
+    let host = match address.rfind(':') {
+        Some(i) if !address.ends_with(']') => &address[..i],
+        _ => address,
+    };
+    ["localhost", "127.0.0.1", "[::1]"].contains(&host)
+}
+
+/// Multi-tenant server, each api token names a tenant with its own stores.
+#[derive(Clone, Debug, Default, PartialEq, ::serde::Deserialize, ::serde::Serialize)]
+#[serde(default)]
+pub struct TenantSettings {
+    /// Serve the api from the stores of the tenant of the token, tokens are always required.
+    pub enabled: bool,
+    /// One sub folder of stores per tenant, `tenants` in the app folder when missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folder: Option<PathBuf>,
+    /// Folder of key files named after the tenants. When missing the repos use the
+    /// password of the server, with a salt of their own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keys: Option<PathBuf>,
+}
+
+impl TenantSettings {
+    pub fn folder_path(&self) -> Result<PathBuf> {
+        match self.folder {
+            Some(ref p) => Ok(p.clone()),
+            None => file_utils::app_folder().map(|f| f.join("tenants")),
+        }
+    }
+
+    /// How to unlock the repo of the tenant, `server` is the source of the server.
+    pub fn password_source(&self, name: &str, server: &PasswordSource) -> PasswordSource {
+        match self.keys {
+            Some(ref keys) => PasswordSource::KeyFile(keys.join(name)),
+            None => server.clone(),
+        }
+    }
+}
+
+/// All the settings, one section per area.
+#[derive(Clone, Debug, Default, PartialEq, ::serde::Deserialize, ::serde::Serialize)]
+#[serde(default)]
+pub struct Settings {
+    pub server: ServerConfig,
+    pub client: ClientSettings,
+    pub tls: TlsSettings,
+    pub store: StoreSettings,
+    pub index: IndexSettings,
+    pub ui: UiSettings,
+    pub retention: Retention,
+    pub sync: SyncSettings,
+    pub tenants: TenantSettings,
+}
+
+// Parse the value of an environment variable.
+fn parse_var<T: FromStr>(value: &str) -> Result<T> {
+    value
+        .parse::<T>()
+        .map_err(|_| WeaverError::from(format!("bad value {:?}", value)))
+}
+
+impl Settings {
+    /// The path of the settings file.
+    pub fn path() -> Result<PathBuf> {
+        file_utils::app_folder().map(|f| f.join(SETTINGS_FILE))
+    }
+
+    /// Load the effective settings: defaults, settings file then environment.
+    pub fn load() -> Result<Settings> {
+        let path = Self::path()?;
+        let mut settings = if path.exists() {
+            Self::parse(&file_utils::read_content(&path)?)
+                .map_err(|e| WeaverError::from(format!("{}: {}", path.display(), e)))?
+        } else {
+            Settings::default()
+        };
+        // Older setups keep the retention rules in their own file.
+        if settings.retention.is_empty() {
+            settings.retention = Retention::load_file()?;
+        }
+        settings.apply_env(|name| env::var(name).ok())?;
+        Ok(settings)
+    }
+
+    /// The effective settings for the tools which should keep working with a broken settings
+    /// file, like the shell prompt: the defaults are used instead, with a warning.
+    pub fn load_or_default() -> Settings {
+        match Self::load() {
+            Ok(settings) => settings,
+            Err(e) => {
+                ::log::warn!("cannot load the settings, using the defaults: {}", e);
+                Settings::default()
+            }
+        }
+    }
+
+    /// Parse and validate the content of a settings file.
+    pub fn parse(content: &str) -> Result<Settings> {
+        let settings: Settings = toml::from_str(content)
+            .map_err(|e| WeaverError::from(format!("cannot parse settings {}", e)))?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Override the settings with the environment variables, `var` returns their values.
+    pub fn apply_env<F>(&mut self, var: F) -> Result<()>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        for env_var in ENV_VARS.iter() {
+            if let Some(value) = var(env_var.name) {
+                (env_var.set)(self, value)
+                    .map_err(|e| WeaverError::from(format!("{} {}", env_var.name, e)))?;
+            }
+        }
+        self.validate()
+    }
+
+    /// Check the values which cannot be checked by the parser.
+    pub fn validate(&self) -> Result<()> {
+        self.store.password_source()?;
+        if !self.server.base_url.starts_with('/') {
+            return Err(WeaverError::from(format!(
+                "server.base_url must start with /, got {}",
+                self.server.base_url
+            )));
+        }
+        // Tantivy needs at least 3MB per indexing thread.
+        if self.index.writer_memory < 3_000_000 {
+            return Err("index.writer_memory must be at least 3000000".into());
+        }
+        if self.index.search_limit == 0 {
+            return Err("index.search_limit must be positive".into());
+        }
+        if self.sync.interval_secs == 0 {
+            return Err("sync.interval_secs must be positive".into());
+        }
+        Ok(())
+    }
+
+    /// The server used by the command line tools.
+    pub fn destination(&self) -> Result<Destination> {
+        if self.client.https {
+            let address = match self.client.server {
+                Some(ref s) => s.clone(),
+                None => format!("localhost:{}", self.server.https_port),
+            };
+            Ok(Destination::Secure(address, self.tls.ca_path()?))
+        } else {
+            let address = match self.client.server {
+                Some(ref s) => s.clone(),
+                None => self.server.actix_address(),
+            };
+            Ok(Destination::Remote(address))
+        }
+    }
+
+    /// Display the settings in the format of the settings file, with the default paths
+    /// filled in and the passwords hidden.
+    pub fn show(&self) -> Result<String> {
+        let mut shown = self.clone();
+        shown.tls.ca = Some(self.tls.ca_path()?);
+        shown.tls.cert = Some(self.tls.cert_path()?);
+        shown.tls.key = Some(self.tls.key_path()?);
+        shown.tls.pfx = Some(self.tls.pfx_path()?);
+        shown.tls.pfx_password = Some("********".into());
+        if shown.sync.token.is_some() {
+            shown.sync.token = Some("********".into());
+        }
+        shown.ui.templates = Some(self.ui.templates_path()?);
+        shown.ui.assets = Some(self.ui.assets_path()?);
+        shown.tenants.folder = Some(self.tenants.folder_path()?);
+        if shown.store.password_source.is_none() {
+            shown.store.password_source = Some("keyring".into());
+        }
+        toml::to_string_pretty(&shown)
+            .map_err(|e| WeaverError::from(format!("cannot encode settings {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_parse() {
+        let settings = Settings::parse(
+            r#"
+            [server]
+            http_port = 9000
+            [store]
+            password_source = "environment"
+            [retention]
+            url_days = 30
+            "#,
+        )
+        .expect("parse");
+        assert_eq!(settings.server.http_port, 9000);
+        assert_eq!(settings.server.https_port, 8467);
+        assert_eq!(settings.server.address, "127.0.0.1");
+        assert!(!settings.server.open_api);
+        assert_eq!(
+            settings.store.password_source().unwrap(),
+            PasswordSource::Environment
+        );
+        assert_eq!(settings.retention.url_days, Some(30));
+        assert_eq!(settings.index, IndexSettings::default());
+        assert_eq!(settings.tls.pfx_password(), "1234");
+
+        assert!(Settings::parse("[server]\nhttp_port = \"x\"").is_err());
+        assert!(Settings::parse("[store]\npassword_source = \"other\"").is_err());
+        assert!(Settings::parse("[index]\nsearch_limit = 0").is_err());
+    }
+
+    #[test]
+    fn test_destination() {
+        let settings = Settings::default();
+        match settings.destination().expect("destination") {
+            Destination::Remote(address) => assert_eq!(address, "localhost:8466"),
+            _ => panic!("expected http"),
+        }
+
+        let settings = Settings::parse(
+            r#"
+            [client]
+            server = "weaver.example.com:443"
+            https = true
+            [tls]
+            ca = "/etc/weaver/ca.crt"
+            "#,
+        )
+        .expect("parse");
+        match settings.destination().expect("destination") {
+            Destination::Secure(address, ca) => {
+                assert_eq!(address, "weaver.example.com:443");
+                assert_eq!(ca, PathBuf::from("/etc/weaver/ca.crt"));
+            }
+            _ => panic!("expected https"),
+        }
+    }
+
+    #[test]
+    fn test_sync_destination() {
+        let settings = Settings::default();
+        assert!(settings
+            .sync
+            .destination(&settings.tls)
+            .expect("destination")
+            .is_none());
+
+        let settings = Settings::parse(
+            r#"
+            [sync]
+            peer = "desktop.local:8467"
+            https = true
+            [tls]
+            ca = "/etc/weaver/ca.crt"
+            "#,
+        )
+        .expect("parse");
+        match settings.sync.destination(&settings.tls).expect("destination") {
+            Some(Destination::Secure(address, _)) => assert_eq!(address, "desktop.local:8467"),
+            _ => panic!("expected https"),
+        }
+        assert_eq!(settings.sync.interval_secs, 300);
+        assert!(Settings::parse("[sync]\ninterval_secs = 0").is_err());
+
+        let settings = Settings::parse("[sync]\npeer = \"localhost:8466\"\nhttps = false")
+            .expect("parse");
+        match settings.sync.destination(&settings.tls).expect("destination") {
+            Some(Destination::Remote(address)) => assert_eq!(address, "localhost:8466"),
+            _ => panic!("expected http"),
+        }
+        assert!(Settings::default().sync.https);
+        assert!(is_local("[::1]:8466"));
+        assert!(is_local("127.0.0.1:8466"));
+        assert!(!is_local("desktop.local:8466"));
+    }
+
+    #[test]
+    fn test_apply_env() {
+        let vars: HashMap<&str, &str> = vec![
+            ("WEAVER_HTTP_PORT", "9100"),
+            ("WEAVER_BASE_URL", "/wr"),
+            ("WEAVER_TLS_PFX_PASSWORD", "secret"),
+            ("WEAVER_RETENTION_PAGE_DAYS", "7"),
+            ("WEAVER_TENANTS", "true"),
+            ("WEAVER_TENANTS_KEYS", "/secrets/tenants"),
+        ]
+        .into_iter()
+        .collect();
+        let mut settings =
+            Settings::parse("[server]\nhttp_port = 9000\nbase_url = \"/x\"").unwrap();
+        settings
+            .apply_env(|name| vars.get(name).map(|v| v.to_string()))
+            .expect("apply env");
+        assert_eq!(settings.server.http_port, 9100);
+        assert_eq!(settings.server.base_url, "/wr");
+        assert_eq!(settings.tls.pfx_password(), "secret");
+        assert_eq!(settings.retention.page_days, Some(7));
+        assert!(settings.tenants.enabled);
+        assert_eq!(
+            settings
+                .tenants
+                .password_source("alice", &PasswordSource::Environment),
+            PasswordSource::KeyFile(PathBuf::from("/secrets/tenants/alice"))
+        );
+
+        let mut settings = Settings::default();
+        assert!(settings
+            .apply_env(|name| match name {
+                "WEAVER_HTTPS_PORT" => Some("many".into()),
+                _ => None,
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn test_show_hides_password() {
+        let mut settings = Settings::default();
+        settings.tls.pfx_password = Some("secret".into());
+        settings.sync.token = Some("wvr_secret".into());
+        let shown = settings.show().expect("show");
+        assert!(!shown.contains("secret"));
+        assert!(shown.contains("[server]"));
+        // The output is a valid settings file.
+        let parsed = Settings::parse(&shown).expect("parse shown");
+        assert_eq!(parsed.store.password_source, Some("keyring".into()));
+    }
+}
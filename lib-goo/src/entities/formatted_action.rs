@@ -67,6 +67,12 @@ pub struct FormattedAction {
     pub location: Option<String>,
     /// The reason why this action is being recommended.
     pub reason: RecommendReason,
+    /// The page of the url action is starred.
+    #[serde(default)]
+    pub starred: bool,
+    /// Tags of the action, sorted by name.
+    #[serde(default)]
+    pub tags: Vec<String>,
     pub when: Option<Date>,
 }
 
@@ -1,7 +1,7 @@
 pub use self::epic::Epic;
 pub use self::formatted_action::{ActionId, Cycle, FormattedAction, RecommendReason};
 pub use self::new_action::NewAction;
-pub use self::page_content::PageContent;
+pub use self::page_content::{PageContent, PageHtml};
 
 mod epic;
 pub mod flow;
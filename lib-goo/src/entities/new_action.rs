@@ -52,14 +52,15 @@ impl NewAction {
         })
     }
 
-    pub fn build_from_url(url: &str, location: &str, epic: Option<&str>) -> Result<NewAction> {
+    /// A visit of the url, its transition type is saved with the navigation.
+    pub fn build_from_url(url: &str, epic: Option<&str>) -> Result<NewAction> {
         let host = sys_info::hostname().context("build url in new action".into())?;
         let executed = now();
         Ok(NewAction {
             executed,
             kind: "url".into(),
             command: url.into(),
-            location: Some(location.into()),
+            location: None,
             epic: epic.map(String::from),
             host,
             parent_id: None,
@@ -4,7 +4,19 @@
 pub struct PageContent {
     pub url: String,
     pub title: String,
+    /// Main text of the page, without the navigation and the scripts.
     pub body: String,
+    /// ISO 639-3 code of the language of the body, when it could be detected.
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub word_count: usize,
+    /// Page description or the beginning of the body.
+    #[serde(default)]
+    pub excerpt: Option<String>,
+    /// Absolute urls linked from the main content.
+    #[serde(default)]
+    pub links: Vec<String>,
 }
 
 impl PageContent {
@@ -13,3 +25,17 @@ impl PageContent {
         "page-content"
     }
 }
+
+/// Raw html of a page, saved in the repo when `index.keep_html` is set.
+#[derive(Clone, Debug, ::serde::Serialize, ::serde::Deserialize, Default)]
+pub struct PageHtml {
+    pub url: String,
+    pub html: String,
+}
+
+impl PageHtml {
+    /// Name of the collection to use in the encrypted repo.
+    pub fn collection_name() -> &'static str {
+        "page-html"
+    }
+}
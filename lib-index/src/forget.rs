@@ -0,0 +1,78 @@
+//! Forget everything matching a pattern, across the sqlite store, the encrypted repo
+//! and the text index.
+use crate::indexer::Indexer;
+use crate::repo::{decode_action, decode_page, Collection, Repo};
+use bincode;
+use lib_db::{self, Connection};
+use lib_error::*;
+use lib_goo::config::net::ForgetReport;
+use lib_goo::entities::{NewAction, PageContent, PageHtml};
+
+/// Delete the actions, commands, pages, repo entries and text index documents
+/// whose url or command contains the pattern. Returns what was deleted.
+pub fn forget(
+    connection: &Connection,
+    repo: &Repo,
+    indexer: &Indexer,
+    pattern: &str,
+) -> Result<ForgetReport> {
+    let mut report = ForgetReport::default();
+    lib_db::forget::forget(connection, pattern, &mut report)?;
+
+    // Page content uploaded by the Chrome extension.
+    let pages = Collection(PageContent::collection_name().into());
+    for handle in repo.list(&pages)? {
+        let content = repo.read(&pages, &handle.id)?;
+        match decode_page(&content) {
+            Ok(page_content) => {
+                if page_content.url.contains(pattern) {
+                    repo.delete(&pages, &handle.id)?;
+                    report.repo_entries += 1;
+                    report.urls.push(page_content.url);
+                }
+            }
+            Err(e) => ::log::warn!("skipping page content {}: {:?}", handle.id, e),
+        }
+    }
+
+    // Raw html of the pages, only saved when `index.keep_html` is set.
+    let html = Collection(PageHtml::collection_name().into());
+    for handle in repo.list(&html)? {
+        let content = repo.read(&html, &handle.id)?;
+        match bincode::deserialize::<PageHtml>(&content) {
+            Ok(page_html) => {
+                if page_html.url.contains(pattern) {
+                    repo.delete(&html, &handle.id)?;
+                    report.repo_entries += 1;
+                }
+            }
+            Err(e) => ::log::warn!("skipping page html {}: {:?}", handle.id, e),
+        }
+    }
+
+    // Actions from the shell and the browser.
+    let actions = Collection(NewAction::collection_name().into());
+    for handle in repo.list(&actions)? {
+        let content = repo.read(&actions, &handle.id)?;
+        match decode_action(&content) {
+            Ok(action) => {
+                if action.command.contains(pattern) {
+                    repo.delete(&actions, &handle.id)?;
+                    report.repo_entries += 1;
+                }
+            }
+            Err(e) => ::log::warn!("skipping action {}: {:?}", handle.id, e),
+        }
+    }
+
+    // The text index uses the normalized url as the document id.
+    report.urls.sort();
+    report.urls.dedup();
+    for url in &report.urls {
+        if indexer.delete(url)? {
+            report.index_documents += 1;
+        }
+    }
+
+    Ok(report)
+}
@@ -11,10 +11,21 @@ pub struct Results {
     pub matches: Vec<PageContent>,
 }
 
+/// Size of the index, used for monitoring.
+#[derive(Debug, Default)]
+pub struct IndexStats {
+    pub documents: u64,
+    pub segments: usize,
+}
+
 /// Public/light interface to the indexer.
 pub trait Indexer {
     fn add(&self, page_content: &PageContent) -> Result<(u64)>;
-    fn delete(&self, id: &str) -> Result<()>;
+    /// Delete the document with this id, returns false when there was none.
+    fn delete(&self, id: &str) -> Result<bool>;
     fn search(&self, what: &str) -> Result<Results>;
+    /// Search like `search`, returning at most `limit` matches.
+    fn search_top(&self, what: &str, limit: usize) -> Result<Results>;
     fn summary(&self) -> Option<String>;
+    fn stats(&self) -> Result<IndexStats>;
 }
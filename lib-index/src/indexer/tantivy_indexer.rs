@@ -1,18 +1,20 @@
 //! Provide an interface to the Tantivy index.
 //!
-use crate::indexer::{Indexer, Results};
+use crate::indexer::{IndexStats, Indexer, Results};
 use lib_error::*;
 use lib_goo::config::file_utils::app_folder;
+use lib_goo::config::IndexSettings;
 use lib_goo::entities::PageContent;
 use std::fs;
-use std::path::PathBuf;
-use tantivy::collector::TopDocs;
-use tantivy::query::QueryParser;
+use std::path::{Path, PathBuf};
+use tantivy::collector::{Count, TopDocs};
+use tantivy::query::{QueryParser, TermQuery};
 use tantivy::schema::*;
 use tantivy::Index;
 
 pub struct TantivyIndexer {
     index: Index,
+    settings: IndexSettings,
 }
 
 fn index_path() -> Result<PathBuf> {
@@ -24,12 +26,20 @@ fn index_path() -> Result<PathBuf> {
 impl TantivyIndexer {
     /// Build the application wide indexer. If the index is not setup properly this will
     /// fail and the user should call the setup function.
-    pub fn build() -> Result<Self> {
-        let index_path = index_path()?;
+    pub fn build(settings: IndexSettings) -> Result<Self> {
+        Self::build_in(&index_path()?, settings)
+    }
 
+    /// Build the indexer for the index in the folder, created when missing.
+    pub fn build_in(index_path: &Path, settings: IndexSettings) -> Result<Self> {
+        Self::setup_in(index_path)?;
         let index = Index::open_in_dir(index_path)?;
+        Ok(Self { index, settings })
+    }
 
-        Ok(Self { index })
+    /// The folder holding the index.
+    pub fn location() -> Result<PathBuf> {
+        index_path()
     }
 
     /// Delete all the files int the index.
@@ -43,9 +53,13 @@ impl TantivyIndexer {
 
     /// Setup the index
     pub fn setup_if_needed() -> Result<()> {
-        let index_path = index_path()?;
+        Self::setup_in(&index_path()?)
+    }
+
+    /// Create the index in the folder if missing.
+    pub fn setup_in(index_path: &Path) -> Result<()> {
         if !index_path.exists() {
-            fs::create_dir(&index_path)?;
+            fs::create_dir_all(index_path)?;
             let mut schema_builder = SchemaBuilder::default();
 
             schema_builder.add_text_field("id", STRING | STORED);
@@ -55,7 +69,7 @@ impl TantivyIndexer {
             schema_builder.add_text_field("body", TEXT);
 
             let schema = schema_builder.build();
-            let _ = Index::create_in_dir(index_path.clone(), schema)?;
+            let _ = Index::create_in_dir(index_path, schema)?;
         }
 
         Ok(())
@@ -76,7 +90,9 @@ impl TantivyIndexer {
 
 impl Indexer for TantivyIndexer {
     fn add(&self, page_content: &PageContent) -> Result<(u64)> {
-        let mut index_writer = self.index.writer_with_num_threads(1, 10_000_000)?;
+        let mut index_writer = self
+            .index
+            .writer_with_num_threads(1, self.settings.writer_memory)?;
 
         let schema = self.index.schema();
         let f_id = schema.get_field("id").expect("get id field");
@@ -93,18 +109,30 @@ impl Indexer for TantivyIndexer {
         Ok(id)
     }
 
-    fn delete(&self, id: &str) -> Result<()> {
-        let mut index_writer = self.index.writer(50_000_000)?;
-
+    fn delete(&self, id: &str) -> Result<bool> {
         let schema = self.index.schema();
         let f_id = schema.get_field("id").expect("get id field");
         let term = Term::from_field_text(f_id, id);
+
+        self.index.load_searchers()?;
+        let query = TermQuery::new(term.clone(), IndexRecordOption::Basic);
+        if self.index.searcher().search(&query, &Count)? == 0 {
+            return Ok(false);
+        }
+
+        let mut index_writer = self
+            .index
+            .writer_with_num_threads(1, self.settings.writer_memory)?;
         index_writer.delete_term(term);
         index_writer.commit()?;
-        Ok(())
+        Ok(true)
     }
 
     fn search(&self, what: &str) -> Result<Results> {
+        self.search_top(what, self.settings.search_limit)
+    }
+
+    fn search_top(&self, what: &str, limit: usize) -> Result<Results> {
         self.index.load_searchers()?;
 
         // Afterwards create one (or more) searchers.
@@ -143,7 +171,7 @@ impl Indexer for TantivyIndexer {
         // We are not interested in all of the documents but
         // only in the top N. Keeping track of our top best documents
         // is the role of the TopCollector.
-        let top_docs = TopDocs::with_limit(40);
+        let top_docs = TopDocs::with_limit(limit);
 
         // We can now perform our query.
         let doc_addresses = searcher.search(&*query, &top_docs)?;
@@ -171,7 +199,7 @@ impl Indexer for TantivyIndexer {
             out.push(PageContent {
                 url: String::from(found_id),
                 title: String::from(found_title),
-                body: String::new(),
+                ..PageContent::default()
             });
         }
         Ok(Results {
@@ -182,8 +210,17 @@ impl Indexer for TantivyIndexer {
 
     /// Build a textual representation of the summary to be displayed in the web interface.
     fn summary(&self) -> Option<String> {
-        self.search("weaver")
-            .map(|r| format!("Indexed docs: {}", r.total))
+        self.stats()
+            .map(|s| format!("Indexed docs: {}", s.documents))
             .ok()
     }
+
+    fn stats(&self) -> Result<IndexStats> {
+        self.index.load_searchers()?;
+        let searcher = self.index.searcher();
+        Ok(IndexStats {
+            documents: searcher.num_docs(),
+            segments: searcher.segment_readers().len(),
+        })
+    }
 }
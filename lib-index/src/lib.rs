@@ -2,11 +2,13 @@
 //! storing documents for further data mining.
 
 
-pub use crate::indexer::Results;
+pub use crate::indexer::{IndexStats, Results};
 pub use crate::indexer::{Indexer, TantivyIndexer};
 use lib_error::*;
 
+pub mod forget;
 mod indexer;
+pub mod prune;
 pub mod repo;
 
 /// Initialize libraries required by the stores.
@@ -0,0 +1,132 @@
+//! Enforce the age based retention rules across the sqlite store, the encrypted repo
+//! and the text index.
+use crate::indexer::Indexer;
+use crate::repo::{decode_action, decode_page, Collection, Repo};
+use bincode;
+use lib_db::{actions2, bookmarks, tags, Connection};
+use lib_error::*;
+use lib_goo::config::Retention;
+use lib_goo::date;
+use lib_goo::entities::{NewAction, PageContent, PageHtml};
+use std::collections::HashSet;
+
+/// What was, or would be in dry run mode, deleted by the retention rules.
+#[derive(Debug, Default, ::serde::Serialize)]
+pub struct PruneReport {
+    pub dry_run: bool,
+    pub actions: usize,
+    pub repo_entries: usize,
+    pub index_documents: usize,
+}
+
+/// Delete the data older than allowed by the retention rules. In dry run mode only
+/// count what would be deleted.
+pub fn prune(
+    connection: &Connection,
+    repo: &Repo,
+    indexer: &Indexer,
+    retention: &Retention,
+    dry_run: bool,
+) -> Result<PruneReport> {
+    let mut report = PruneReport {
+        dry_run,
+        ..PruneReport::default()
+    };
+
+    // Actions in the sqlite store.
+    for kind in &["shell", "url"] {
+        if let Some(max_age) = retention.max_action_age(kind) {
+            let expired = actions2::expired(connection, kind, max_age)?;
+            report.actions += if dry_run {
+                expired.len()
+            } else {
+                actions2::delete_and_collect(connection, &expired)?.1.actions
+            };
+        }
+    }
+
+    // Actions in the repo.
+    let actions = Collection(NewAction::collection_name().into());
+    for handle in repo.list(&actions)? {
+        let content = repo.read(&actions, &handle.id)?;
+        match decode_action(&content) {
+            Ok(action) => {
+                let expired = retention
+                    .max_action_age(&action.kind)
+                    .map(|max_age| {
+                        date::age(&action.executed)
+                            .map(|a| a > max_age)
+                            .unwrap_or(false)
+                    })
+                    .unwrap_or(false);
+                if expired {
+                    if !dry_run {
+                        repo.delete(&actions, &handle.id)?;
+                    }
+                    report.repo_entries += 1;
+                }
+            }
+            Err(e) => ::log::warn!("skipping action {}: {:?}", handle.id, e),
+        }
+    }
+
+    // Page snapshots, keep the ones with annotated actions and the starred or listed pages.
+    if let Some(max_age) = retention.max_page_age() {
+        let annotated = actions2::annotated_urls(connection)?
+            .into_iter()
+            .collect::<HashSet<String>>();
+        let bookmarked = bookmarks::kept_urls(connection)?;
+        let is_kept = |url: &str| annotated.contains(url) || tags::contains_url(&bookmarked, url);
+        let pages = Collection(PageContent::collection_name().into());
+        let mut expired_urls = HashSet::new();
+        let mut current_urls = HashSet::new();
+        for handle in repo.list(&pages)? {
+            let content = repo.read(&pages, &handle.id)?;
+            let page_content = match decode_page(&content) {
+                Ok(p) => p,
+                Err(e) => {
+                    ::log::warn!("skipping page content {}: {:?}", handle.id, e);
+                    continue;
+                }
+            };
+            if handle.age() > max_age && !is_kept(&page_content.url) {
+                if !dry_run {
+                    repo.delete(&pages, &handle.id)?;
+                }
+                report.repo_entries += 1;
+                expired_urls.insert(page_content.url);
+            } else {
+                current_urls.insert(page_content.url);
+            }
+        }
+
+        // The raw html follows the same rules as the snapshots.
+        let html = Collection(PageHtml::collection_name().into());
+        for handle in repo.list(&html)? {
+            let content = repo.read(&html, &handle.id)?;
+            let page_html = match bincode::deserialize::<PageHtml>(&content) {
+                Ok(p) => p,
+                Err(e) => {
+                    ::log::warn!("skipping page html {}: {:?}", handle.id, e);
+                    continue;
+                }
+            };
+            if handle.age() > max_age && !is_kept(&page_html.url) {
+                if !dry_run {
+                    repo.delete(&html, &handle.id)?;
+                }
+                report.repo_entries += 1;
+            }
+        }
+
+        // The index only holds the latest snapshot of a url, keep it while any
+        // snapshot is still current.
+        for url in expired_urls.difference(&current_urls) {
+            if dry_run || indexer.delete(url)? {
+                report.index_documents += 1;
+            }
+        }
+    }
+
+    Ok(report)
+}
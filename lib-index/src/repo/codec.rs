@@ -0,0 +1,76 @@
+//! Decode the documents saved in the repo, including the layouts of the older versions.
+use bincode;
+use bson;
+use lib_error::*;
+use lib_goo::entities::{NewAction, PageContent};
+use std::io::Cursor;
+
+/// Decode an action envelope, as saved in the repo by the actions api.
+pub fn decode_action(content: &[u8]) -> Result<NewAction> {
+    let document = bson::decode_document(&mut Cursor::new(content))
+        .map_err(|e| WeaverError::from(format!("decode action document {:?}", e)))?;
+    match document.get("data") {
+        Some(data) => bson::from_bson::<NewAction>(data.clone())
+            .map_err(|e| WeaverError::from(format!("decode new_action {:?}", e))),
+        None => Err("missing data in action document".into()),
+    }
+}
+
+// Layout of the page snapshots saved before the extraction details were added.
+#[derive(::serde::Deserialize)]
+struct LegacyPageContent {
+    url: String,
+    title: String,
+    body: String,
+}
+
+/// Decode a page snapshot from the repo, the older snapshots only have the url, title and body.
+pub fn decode_page(content: &[u8]) -> Result<PageContent> {
+    match bincode::deserialize::<PageContent>(content) {
+        Ok(page_content) => Ok(page_content),
+        Err(_) => bincode::deserialize::<LegacyPageContent>(content)
+            .map(|legacy| PageContent {
+                url: legacy.url,
+                title: legacy.title,
+                body: legacy.body,
+                ..PageContent::default()
+            })
+            .map_err(|e| WeaverError::from(format!("decode page content {:?}", e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(::serde::Serialize)]
+    struct Legacy<'a> {
+        url: &'a str,
+        title: &'a str,
+        body: &'a str,
+    }
+
+    #[test]
+    fn test_decode_page() {
+        let legacy = bincode::serialize(&Legacy {
+            url: "https://example.com/",
+            title: "Example",
+            body: "Some text",
+        })
+        .expect("serialize");
+        let decoded = decode_page(&legacy).expect("legacy");
+        assert_eq!(decoded.title, "Example");
+        assert_eq!(decoded.word_count, 0);
+
+        let current = bincode::serialize(&PageContent {
+            url: "https://example.com/".into(),
+            word_count: 2,
+            links: vec!["https://example.org/".into()],
+            ..PageContent::default()
+        })
+        .expect("serialize");
+        let decoded = decode_page(&current).expect("current");
+        assert_eq!(decoded.word_count, 2);
+        assert_eq!(decoded.links.len(), 1);
+    }
+}
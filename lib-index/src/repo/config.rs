@@ -15,7 +15,12 @@ pub(crate) struct Config {
 impl Config {
     // Read the Repo configuration from the default location, if it's present.
     pub fn read() -> Result<Option<Config>> {
-        let path = Config::config_path()?;
+        Config::read_in(&Self::repo_folder()?)
+    }
+
+    // Read the configuration of the repo in the given folder, if it's present.
+    pub fn read_in(folder: &Path) -> Result<Option<Config>> {
+        let path = Config::config_path(folder);
         if !path.exists() {
             return Ok(None);
         };
@@ -31,7 +36,12 @@ impl Config {
 
     // Read an existing config or build a new one if
     pub fn read_or_build() -> Result<Config> {
-        match Config::read()? {
+        Config::read_or_build_in(&Self::repo_folder()?)
+    }
+
+    // Same as read_or_build for the repo in the given folder.
+    pub fn read_or_build_in(folder: &Path) -> Result<Config> {
+        match Config::read_in(folder)? {
             Some(c) => Ok(c),
             None => {
                 // Generate some new salt for this repo
@@ -41,7 +51,7 @@ impl Config {
 
                 // Create the config and save it to disk.
                 let config = Config { salt_raw };
-                config.write()?;
+                config.write(folder)?;
 
                 Ok(config)
             }
@@ -49,10 +59,8 @@ impl Config {
     }
 
     // Where to store the configuration.
-    fn config_path() -> Result<PathBuf> {
-        let mut path = Self::repo_folder()?;
-        path.push("repo.def");
-        Ok(path)
+    fn config_path(folder: &Path) -> PathBuf {
+        folder.join("repo.def")
     }
 
     pub fn is_config(path: &Path) -> bool {
@@ -64,10 +72,10 @@ impl Config {
 
     // Write the configuration of this Repo. Overwriting the hash would make this store
     // unaccessible so guard against that.
-    fn write(&self) -> Result<()> {
-        let path = Config::config_path()?;
+    fn write(&self, folder: &Path) -> Result<()> {
+        let path = Config::config_path(folder);
         if path.exists() {
-            match Self::read() {
+            match Self::read_in(folder) {
                 Ok(Some(existing)) => {
                     if existing.salt_raw != self.salt_raw {
                         return Err("cannot overwrite existing repo config".into());
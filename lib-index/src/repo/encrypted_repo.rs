@@ -4,14 +4,18 @@
 //! to add some structure to them.
 
 use super::config::Config;
-use crate::repo::{Collection, Repo};
+use super::field_cipher::RepoFieldCipher;
+use crate::repo::{Collection, Repo, RepoHandle};
 use bincode::{deserialize, serialize};
 use keyring;
+use lib_db::crypto::FieldCipher;
 use lib_error::*;
 use lib_goo::config::db::PasswordSource;
 use metrohash::MetroHash128;
 use rust_sodium::crypto::{pwhash, secretbox};
-use std::fs::{create_dir, read, read_dir, remove_file, write, ReadDir};
+use std::fs::{
+    create_dir, create_dir_all, read, read_dir, read_to_string, remove_file, write, ReadDir,
+};
 use std::hash::Hasher;
 use std::path::{Path, PathBuf};
 
@@ -41,25 +45,74 @@ pub struct RepoDir<'a> {
 /// The entry returned by the RepoDir iterator.
 pub struct RepoEntry(Vec<u8>);
 
-#[cfg(target_os = "macos")]
+#[cfg(unix)]
 fn prompt_for_password() -> Result<String> {
     let new_pwd =
         rpassword::prompt_password_stdout("Enter a password for the document repo: ")?;
     Ok(new_pwd)
 }
 
-#[cfg(not(target_os = "macos"))]
+#[cfg(not(unix))]
 fn prompt_for_password() -> Result<String> {
     Err(WeaverError::from(
         "prompting for the password not supported",
     ))
 }
 
+/// Refuse key files which other users can read or which the group can change.
+#[cfg(unix)]
+fn check_key_file_permissions(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = path.metadata()?.permissions().mode();
+    if mode & 0o027 != 0 {
+        return Err(WeaverError::from(format!(
+            "key file {} has permissions {:o}, run `chmod 600` on it",
+            path.display(),
+            mode & 0o777
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_key_file_permissions(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Read the password from the first line of the key file.
+fn read_key_file(path: &Path) -> Result<String> {
+    if !path.exists() {
+        return Err(WeaverError::from(format!(
+            "key file {} does not exist",
+            path.display()
+        )));
+    }
+    check_key_file_permissions(path)?;
+    let content = read_to_string(path)?;
+    let password = content.lines().next().unwrap_or_default();
+    if password.is_empty() {
+        return Err("the key file is empty".into());
+    }
+    Ok(password.to_owned())
+}
+
 impl EncryptedRepo {
     // Build the repo with information from its config and the keyring
     pub fn build(password_source: &PasswordSource) -> Result<EncryptedRepo> {
-        let base_folder = Config::repo_folder()?;
-        let config = Config::read_or_build()?;
+        Self::build_in(Config::repo_folder()?, password_source)
+    }
+
+    /// Build the repo saved in the folder, it is created with its own salt when missing.
+    /// The same password gives a different key for each folder.
+    pub fn build_in(
+        base_folder: PathBuf,
+        password_source: &PasswordSource,
+    ) -> Result<EncryptedRepo> {
+        if !base_folder.exists() {
+            create_dir_all(&base_folder)?;
+        }
+        let config = Config::read_or_build_in(&base_folder)?;
         let salt = config.salt()?;
 
         let password = Self::get_password(password_source)?;
@@ -78,6 +131,11 @@ impl EncryptedRepo {
         Ok(Self { key, base_folder })
     }
 
+    /// The folder holding the collections.
+    pub fn location() -> Result<PathBuf> {
+        Config::repo_folder()
+    }
+
     /// Compute the path for the given collection.
     fn collection_path(&self, collection: &Collection) -> PathBuf {
         let mut out = self.base_folder.clone();
@@ -111,14 +169,24 @@ impl EncryptedRepo {
             PasswordSource::PassIn(value) => Ok(value.clone()),
             PasswordSource::Environment => std::env::var("WEAVER_PASSWORD")
                 .map_err(|_| WeaverError::from("no password in the environment")),
+            PasswordSource::KeyFile(path) => read_key_file(path),
         }
     }
 
+    /// Build the cipher used for the encrypted database columns, shares the repo key.
+    pub fn field_cipher(&self) -> Box<FieldCipher> {
+        Box::new(RepoFieldCipher::new(self.key.clone()))
+    }
 
     pub fn setup_if_needed(source: &PasswordSource) -> Result<()> {
-        if Self::get_password(source).is_ok() {
+        let password = Self::get_password(source);
+        if password.is_ok() {
             return Ok(());
         }
+        if let PasswordSource::KeyFile(_) = source {
+            // The key file is provisioned outside of weaver, report why it cannot be used.
+            return password.map(|_| ());
+        }
         if source == &PasswordSource::Keyring {
             let ring = keyring::Keyring::new("weaver", "weaver-user");
             let new_pwd = prompt_for_password()?;
@@ -129,20 +197,8 @@ impl EncryptedRepo {
         Ok(())
     }
 
-    /// Delete the file from the repo.
-    pub fn delete(&self, collection: &Collection, id: &str) -> Result<()> {
-        let mut out = self.collection_path(collection);
-        out.push(id);
-        if out.exists() {
-            remove_file(&out)?;
-            Ok(())
-        } else {
-            Err("File does not exist".into())
-        }
-    }
-
-    /// List all the encrypted files.
-    pub fn list(&self, collection: &Collection) -> Result<RepoDir> {
+    /// Iterate over the decrypted content of all the files in the collection.
+    pub fn entries(&self, collection: &Collection) -> Result<RepoDir> {
         let read_dir = read_dir(&self.collection_path(collection))?;
 
         Ok(RepoDir {
@@ -151,14 +207,6 @@ impl EncryptedRepo {
         })
     }
 
-    /// Read and decrypt the given handle.
-    pub fn read(&self, collection: &Collection, id: &str) -> Result<Vec<u8>> {
-        // Read the file
-        let mut out = self.collection_path(collection);
-        out.push(id);
-        self.read_file(&out)
-    }
-
     /// Read and decrypt the given file.
     pub fn read_file(&self, path: &Path) -> Result<Vec<u8>> {
         if !path.exists() {
@@ -232,6 +280,50 @@ impl Repo for EncryptedRepo {
         write(&out, &serialized)?;
         Ok(hash)
     }
+
+    /// Delete the file from the repo.
+    fn delete(&self, collection: &Collection, id: &str) -> Result<()> {
+        let mut out = self.collection_path(collection);
+        out.push(id);
+        if out.exists() {
+            remove_file(&out)?;
+            Ok(())
+        } else {
+            Err("File does not exist".into())
+        }
+    }
+
+    /// List the handles of all the encrypted files in the collection.
+    fn list(&self, collection: &Collection) -> Result<Vec<RepoHandle>> {
+        let path = self.collection_path(collection);
+        let mut out = Vec::new();
+        if !path.exists() {
+            return Ok(out);
+        }
+        for entry in read_dir(&path)? {
+            let entry = entry?;
+            let path = entry.path();
+            let metadata = entry.metadata()?;
+            if metadata.is_file() && !Config::is_config(&path) {
+                if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                    out.push(RepoHandle {
+                        id: name.to_owned(),
+                        size: metadata.len(),
+                        modified: metadata.modified()?,
+                    });
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Read and decrypt the given handle.
+    fn read(&self, collection: &Collection, id: &str) -> Result<Vec<u8>> {
+        // Read the file
+        let mut out = self.collection_path(collection);
+        out.push(id);
+        self.read_file(&out)
+    }
 }
 
 /// Iterate over all the encrypted files in the repo.
@@ -0,0 +1,104 @@
+//! Encrypt the sensitive database columns with the key of the document repo.
+
+use lib_db::crypto::{FieldCipher, ENCRYPTED_PREFIX};
+use lib_error::*;
+use rust_sodium::crypto::{auth, hash::sha256, secretbox};
+use std::fmt::Write;
+
+// Domain separation for the key used by the blind index.
+const BLIND_INDEX_CONTEXT: &[u8] = b"weaver-blind-index";
+
+/// Seal the values with secretbox, the blind index is a keyed HMAC.
+pub struct RepoFieldCipher {
+    key: secretbox::Key,
+    index_key: auth::Key,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        write!(out, "{:02x}", b).expect("write to string");
+    }
+    out
+}
+
+fn from_hex(text: &str) -> Result<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return Err("invalid hex length".into());
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&text[i..i + 2], 16).map_err(|_| WeaverError::from("invalid hex"))
+        })
+        .collect()
+}
+
+impl RepoFieldCipher {
+    pub fn new(key: secretbox::Key) -> RepoFieldCipher {
+        let mut material = Vec::with_capacity(secretbox::KEYBYTES + BLIND_INDEX_CONTEXT.len());
+        material.extend_from_slice(&key.0);
+        material.extend_from_slice(BLIND_INDEX_CONTEXT);
+        let sha256::Digest(digest) = sha256::hash(&material);
+        let index_key = auth::Key::from_slice(&digest[..auth::KEYBYTES]).expect("auth key size");
+        RepoFieldCipher { key, index_key }
+    }
+}
+
+impl FieldCipher for RepoFieldCipher {
+    fn encrypt(&self, plain: &str) -> String {
+        let nonce = secretbox::gen_nonce();
+        let mut sealed = Vec::new();
+        sealed.extend_from_slice(&nonce.0);
+        sealed.extend(secretbox::seal(plain.as_bytes(), &nonce, &self.key));
+        format!("{}{}", ENCRYPTED_PREFIX, to_hex(&sealed))
+    }
+
+    fn decrypt(&self, stored: &str) -> Result<String> {
+        if !stored.starts_with(ENCRYPTED_PREFIX) {
+            return Err("value is not encrypted".into());
+        }
+        let sealed = from_hex(&stored[ENCRYPTED_PREFIX.len()..])?;
+        if sealed.len() < secretbox::NONCEBYTES {
+            return Err("encrypted value is too short".into());
+        }
+        let (nonce, ciphertext) = sealed.split_at(secretbox::NONCEBYTES);
+        let nonce = match secretbox::Nonce::from_slice(nonce) {
+            Some(n) => n,
+            None => return Err("could not rebuild nonce".into()),
+        };
+        let plain = secretbox::open(ciphertext, &nonce, &self.key)
+            .map_err(|_| WeaverError::from("decrypt error"))?;
+        String::from_utf8(plain).map_err(|_| "decrypted value is not utf8".into())
+    }
+
+    fn blind_index(&self, plain: &str) -> String {
+        let auth::Tag(tag) = auth::authenticate(plain.as_bytes(), &self.index_key);
+        to_hex(&tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let cipher = RepoFieldCipher::new(secretbox::gen_key());
+        let first = cipher.encrypt("ls -la");
+        let second = cipher.encrypt("ls -la");
+        assert!(first.starts_with(ENCRYPTED_PREFIX));
+        assert_ne!(first, second);
+        assert_eq!(cipher.decrypt(&first).unwrap(), "ls -la");
+        assert_eq!(cipher.blind_index("ls -la"), cipher.blind_index("ls -la"));
+        assert_ne!(cipher.blind_index("ls -la"), cipher.blind_index("ls"));
+    }
+
+    #[test]
+    fn test_other_key() {
+        let cipher = RepoFieldCipher::new(secretbox::gen_key());
+        let other = RepoFieldCipher::new(secretbox::gen_key());
+        assert!(other.decrypt(&cipher.encrypt("secret")).is_err());
+        assert!(cipher.decrypt("plain").is_err());
+    }
+}
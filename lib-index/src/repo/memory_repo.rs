@@ -0,0 +1,123 @@
+//! Provide a repository of documents kept in memory, without encryption.
+//! Useful in tests and tools which should not touch the disk or the keyring.
+
+use crate::repo::{Collection, Repo, RepoHandle};
+use lib_error::*;
+use metrohash::MetroHash128;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hasher;
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+/// A document saved in memory.
+struct MemoryEntry {
+    content: Vec<u8>,
+    modified: SystemTime,
+}
+
+/// Hold the documents of every collection, by id.
+#[derive(Default)]
+pub struct MemoryRepo(RwLock<HashMap<String, BTreeMap<String, MemoryEntry>>>);
+
+impl MemoryRepo {
+    pub fn new() -> MemoryRepo {
+        MemoryRepo::default()
+    }
+}
+
+fn lock_error<T>(e: T) -> WeaverError
+where
+    T: ::std::fmt::Debug,
+{
+    WeaverError::from(format!("lock memory repo {:?}", e))
+}
+
+impl Repo for MemoryRepo {
+    fn add(&self, collection: &Collection, content: &[u8]) -> Result<String> {
+        let mut hasher = MetroHash128::default();
+        hasher.write(content);
+        let id = format!("{}", hasher.finish());
+
+        let mut guard = self.0.write().map_err(lock_error)?;
+        guard
+            .entry(collection.name().to_owned())
+            .or_insert_with(BTreeMap::new)
+            .insert(
+                id.clone(),
+                MemoryEntry {
+                    content: content.to_vec(),
+                    modified: SystemTime::now(),
+                },
+            );
+        Ok(id)
+    }
+
+    fn delete(&self, collection: &Collection, id: &str) -> Result<()> {
+        let mut guard = self.0.write().map_err(lock_error)?;
+        match guard
+            .get_mut(collection.name())
+            .and_then(|entries| entries.remove(id))
+        {
+            Some(_) => Ok(()),
+            None => Err("File does not exist".into()),
+        }
+    }
+
+    fn list(&self, collection: &Collection) -> Result<Vec<RepoHandle>> {
+        let guard = self.0.read().map_err(lock_error)?;
+        let out = match guard.get(collection.name()) {
+            Some(entries) => entries
+                .iter()
+                .map(|(id, entry)| RepoHandle {
+                    id: id.clone(),
+                    size: entry.content.len() as u64,
+                    modified: entry.modified,
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        Ok(out)
+    }
+
+    fn read(&self, collection: &Collection, id: &str) -> Result<Vec<u8>> {
+        let guard = self.0.read().map_err(lock_error)?;
+        match guard
+            .get(collection.name())
+            .and_then(|entries| entries.get(id))
+        {
+            Some(entry) => Ok(entry.content.clone()),
+            None => Err("File does not exist".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_read_delete() {
+        let repo = MemoryRepo::new();
+        let collection = Collection::from("test");
+        let id = repo.add(&collection, b"content").expect("add");
+
+        assert_eq!(repo.read(&collection, &id).expect("read"), b"content".to_vec());
+
+        let listed = repo.list(&collection).expect("list");
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, id);
+        assert_eq!(listed[0].size, 7);
+
+        repo.delete(&collection, &id).expect("delete");
+        assert!(repo.read(&collection, &id).is_err());
+        assert!(repo.list(&collection).expect("list").is_empty());
+    }
+
+    #[test]
+    fn test_collections_are_separate() {
+        let repo = MemoryRepo::new();
+        let id = repo.add(&Collection::from("one"), b"content").expect("add");
+        assert!(repo.read(&Collection::from("two"), &id).is_err());
+        assert!(repo.list(&Collection::from("two")).expect("list").is_empty());
+    }
+}
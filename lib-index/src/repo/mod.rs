@@ -1,9 +1,16 @@
 use lib_error::*;
 use std::convert::From;
+use std::time::SystemTime;
 
+mod codec;
 mod config;
 mod encrypted_repo;
-pub use self::encrypted_repo::EncryptedRepo;
+mod field_cipher;
+mod memory_repo;
+pub use self::codec::{decode_action, decode_page};
+pub use self::encrypted_repo::{EncryptedRepo, RepoDir, RepoEntry};
+pub use self::field_cipher::RepoFieldCipher;
+pub use self::memory_repo::MemoryRepo;
 
 /// Represents a collection in the repo.
 #[derive(Debug)]
@@ -27,7 +34,42 @@ impl<'a> From<&'a str> for Collection {
     }
 }
 
+/// Handle to a document saved in the repo, together with its metadata.
+#[derive(Clone, Debug)]
+pub struct RepoHandle {
+    /// The id under which the document was saved.
+    pub id: String,
+    /// Size of the encrypted document.
+    pub size: u64,
+    /// Last time the document was written.
+    pub modified: SystemTime,
+}
+
+impl RepoHandle {
+    /// Age of the document in seconds.
+    pub fn age(&self) -> i64 {
+        self.modified
+            .elapsed()
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0)
+    }
+}
+
 /// Trait with document management related api
 pub trait Repo {
+    /// Save the content in the collection, return the id to use for later access.
     fn add(&self, collection: &Collection, content: &[u8]) -> Result<String>;
+    /// Delete the document with the given id.
+    fn delete(&self, collection: &Collection, id: &str) -> Result<()>;
+    /// List the handles of all the documents in the collection.
+    fn list(&self, collection: &Collection) -> Result<Vec<RepoHandle>>;
+    /// Read the content of the document with the given id.
+    fn read(&self, collection: &Collection, id: &str) -> Result<Vec<u8>>;
+    /// Check that the documents in the collection can be read, for example with the current key.
+    fn verify(&self, collection: &Collection) -> Result<()> {
+        match self.list(collection)?.first() {
+            Some(handle) => self.read(collection, &handle.id).map(|_| ()),
+            None => Ok(()),
+        }
+    }
 }
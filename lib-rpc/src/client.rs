@@ -1,14 +1,51 @@
 use lib_error::*;
+use lib_goo::config::api_token;
 use lib_goo::config::net::{self, ANNOTATIONS};
 use lib_goo::config::Destination;
 use lib_goo::entities::{ActionId, NewAction};
-use reqwest;
+use reqwest::blocking::{Client, RequestBuilder};
 use serde_urlencoded;
+use std::fs;
 
-/// Extract the rpc address from the Destination.
-fn rpc_addr(destination: &Destination) -> &str {
+/// Build the scheme and address of the server from the Destination.
+fn rpc_addr(destination: &Destination) -> String {
     match destination {
-        Destination::Remote(ref a) => a,
+        Destination::Remote(ref a) => format!("http://{}", a),
+        Destination::Secure(ref a, _) => format!("https://{}", a),
+    }
+}
+
+/// Build a client for the Destination, https only trusts the certificate authority generated by
+/// `weaver-data setup`, the system authorities are not used.
+fn client(destination: &Destination) -> Result<Client> {
+    match destination {
+        Destination::Remote(_) => Ok(Client::new()),
+        Destination::Secure(_, ref ca) => {
+            let pem = fs::read(ca).map_err(|e| {
+                WeaverError::from(format!("reading the certificate authority {:?}: {}", ca, e))
+            })?;
+            let certificate = reqwest::Certificate::from_pem(&pem).map_err(|e| {
+                WeaverError::from(format!("parsing the certificate authority {:?}: {}", ca, e))
+            })?;
+            let client = Client::builder()
+                .tls_built_in_root_certs(false)
+                .add_root_certificate(certificate)
+                .build()
+                .context("building the https client".into())?;
+            Ok(client)
+        }
+    }
+}
+
+/// Send the api token, when one was setup for this client.
+fn authorize(request: RequestBuilder) -> RequestBuilder {
+    match api_token::load() {
+        Ok(Some(token)) => request.bearer_auth(token),
+        Ok(None) => request,
+        Err(e) => {
+            ::log::warn!("cannot load the api token {:?}", e);
+            request
+        }
     }
 }
 
@@ -17,7 +54,7 @@ pub fn recommendations(
     params: &net::RecommendationQuery,
 ) -> Result<net::PaginatedActions> {
     let url = format!(
-        "http://{}{}{}{}?{}",
+        "{}{}{}{}?{}",
         rpc_addr(destination),
         net::API_BASE,
         net::ACTIONS2_BASE,
@@ -25,26 +62,27 @@ pub fn recommendations(
         serde_urlencoded::to_string(params).context("encoding url params".into())?
     );
     ::log::debug!("Downloading recommendations from {}", url);
-    let client = reqwest::Client::new();
-    let mut response = client
-        .get(&url)
+    let client = client(destination)?;
+    let response = authorize(client.get(&url))
         .send()
+        .and_then(|r| r.error_for_status())
         .context("error in getting recommendations".into())?;
     response
         .json::<net::PaginatedActions>().map_err(|a| a.into())
 }
 
 pub fn add(destination: &Destination, req: &NewAction) -> Result<u64> {
-    let client = reqwest::Client::new();
-    let id = client
-        .post(&format!(
-            "http://{}{}{}",
-            rpc_addr(destination),
-            net::API_BASE,
-            net::ACTIONS2_BASE
-        )).json(req)
-        .send()
-        .map(|_| 0)?;
+    let client = client(destination)?;
+    let id = authorize(client.post(&format!(
+        "{}{}{}",
+        rpc_addr(destination),
+        net::API_BASE,
+        net::ACTIONS2_BASE
+    )))
+    .json(req)
+    .send()?
+    .error_for_status()
+    .map(|_| 0)?;
     Ok(id)
 }
 
@@ -52,17 +90,78 @@ pub fn set_annotation(destination: &Destination, id: &ActionId, content: &str) -
     let data = net::Annotation {
         annotation: content.into(),
     };
-    let client = reqwest::Client::new();
-    client
-        .post(&format!(
-            "http://{}{}/{}/{}{}",
-            rpc_addr(destination),
-            net::API_BASE,
-            net::ACTIONS2_BASE,
-            id,
-            ANNOTATIONS
-        )).json(&data)
+    let client = client(destination)?;
+    authorize(client.post(&format!(
+        "{}{}/{}/{}{}",
+        rpc_addr(destination),
+        net::API_BASE,
+        net::ACTIONS2_BASE,
+        id,
+        ANNOTATIONS
+    )))
+    .json(&data)
+    .send()
+    .and_then(|r| r.error_for_status())
+    .map(|_| 0)
+    .map_err(|a| a.into())
+}
+
+pub fn delete(destination: &Destination, id: &ActionId) -> Result<net::DeleteReport> {
+    let client = client(destination)?;
+    let response = authorize(client.delete(&format!(
+        "{}{}{}/{}",
+        rpc_addr(destination),
+        net::API_BASE,
+        net::ACTIONS2_BASE,
+        id
+    )))
+    .send()
+    .and_then(|r| r.error_for_status())
+    .context("error in deleting action".into())?;
+    response
+        .json::<net::DeleteReport>()
+        .map_err(|a| a.into())
+}
+
+/// Fetch the health or readiness report from the server, `path` is `net::HEALTH` or `net::READY`.
+/// The report is returned even when the server is not healthy.
+pub fn health(destination: &Destination, base_url: &str, path: &str) -> Result<net::HealthReport> {
+    let url = format!(
+        "{}{}{}",
+        rpc_addr(destination),
+        base_url.trim_end_matches('/'),
+        path
+    );
+    let client = client(destination)?;
+    let response = client
+        .get(&url)
         .send()
-        .map(|_| 0)
+        .context("error in checking the server health".into())?;
+    response
+        .json::<net::HealthReport>()
         .map_err(|a| a.into())
 }
+
+/// Push a batch of actions and pages to a peer server, `token` is an api token of the peer.
+pub fn push_sync(
+    destination: &Destination,
+    token: Option<&str>,
+    batch: &net::SyncBatch,
+) -> Result<net::SyncReport> {
+    let client = client(destination)?;
+    let mut request = client.post(&format!(
+        "{}{}{}",
+        rpc_addr(destination),
+        net::API_BASE,
+        net::SYNC
+    ));
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let response = request
+        .json(batch)
+        .send()
+        .and_then(|r| r.error_for_status())
+        .context("error in pushing to the peer".into())?;
+    response.json::<net::SyncReport>().map_err(|a| a.into())
+}
@@ -0,0 +1,72 @@
+// Calls from the pages to the api, with the token created by `weaver-data token create`.
+// The token is asked once and kept in the local storage of the browser.
+(function () {
+  const TOKEN_KEY = "weaver-api-token";
+
+  function headers() {
+    const token = window.localStorage.getItem(TOKEN_KEY);
+    return token ? { "Authorization": "Bearer " + token } : {};
+  }
+
+  // Ask for a token after a rejected call, returns false when the user cancels.
+  function askToken() {
+    const token = window.prompt("Weaver api token");
+    if (!token) {
+      return false;
+    }
+    window.localStorage.setItem(TOKEN_KEY, token.trim());
+    return true;
+  }
+
+  // Fetch from the api, retried once with a new token when rejected.
+  function api(path, options) {
+    const call = function () {
+      return fetch(path, Object.assign({}, options, { headers: headers() }));
+    };
+    return call().then(function (response) {
+      if (response.status == 401 && askToken()) {
+        return call();
+      }
+      return response;
+    });
+  }
+
+  // Read the server-sent events of the api, `onEvent` receives the decoded data.
+  // EventSource cannot send the authorization header, the stream is read with fetch.
+  function events(path, onEvent) {
+    return api(path, {}).then(function (response) {
+      if (!response.ok || !response.body) {
+        return false;
+      }
+      const reader = response.body.getReader();
+      const decoder = new TextDecoder();
+      let buffer = "";
+      function read() {
+        return reader.read().then(function (chunk) {
+          if (chunk.done) {
+            return true;
+          }
+          buffer += decoder.decode(chunk.value, { stream: true });
+          let end = buffer.indexOf("\n\n");
+          while (end >= 0) {
+            const data = buffer
+              .slice(0, end)
+              .split("\n")
+              .filter(function (line) { return line.indexOf("data:") == 0; })
+              .map(function (line) { return line.slice(5).trim(); })
+              .join("\n");
+            buffer = buffer.slice(end + 2);
+            if (data) {
+              onEvent(JSON.parse(data));
+            }
+            end = buffer.indexOf("\n\n");
+          }
+          return read();
+        });
+      }
+      return read();
+    });
+  }
+
+  window.weaver = { api: api, events: events };
+})();
@@ -1,14 +1,76 @@
-use lib_db::{topics, SqlProvider};
+use crate::events::Broadcaster;
+use crate::metrics::Metrics;
+use crate::tenants::Tenants;
+use lib_db::{actions2, bookmarks, topics, Connection, SqlProvider};
+use lib_error::Result as WResult;
+use lib_goo::config::net::{ActionEvent, DeleteReport};
+use lib_goo::config::Settings;
+use lib_goo::entities::ActionId;
 use lib_index::repo::Repo;
-use lib_index::Indexer;
+use lib_index::{Indexer, Results};
+use std::convert::TryFrom;
 use std::sync::Arc;
 
+/// Times the search limit of matches fetched from the index, the starred pages in this
+/// window are moved ahead before the results are cut to the search limit.
+const BOOST_WINDOW: usize = 4;
+
 /// Store per request state.
+#[derive(Clone)]
 pub(crate) struct ApiState {
+    pub events: Arc<Broadcaster>,
     pub indexer: Arc<Indexer>,
+    pub metrics: Arc<Metrics>,
     pub repo: Arc<Repo>,
+    pub settings: Arc<Settings>,
     pub sql: Arc<SqlProvider>,
     pub topic_store: Arc<Option<topics::TopicStore>>,
+    /// The stores of each tenant, in multi-tenant mode.
+    pub tenants: Option<Arc<Tenants>>,
+}
+
+impl ApiState {
+    /// Push a newly saved action to the live clients.
+    pub fn publish_created(&self, connection: &Connection, id: u64) {
+        match actions2::fetch_by_id(connection, id) {
+            Ok(Some(action)) => self.events.publish(&ActionEvent::Created { action }),
+            Ok(None) => ::log::warn!("cannot find the new action {}", id),
+            Err(e) => ::log::error!("cannot load the new action {} {:?}", id, e),
+        }
+    }
+
+    /// Search the text index, the starred pages come first. Without the starred pages,
+    /// for example when the database fails, the results are in the index order.
+    pub fn search_boosted(&self, term: &str) -> WResult<Results> {
+        let starred = match self.sql.connection().and_then(|c| bookmarks::starred_urls(&c)) {
+            Ok(urls) => urls,
+            Err(e) => {
+                ::log::error!("cannot load the starred pages {:?}", e);
+                return self.indexer.search(term);
+            }
+        };
+        let limit = self.settings.index.search_limit;
+        let mut results = self.indexer.search_top(term, limit * BOOST_WINDOW)?;
+        bookmarks::boost(&mut results.matches, &starred);
+        results.matches.truncate(limit);
+        Ok(results)
+    }
+
+    /// Delete the actions and the rows only they used, tell the live clients about the
+    /// actions which were actually deleted.
+    pub fn delete_actions(&self, ids: &[i32]) -> WResult<DeleteReport> {
+        let (deleted, report) = actions2::delete_and_collect(&self.sql.connection()?, ids)?;
+        // The ids come from the database, they are positive.
+        let deleted: Vec<ActionId> = deleted
+            .into_iter()
+            .filter_map(|id| usize::try_from(id).ok())
+            .map(ActionId::new)
+            .collect();
+        if !deleted.is_empty() {
+            self.events.publish(&ActionEvent::Deleted { ids: deleted });
+        }
+        Ok(report)
+    }
 }
 
 // Define a helper environment for tests.
@@ -20,9 +82,8 @@ pub(crate) mod tests {
     use lib_db::{Connection, SqlProvider};
     use lib_error::{Result as WResult};
     use lib_goo::entities::{NewAction, PageContent};
-    use lib_index::repo::Collection;
-    use lib_index::repo::Repo;
-    use lib_index::{Indexer, Results};
+    use lib_index::repo::MemoryRepo;
+    use lib_index::{IndexStats, Indexer, Results};
     use std::cell::RefCell;
     use std::sync::Arc;
 
@@ -52,9 +113,10 @@ pub(crate) mod tests {
             Ok(1)
         }
 
-        fn delete(&self, _id: &str) -> WResult<()> {
+        fn delete(&self, _id: &str) -> WResult<bool> {
+            let found = !self.pages.borrow().is_empty();
             self.pages.borrow_mut().clear();
-            Ok(())
+            Ok(found)
         }
         fn search(&self, _what: &str) -> WResult<Results> {
             Ok(Results {
@@ -62,25 +124,32 @@ pub(crate) mod tests {
                 matches: self.pages.borrow().clone(),
             })
         }
+        fn search_top(&self, what: &str, limit: usize) -> WResult<Results> {
+            let mut results = self.search(what)?;
+            results.matches.truncate(limit);
+            Ok(results)
+        }
         fn summary(&self) -> Option<String> {
             Some("soomary".into())
         }
-    }
-
-    struct TestRepo;
-
-    impl Repo for TestRepo {
-        fn add(&self, _collection: &Collection, _content: &[u8]) -> WResult<String> {
-            Ok("repo add".into())
+        fn stats(&self) -> WResult<IndexStats> {
+            Ok(IndexStats {
+                documents: self.pages.borrow().len() as u64,
+                segments: 1,
+            })
         }
     }
 
     pub(crate) fn default_test() -> ApiState {
         ApiState {
+            events: Arc::new(Broadcaster::default()),
             indexer: Arc::new(TestIndexer::new()),
-            repo: Arc::new(TestRepo),
+            metrics: Arc::new(Metrics::default()),
+            repo: Arc::new(MemoryRepo::new()),
+            settings: Arc::new(Settings::default()),
             sql: Arc::new(FailingSqlProvider),
             topic_store: Arc::new(None),
+            tenants: None,
         }
     }
 
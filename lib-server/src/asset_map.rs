@@ -2,38 +2,141 @@ use lib_error::*;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::RwLock;
+use walkdir::WalkDir;
 
+/// Assets served under `/assets`, by name, the user folder replaces or adds entries.
 #[derive(Default)]
-pub struct AssetMap(RwLock<HashMap<String, Vec<u8>>>);
+pub struct AssetMap {
+    assets: RwLock<HashMap<String, Vec<u8>>>,
+    overrides: Option<PathBuf>,
+}
 
 const CSS: &[u8] = include_bytes!("../web/dist/css/weaver.css");
+const FAVICON: &[u8] = include_bytes!("../assets/favicon.ico");
+const SVGS: &[u8] = include_bytes!("../assets/inline.svg");
+const API_JS: &[u8] = include_bytes!("../assets/api.js");
+
+/// Stylesheet of the source checkout, used when the server runs from it.
+const DEV_CSS: &str = "lib-server/web/dist/css/weaver.css";
+
+fn embedded() -> HashMap<String, Vec<u8>> {
+    let mut hm = HashMap::new();
+    hm.insert("weaver.css".into(), CSS.into());
+    hm.insert("favicon.ico".into(), FAVICON.into());
+    hm.insert("inline.svg".into(), SVGS.into());
+    hm.insert("api.js".into(), API_JS.into());
+    hm
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+    let mut content = vec![];
+    File::open(path)?
+        .read_to_end(&mut content)
+        .context("asset file open".into())?;
+    Ok(content)
+}
+
+// The files in the folder, named by their path relative to it with `/` separators.
+fn read_folder(folder: &Path) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut out = Vec::new();
+    if !folder.is_dir() {
+        return Ok(out);
+    }
+    for entry in WalkDir::new(folder) {
+        let entry = entry.map_err(|_| "listing assets")?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry
+            .path()
+            .strip_prefix(folder)
+            .map_err(|_| "asset outside of its folder")?;
+        let name = relative
+            .components()
+            .filter_map(|c| c.as_os_str().to_str())
+            .collect::<Vec<&str>>()
+            .join("/");
+        // Hidden files, such as editor backups, are not served.
+        if name.is_empty() || name.starts_with('.') || name.contains("/.") {
+            continue;
+        }
+        out.push((name, read_file(entry.path())?));
+    }
+    Ok(out)
+}
+
+/// Content type of the asset, from its extension.
+pub fn content_type(name: &str) -> &'static str {
+    let extension = name.rsplit('.').next().unwrap_or_default();
+    match extension.to_lowercase().as_str() {
+        "css" => "text/css",
+        "js" => "application/javascript",
+        "json" => "application/json",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "txt" => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
 
 impl AssetMap {
+    /// The embedded assets.
     pub fn build() -> AssetMap {
-        let mut hm = HashMap::new();
-        hm.insert("weaver.css".into(), CSS.into());
-        AssetMap(RwLock::new(hm))
+        AssetMap {
+            assets: RwLock::new(embedded()),
+            overrides: None,
+        }
     }
 
+    /// The embedded assets, replaced or completed by the files in the folder.
+    pub fn with_overrides(folder: PathBuf) -> Result<AssetMap> {
+        let mut hm = embedded();
+        let replaced = read_folder(&folder)?;
+        if !replaced.is_empty() {
+            ::log::info!("{} assets from {:?}", replaced.len(), folder);
+        }
+        hm.extend(replaced);
+        Ok(AssetMap {
+            assets: RwLock::new(hm),
+            overrides: Some(folder),
+        })
+    }
+
+    /// Reload the stylesheet of the source checkout, when present, then the user folder.
+    /// Returns the names of the assets read from the disk.
     pub fn reload(&self) -> Result<String> {
-        let mut css = vec![];
-        let path = Path::new("lib-server/web/dist/css/weaver.css");
-        File::open(&path)?
-            .read_to_end(&mut css)
-            .context("css file open".into())?;
+        let mut hm = embedded();
+        let mut names = Vec::new();
+        let dev = Path::new(DEV_CSS);
+        if dev.is_file() {
+            hm.insert("weaver.css".into(), read_file(dev)?);
+            names.push("weaver.css".to_owned());
+        }
+        if let Some(ref folder) = self.overrides {
+            for (name, content) in read_folder(folder)? {
+                names.push(name.clone());
+                hm.insert(name, content);
+            }
+        }
         let mut guard = self
-            .0
+            .assets
             .write()
             .map_err(|e| WeaverError::from(format!("lock asset hash map {:?}", e)))?;
-        guard.insert("weaver.css".into(), css);
-        Ok("weaver.css".into())
+        *guard = hm;
+        Ok(names.join(" "))
     }
 
     pub fn asset(&self, name: &str) -> Result<Vec<u8>> {
         let guard = self
-            .0
+            .assets
             .read()
             .map_err(|e| WeaverError::from(format!("lock asset hash map {:?}", e)))?;
         match guard.get(name) {
@@ -42,3 +145,32 @@ impl AssetMap {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_overrides() {
+        let folder = env::temp_dir().join(format!("weaver-assets-{}", std::process::id()));
+        fs::create_dir_all(folder.join("img")).expect("create folder");
+        fs::write(folder.join("weaver.css"), "body {}").expect("write css");
+        fs::write(folder.join("img").join("logo.png"), "png").expect("write png");
+        fs::write(folder.join(".weaver.css.swp"), "swap").expect("write swap");
+
+        let assets = AssetMap::with_overrides(folder.clone()).expect("assets");
+        assert_eq!(assets.asset("weaver.css").unwrap(), b"body {}".to_vec());
+        assert_eq!(assets.asset("img/logo.png").unwrap(), b"png".to_vec());
+        assert!(assets.asset(".weaver.css.swp").is_err());
+        assert_eq!(assets.asset("inline.svg").unwrap(), SVGS.to_vec());
+
+        // Removed files fall back to the embedded assets.
+        fs::remove_file(folder.join("weaver.css")).expect("remove css");
+        assets.reload().expect("reload");
+        assert_eq!(assets.asset("weaver.css").unwrap(), CSS.to_vec());
+        fs::remove_dir_all(&folder).expect("cleanup");
+        assert_eq!(content_type("img/logo.png"), "image/png");
+    }
+}
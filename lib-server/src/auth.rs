@@ -0,0 +1,200 @@
+//! Authenticate the api requests with the tokens created by `weaver-data token create`.
+//! The api is closed until the first token is created, unless `server.open_api` is set.
+//!
+//! In multi-tenant mode a token is always required, its name is the tenant.
+use actix_web::http::header;
+use actix_web::middleware::{Middleware, Started};
+use actix_web::{HttpRequest, HttpResponse, Result as AwResult};
+use crate::app_state::ApiState;
+use crate::tenants::{self, Tenant};
+use lib_db::api_tokens;
+use lib_error::*;
+
+/// Reject the requests without a valid `Authorization: Bearer <token>` header.
+pub(crate) struct TokenAuth;
+
+// Extract the token from the authorization header.
+fn bearer_token<S>(req: &HttpRequest<S>) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| {
+            let mut parts = value.splitn(2, ' ');
+            match (parts.next(), parts.next()) {
+                (Some(scheme), Some(token)) if scheme.eq_ignore_ascii_case("bearer") => {
+                    Some(token.trim().to_owned())
+                }
+                _ => None,
+            }
+        })
+}
+
+fn is_authorized(req: &HttpRequest<ApiState>) -> Result<bool> {
+    let connection = req.state().sql.connection()?;
+    if let Some(ref registry) = req.state().tenants {
+        let name = match bearer_token(req) {
+            Some(token) => api_tokens::fetch_name(&connection, &token)?,
+            None => None,
+        };
+        return match name {
+            Some(ref name) if !tenants::is_valid_name(name) => {
+                ::log::warn!("the token {:?} does not name a valid tenant", name);
+                Ok(false)
+            }
+            Some(name) => {
+                let state = registry.state(&name)?;
+                req.extensions_mut().insert(Tenant(state));
+                Ok(true)
+            }
+            None => Ok(false),
+        };
+    }
+    if api_tokens::count(&connection)? == 0 {
+        return Ok(req.state().settings.server.open_api);
+    }
+    match bearer_token(req) {
+        Some(token) => api_tokens::is_valid(&connection, &token),
+        None => Ok(false),
+    }
+}
+
+impl Middleware<ApiState> for TokenAuth {
+    fn start(&self, req: &HttpRequest<ApiState>) -> AwResult<Started> {
+        match is_authorized(req) {
+            Ok(true) => Ok(Started::Done),
+            Ok(false) => {
+                ::log::warn!("rejected unauthenticated request {}", req.path());
+                Ok(Started::Response(
+                    HttpResponse::Unauthorized()
+                        .header(header::WWW_AUTHENTICATE, "Bearer")
+                        .finish(),
+                ))
+            }
+            Err(e) => {
+                ::log::error!("token check error {:?}", e);
+                Ok(Started::Response(HttpResponse::InternalServerError().finish()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http;
+    use actix_web::test::TestServer;
+    use crate::app_state::tests::default_test;
+    use crate::tenants::Tenants;
+    use lib_db::test_helpers::SqlStoreInMemory;
+    use lib_goo::config::db::PasswordSource;
+    use lib_goo::config::Settings;
+    use std::env;
+    use std::fs;
+    use std::sync::Arc;
+
+    const TOKEN: &str = "wvr_test";
+
+    fn server(with_token: bool, open_api: bool) -> TestServer {
+        TestServer::build_with_state(move || {
+            let mut s = default_test();
+            let mut settings = Settings::default();
+            settings.server.open_api = open_api;
+            s.settings = Arc::new(settings);
+            s.sql = Arc::new(SqlStoreInMemory::build(move |connection| {
+                if with_token {
+                    api_tokens::insert(connection, "test", TOKEN)?;
+                }
+                Ok(())
+            }));
+            s
+        })
+        .start(|app| {
+            app.middleware(TokenAuth)
+                .resource("/ping", |r| r.method(http::Method::GET).f(|_| "pong"));
+        })
+    }
+
+    fn status(srv: &mut TestServer, authorization: Option<&str>) -> http::StatusCode {
+        let mut request = srv.get().uri(srv.url("/ping"));
+        if let Some(value) = authorization {
+            request.header(header::AUTHORIZATION, value);
+        }
+        let request = request.finish().expect("request");
+        srv.execute(request.send()).expect("execute send").status()
+    }
+
+    #[test]
+    fn test_closed_without_tokens() {
+        let mut srv = server(false, false);
+        assert_eq!(status(&mut srv, None), http::StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            status(&mut srv, Some("Bearer wvr_test")),
+            http::StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[test]
+    fn test_open_api_without_tokens() {
+        let mut srv = server(false, true);
+        assert_eq!(status(&mut srv, None), http::StatusCode::OK);
+        // The first token closes the api.
+        let mut srv = server(true, true);
+        assert_eq!(status(&mut srv, None), http::StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_token_required() {
+        let mut srv = server(true, false);
+        assert_eq!(status(&mut srv, None), http::StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            status(&mut srv, Some("Bearer wvr_other")),
+            http::StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(&mut srv, Some("Bearer wvr_test")),
+            http::StatusCode::OK
+        );
+    }
+
+    #[test]
+    fn test_tenant_required() {
+        let folder = env::temp_dir().join(format!("weaver-tenants-{}", std::process::id()));
+        let mut settings = Settings::default();
+        settings.tenants.enabled = true;
+        settings.tenants.folder = Some(folder.clone());
+        let tenants = Arc::new(
+            Tenants::build(
+                Arc::new(settings),
+                default_test().metrics,
+                &PasswordSource::PassIn("secret".into()),
+                false,
+            )
+            .expect("tenants"),
+        );
+        let mut srv = TestServer::build_with_state(move || {
+            let mut s = default_test();
+            s.sql = Arc::new(SqlStoreInMemory::build(|connection| {
+                api_tokens::insert(connection, "alice", "wvr_alice")?;
+                api_tokens::insert(connection, "Not A Tenant", "wvr_bad")
+            }));
+            s.tenants = Some(tenants.clone());
+            s
+        })
+        .start(|app| {
+            app.middleware(TokenAuth)
+                .resource("/ping", |r| r.method(http::Method::GET).f(|_| "pong"));
+        });
+
+        assert_eq!(status(&mut srv, None), http::StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            status(&mut srv, Some("Bearer wvr_bad")),
+            http::StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(&mut srv, Some("Bearer wvr_alice")),
+            http::StatusCode::OK
+        );
+        assert!(folder.join("alice").join("text-repo").join("repo.def").exists());
+        fs::remove_dir_all(&folder).expect("cleanup");
+    }
+}
@@ -0,0 +1,71 @@
+//! Push the action changes to the live clients, as Server-Sent Events.
+use bytes::Bytes;
+use futures::sync::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use lib_goo::config::net::ActionEvent;
+use serde_json as json;
+use std::sync::Mutex;
+
+/// Keep track of the connected clients and send them the events.
+#[derive(Default)]
+pub(crate) struct Broadcaster {
+    clients: Mutex<Vec<UnboundedSender<Bytes>>>,
+}
+
+impl Broadcaster {
+    /// Register a new client, the stream ends when the broadcaster is dropped.
+    pub fn subscribe(&self) -> UnboundedReceiver<Bytes> {
+        let (tx, rx) = unbounded();
+        // Send a comment so that the client sees the stream open right away.
+        let _ = tx.unbounded_send(Bytes::from_static(b": connected\n\n"));
+        self.clients.lock().expect("lock clients").push(tx);
+        rx
+    }
+
+    /// Send the event to all the clients, forget the ones which disconnected.
+    pub fn publish(&self, event: &ActionEvent) {
+        let data = match json::to_string(event) {
+            Ok(d) => d,
+            Err(e) => {
+                ::log::error!("cannot serialize event {:?}", e);
+                return;
+            }
+        };
+        let message = Bytes::from(format!("data: {}\n\n", data));
+        let mut clients = self.clients.lock().expect("lock clients");
+        clients.retain(|client| client.unbounded_send(message.clone()).is_ok());
+    }
+
+    /// Number of connected clients.
+    pub fn client_count(&self) -> usize {
+        self.clients.lock().expect("lock clients").len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::Stream;
+    use lib_goo::entities::ActionId;
+
+    #[test]
+    fn test_publish() {
+        let broadcaster = Broadcaster::default();
+        let rx = broadcaster.subscribe();
+        let dropped = broadcaster.subscribe();
+        assert_eq!(broadcaster.client_count(), 2);
+        drop(dropped);
+
+        broadcaster.publish(&ActionEvent::Annotated {
+            id: ActionId::new(3),
+            annotation: "note".into(),
+        });
+        assert_eq!(broadcaster.client_count(), 1);
+
+        let received = rx.wait().take(2).map(|b| b.unwrap()).collect::<Vec<Bytes>>();
+        assert_eq!(&received[0][..], b": connected\n\n");
+        assert_eq!(
+            &received[1][..],
+            &b"data: {\"type\":\"annotated\",\"id\":3,\"annotation\":\"note\"}\n\n"[..]
+        );
+    }
+}
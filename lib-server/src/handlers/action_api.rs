@@ -1,17 +1,19 @@
 #![allow(clippy::needless_pass_by_value)]
 
-use actix_web::{http, App, HttpResponse, Json, Path, Query, State};
+use actix_web::{http, App, HttpResponse, Json, Path, Query};
 use bson::{self, Bson};
 use crate::app_state::ApiState;
+use crate::tenants::Tenant;
 use lib_ai::{compact, recommender};
 use lib_db::{actions2, Connection};
 use lib_error::{Result as Wesult, WeaverError};
 use lib_error::*;
 use lib_goo::config::net;
-use lib_goo::entities::NewAction;
+use lib_goo::entities::{ActionId, NewAction};
 use lib_index::repo::Collection;
 use lib_index::repo::Repo;
 use std::cmp;
+use std::convert::TryFrom;
 
 // Wrap into a bson envelope and save into the repo.
 fn save_to_repo(repo: &Repo, new_action: &NewAction) -> Result<String> {
@@ -28,14 +30,21 @@ fn save_to_repo(repo: &Repo, new_action: &NewAction) -> Result<String> {
 }
 
 /// Create a new action.
-fn create((state, new_action): (State<ApiState>, Json<NewAction>)) -> Wesult<String> {
+fn create((state, new_action): (Tenant, Json<NewAction>)) -> Wesult<String> {
     ::log::debug!("Entering create in action_api");
     let repo = &*state.repo;
     let new_action = &*new_action;
-    save_to_repo(repo, new_action)?;
+    let failed = |e: WeaverError| {
+        state.metrics.insert_failed();
+        e
+    };
+    save_to_repo(repo, new_action).map_err(failed)?;
 
     ::log::debug!("Saving to db");
-    actions2::insert(&state.sql.connection()?, new_action).map(|d| format!("{}", d))
+    let connection = state.sql.connection()?;
+    let id = actions2::insert(&connection, new_action).map_err(failed)?;
+    state.publish_created(&connection, id);
+    Ok(format!("{}", id))
 }
 
 /// Maximum number of recommendations to return.
@@ -49,8 +58,12 @@ fn build_recommendations(
         length: query.length,
         start: query.start,
     };
-    let mut historical =
-        actions2::fetch(&connection, query.term.as_ref().map(|a| &**a), &pagination)?;
+    let mut historical = actions2::fetch(
+        &connection,
+        query.term.as_ref().map(|a| &**a),
+        query.tag.as_ref().map(|a| &**a),
+        &pagination,
+    )?;
     let cycles = compact::extract_cycles(&historical, 4);
     let mut recommended = recommender::recommend(&historical, &query.term);
     compact::decycle(&mut historical, &cycles);
@@ -74,7 +87,7 @@ fn build_recommendations(
 }
 
 fn recommendations(
-    (state, input): (State<ApiState>, Query<net::RecommendationQuery>),
+    (state, input): (Tenant, Query<net::RecommendationQuery>),
 ) -> HttpResponse {
     match state
         .sql
@@ -90,15 +103,18 @@ fn recommendations(
     }
 }
 
-/// Pagination enabled fetch.
+/// Pagination enabled fetch, optionally restricted to the actions with a tag.
 /// Returns actions together with pagination meta data.
-fn paginated_fetch((state, input): (State<ApiState>, Query<net::Pagination>)) -> HttpResponse {
+fn paginated_fetch(
+    (state, input, filter): (Tenant, Query<net::Pagination>, Query<net::TagFilter>),
+) -> HttpResponse {
     let pagination = &*input;
-    ::log::debug!("Entering paginated_fetch {:?}", pagination);
+    let tag = filter.tag.as_ref().map(|t| &**t);
+    ::log::debug!("Entering paginated_fetch {:?} {:?}", pagination, tag);
     match state
         .sql
         .connection()
-        .and_then(|c| actions2::fetch(&c, None, pagination).map(|all| (c, all)))
+        .and_then(|c| actions2::fetch(&c, None, tag, pagination).map(|all| (c, all)))
         .and_then(|(c, all)| actions2::count(&c).map(|total| (all, total)))
     {
         Ok((entries, total)) => {
@@ -119,10 +135,82 @@ fn paginated_fetch((state, input): (State<ApiState>, Query<net::Pagination>)) ->
 
 /// Update the annotation for the given action.
 fn set_annotation(
-    (state, input, path): (State<ApiState>, Json<net::Annotation>, Path<u64>),
+    (state, input, path): (Tenant, Json<net::Annotation>, Path<u64>),
 ) -> Wesult<String> {
-    actions2::set_annotation(&state.sql.connection()?, *path, &input.annotation)
-        .map(|d| format!("{}", d))
+    let updated = actions2::set_annotation(&state.sql.connection()?, *path, &input.annotation)?;
+    if updated > 0 {
+        state.events.publish(&net::ActionEvent::Annotated {
+            id: ActionId::new(*path as usize),
+            annotation: input.annotation.clone(),
+        });
+    }
+    Ok(format!("{}", updated))
+}
+
+// The id of the action in the path, the ids which do not fit a sqlite integer cannot exist.
+fn path_id(path: &Path<u64>) -> Option<i32> {
+    i32::try_from(**path).ok()
+}
+
+/// Delete one action, reply with what was deleted.
+fn delete_one((state, path): (Tenant, Path<u64>)) -> HttpResponse {
+    let id = match path_id(&path) {
+        Some(id) => id,
+        None => return HttpResponse::NotFound().finish(),
+    };
+    match state.delete_actions(&[id]) {
+        Ok(ref report) if report.actions == 0 => HttpResponse::NotFound().finish(),
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => {
+            let msg = format!("delete action error {:?}", e);
+            ::log::error!("{}", msg);
+            HttpResponse::build(http::StatusCode::INTERNAL_SERVER_ERROR).body(msg)
+        }
+    }
+}
+
+/// Delete all the actions matching the filter passed in the query string.
+fn delete_matching((state, filter): (Tenant, Query<net::ActionFilter>)) -> HttpResponse {
+    if filter.is_empty() {
+        return HttpResponse::BadRequest().body("set at least one of command, host, since, until");
+    }
+    match state
+        .sql
+        .connection()
+        .and_then(|c| actions2::matching(&c, &filter))
+        .and_then(|ids| state.delete_actions(&ids))
+    {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => {
+            let msg = format!("delete actions error {:?}", e);
+            ::log::error!("{}", msg);
+            HttpResponse::build(http::StatusCode::INTERNAL_SERVER_ERROR).body(msg)
+        }
+    }
+}
+
+/// Change the epic and/or the annotation of an action.
+fn patch(
+    (state, input, path): (Tenant, Json<net::ActionPatch>, Path<u64>),
+) -> Wesult<String> {
+    if path_id(&path).is_none() {
+        return Ok("0".into());
+    }
+    let connection = state.sql.connection()?;
+    let mut updated = 0;
+    if let Some(ref epic) = input.epic {
+        updated = actions2::set_epic(&connection, *path, Some(epic))?;
+    }
+    if let Some(ref annotation) = input.annotation {
+        updated = actions2::set_annotation(&connection, *path, annotation)?;
+        if let (true, Ok(id)) = (updated > 0, usize::try_from(*path)) {
+            state.events.publish(&net::ActionEvent::Annotated {
+                id: ActionId::new(id),
+                annotation: annotation.clone(),
+            });
+        }
+    }
+    Ok(format!("{}", updated))
 }
 
 // Register the routes with the application.
@@ -134,6 +222,7 @@ pub(crate) fn config(app: App<ApiState>, should_log: bool) -> App<ApiState> {
     let app = app.resource(net::ACTIONS2_BASE, |r| {
         r.method(http::Method::GET).with(paginated_fetch);
         r.method(http::Method::POST).with(create);
+        r.method(http::Method::DELETE).with(delete_matching);
     });
 
     // recommendations
@@ -148,8 +237,18 @@ pub(crate) fn config(app: App<ApiState>, should_log: bool) -> App<ApiState> {
     if should_log {
         ::log::debug!("registering {}", ann);
     }
-    app.resource(&ann, |r| {
+    let app = app.resource(&ann, |r| {
         r.method(http::Method::POST).with(set_annotation);
+    });
+
+    // single action, registered last since it would also match the paths above
+    let one = format!("{}/{{id}}", net::ACTIONS2_BASE);
+    if should_log {
+        ::log::debug!("registering {}", one);
+    }
+    app.resource(&one, |r| {
+        r.method(http::Method::DELETE).with(delete_one);
+        r.method(http::Method::PATCH).with(patch);
     })
 }
 
@@ -237,4 +336,129 @@ mod tests {
         assert_eq!(out.entries.len(), 1);
         assert_eq!(&out.entries[0].name, "bar");
     }
+
+    #[test]
+    fn test_create_publishes() {
+        use crate::events::Broadcaster;
+        use futures::Stream;
+
+        let events = Arc::new(Broadcaster::default());
+        let received = events.subscribe();
+        let actions = StateWithActions(Arc::new(vec!["foo".to_string()]));
+        let shared = events.clone();
+        let mut srv = TestServer::build_with_state(move || {
+            let mut s = actions.state();
+            s.events = shared.clone();
+            s
+        })
+        .start(|app| {
+            app.resource(net::ACTIONS2_BASE, |r| {
+                r.method(http::Method::POST).with(create);
+            });
+        });
+
+        let request = srv
+            .post()
+            .uri(srv.url(net::ACTIONS2_BASE))
+            .json(NewAction {
+                command: "bar".into(),
+                ..NewAction::default()
+            })
+            .expect("request");
+        let response = srv.execute(request.send()).expect("execute send");
+        assert!(response.status().is_success());
+
+        // Skip the connection comment.
+        let pushed = received.wait().nth(1).expect("event").expect("bytes");
+        let data = String::from_utf8(pushed.to_vec()).expect("utf8");
+        assert!(data.starts_with("data: "));
+        match json::from_str::<net::ActionEvent>(data[6..].trim()).expect("json decode") {
+            net::ActionEvent::Created { action } => {
+                assert_eq!(action.name, "bar");
+                assert_eq!(action.id, ActionId::new(2));
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_delete() {
+        use crate::events::Broadcaster;
+        use futures::Stream;
+
+        let events = Arc::new(Broadcaster::default());
+        let received = events.subscribe();
+        let actions = StateWithActions(Arc::new(vec![
+            "ls".to_string(),
+            "echo secret".to_string(),
+            "echo secret again".to_string(),
+        ]));
+        let shared = events.clone();
+        let mut srv = TestServer::build_with_state(move || {
+            let mut s = actions.state();
+            s.events = shared.clone();
+            s
+        })
+        .start(|app| {
+            app.resource(net::ACTIONS2_BASE, |r| {
+                r.method(http::Method::DELETE).with(delete_matching);
+            });
+            app.resource("/v2/actions/{id}", |r| {
+                r.method(http::Method::DELETE).with(delete_one);
+            });
+        });
+
+        let delete = |srv: &mut TestServer, uri: &str| {
+            let request = srv
+                .client(http::Method::DELETE, uri)
+                .finish()
+                .expect("request");
+            srv.execute(request.send()).expect("execute send")
+        };
+
+        let response = delete(&mut srv, "/v2/actions/1");
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).expect("execute body");
+        let out: net::DeleteReport = json::from_slice(&bytes[..]).expect("json decode");
+        assert_eq!(out.actions, 1);
+        assert_eq!(out.commands, 1);
+
+        // Nothing is published for the unknown ids, the id 2 does not match 2^32 + 2.
+        for uri in &["/v2/actions/1", "/v2/actions/7", "/v2/actions/4294967298"] {
+            let response = delete(&mut srv, uri);
+            assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+        }
+
+        // An empty filter would delete everything.
+        let response = delete(&mut srv, net::ACTIONS2_BASE);
+        assert_eq!(response.status(), http::StatusCode::BAD_REQUEST);
+
+        let response = delete(&mut srv, "/v2/actions?command=secret");
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).expect("execute body");
+        let out: net::DeleteReport = json::from_slice(&bytes[..]).expect("json decode");
+        assert_eq!(out.actions, 2);
+        assert_eq!(out.commands, 2);
+
+        // Skip the connection comment.
+        let pushed = received
+            .wait()
+            .skip(1)
+            .take(2)
+            .map(|b| {
+                let data = String::from_utf8(b.expect("bytes").to_vec()).expect("utf8");
+                match json::from_str::<net::ActionEvent>(data[6..].trim()).expect("json decode") {
+                    net::ActionEvent::Deleted { ids } => ids,
+                    other => panic!("unexpected event {:?}", other),
+                }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pushed,
+            vec![
+                vec![ActionId::new(1)],
+                vec![ActionId::new(2), ActionId::new(3)]
+            ]
+        );
+    }
 }
@@ -0,0 +1,121 @@
+#![allow(clippy::needless_pass_by_value)]
+use actix_web::{http, App, HttpResponse, Json, Path, Query};
+use crate::app_state::ApiState;
+use crate::tenants::Tenant;
+use lib_db::bookmarks;
+use lib_error::*;
+use lib_goo::config::net;
+
+// Reply with the json value or with the error message.
+fn reply<T: ::serde::Serialize>(context: &str, value: Result<T>) -> HttpResponse {
+    match value {
+        Ok(v) => HttpResponse::Ok().json(v),
+        Err(e) => {
+            let msg = format!("{} error {:?}", context, e);
+            ::log::error!("{}", msg);
+            HttpResponse::build(http::StatusCode::INTERNAL_SERVER_ERROR).body(msg)
+        }
+    }
+}
+
+// Reply with 404 when nothing was removed.
+fn reply_removed(context: &str, removed: Result<bool>) -> HttpResponse {
+    match removed {
+        Ok(true) => HttpResponse::Ok().finish(),
+        Ok(false) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            let msg = format!("{} error {:?}", context, e);
+            ::log::error!("{}", msg);
+            HttpResponse::build(http::StatusCode::INTERNAL_SERVER_ERROR).body(msg)
+        }
+    }
+}
+
+/// List the starred pages, most recent first.
+fn starred(state: Tenant) -> HttpResponse {
+    reply(
+        "stars",
+        state
+            .sql
+            .connection()
+            .and_then(|c| bookmarks::fetch_starred(&c)),
+    )
+}
+
+/// Star a page, the page does not need to be indexed.
+fn star((state, input): (Tenant, Json<net::PageQuery>)) -> Result<String> {
+    bookmarks::star(&state.sql.connection()?, &input.url)?;
+    Ok("starred".into())
+}
+
+/// Remove the star of a page.
+fn unstar((state, query): (Tenant, Query<net::PageQuery>)) -> HttpResponse {
+    reply_removed(
+        "unstar",
+        state
+            .sql
+            .connection()
+            .and_then(|c| bookmarks::unstar(&c, &query.url)),
+    )
+}
+
+/// List the reading lists with their number of pages.
+fn lists(state: Tenant) -> HttpResponse {
+    reply(
+        "reading lists",
+        state
+            .sql
+            .connection()
+            .and_then(|c| bookmarks::fetch_lists(&c)),
+    )
+}
+
+/// List the pages of a reading list.
+fn list_pages((state, path): (Tenant, Path<String>)) -> HttpResponse {
+    match state
+        .sql
+        .connection()
+        .and_then(|c| bookmarks::list_pages(&c, &path))
+    {
+        Ok(None) => HttpResponse::NotFound().finish(),
+        found => reply("reading list", found),
+    }
+}
+
+/// Add a page to a reading list, the list is created if needed.
+fn add_page(
+    (state, path, input): (Tenant, Path<String>, Json<net::PageQuery>),
+) -> Result<String> {
+    bookmarks::add_to_list(&state.sql.connection()?, &path, &input.url)?;
+    Ok("added".into())
+}
+
+/// Remove a page from a reading list, or delete the whole list when no url is given.
+fn remove(
+    (state, path, query): (Tenant, Path<String>, Query<net::ReadingListQuery>),
+) -> HttpResponse {
+    reply_removed(
+        "remove from reading list",
+        state.sql.connection().and_then(|c| match query.url {
+            Some(ref url) => bookmarks::remove_from_list(&c, &path, url),
+            None => bookmarks::delete_list(&c, &path),
+        }),
+    )
+}
+
+/// Add our routes to the Actix server configuration.
+pub(crate) fn config(app: App<ApiState>) -> App<ApiState> {
+    let app = app.resource(net::STARS, |r| {
+        r.method(http::Method::GET).with(starred);
+        r.method(http::Method::POST).with(star);
+        r.method(http::Method::DELETE).with(unstar);
+    });
+    let app = app.resource(net::READING_LISTS, |r| {
+        r.method(http::Method::GET).with(lists)
+    });
+    app.resource(&format!("{}/{{name}}", net::READING_LISTS), |r| {
+        r.method(http::Method::GET).with(list_pages);
+        r.method(http::Method::POST).with(add_page);
+        r.method(http::Method::DELETE).with(remove);
+    })
+}
@@ -0,0 +1,73 @@
+//! Stream the new actions and the annotation changes to the HUD, as Server-Sent Events.
+#![allow(clippy::needless_pass_by_value)]
+use actix_web::http::header;
+use actix_web::{error, http, App, HttpResponse};
+use crate::app_state::ApiState;
+use crate::tenants::Tenant;
+use futures::Stream;
+use lib_goo::config::net;
+
+fn hud_events(state: Tenant) -> HttpResponse {
+    let events = state
+        .events
+        .subscribe()
+        .map_err(|_| error::ErrorInternalServerError("event stream closed"));
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .streaming(events)
+}
+
+pub(crate) fn config(app: App<ApiState>) -> App<ApiState> {
+    app.resource(net::HUD_EVENTS, |r| {
+        r.method(http::Method::GET).with(hud_events)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestServer;
+    use crate::app_state::tests::default_test;
+    use crate::auth::TokenAuth;
+    use lib_db::api_tokens;
+    use lib_db::test_helpers::SqlStoreInMemory;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_token_required() {
+        let mut srv = TestServer::build_with_state(|| {
+            let mut s = default_test();
+            s.sql = Arc::new(SqlStoreInMemory::build(|connection| {
+                api_tokens::insert(connection, "hud", "wvr_hud")
+            }));
+            s
+        })
+        .start(|app| {
+            app.middleware(TokenAuth).resource(net::HUD_EVENTS, |r| {
+                r.method(http::Method::GET).with(hud_events)
+            });
+        });
+
+        let request = srv
+            .get()
+            .uri(srv.url(net::HUD_EVENTS))
+            .finish()
+            .expect("request");
+        let response = srv.execute(request.send()).expect("execute send");
+        assert_eq!(response.status(), http::StatusCode::UNAUTHORIZED);
+
+        let request = srv
+            .get()
+            .uri(srv.url(net::HUD_EVENTS))
+            .header(header::AUTHORIZATION, "Bearer wvr_hud")
+            .finish()
+            .expect("request");
+        let response = srv.execute(request.send()).expect("execute send");
+        assert!(response.status().is_success());
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/event-stream"
+        );
+    }
+}
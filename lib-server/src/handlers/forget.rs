@@ -0,0 +1,97 @@
+//! Forget the actions, pages and documents matching a pattern, in all the stores.
+#![allow(clippy::needless_pass_by_value)]
+use actix_web::{http, App, HttpResponse, Json};
+use crate::app_state::ApiState;
+use crate::tenants::Tenant;
+use lib_error::{Result as Wesult};
+use lib_goo::config::net;
+use lib_index::forget;
+
+fn _forget((state, input): (Tenant, Json<net::ForgetRequest>)) -> Wesult<net::ForgetReport> {
+    let connection = state.sql.connection()?;
+    ::log::info!("forgetting {}", input.pattern);
+    forget::forget(&connection, &*state.repo, &*state.indexer, &input.pattern)
+}
+
+fn create(data: (Tenant, Json<net::ForgetRequest>)) -> HttpResponse {
+    match _forget(data) {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => {
+            let msg = format!("forget error {:?}", e);
+            ::log::error!("{}", msg);
+            HttpResponse::build(http::StatusCode::INTERNAL_SERVER_ERROR).body(msg)
+        }
+    }
+}
+
+pub(crate) fn config(app: App<ApiState>) -> App<ApiState> {
+    app.resource(net::FORGET, |r| {
+        r.method(http::Method::POST).with(create);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestServer;
+    use crate::app_state::tests::StateWithActions;
+    use bincode;
+    use lib_goo::entities::PageContent;
+    use lib_index::repo::Collection;
+    use serde_json as json;
+    use std::sync::Arc;
+
+    fn forget_secret(srv: &mut TestServer) -> net::ForgetReport {
+        let request = srv
+            .post()
+            .uri(srv.url(net::FORGET))
+            .json(net::ForgetRequest {
+                pattern: "secret".into(),
+            })
+            .expect("request");
+        let response = srv.execute(request.send()).expect("execute send");
+        assert!(response.status().is_success());
+
+        let bytes = srv.execute(response.body()).expect("execute body");
+        json::from_slice(&bytes[..]).expect("json decode")
+    }
+
+    #[test]
+    fn test_forget() {
+        let actions = StateWithActions(Arc::new(vec!["ls".to_string(), "secret".to_string()]));
+        let mut srv = TestServer::build_with_state(move || actions.state()).start(|app| {
+            app.resource(net::FORGET, |r| r.method(http::Method::POST).with(create));
+        });
+
+        let out = forget_secret(&mut srv);
+        assert_eq!(out.actions, 1);
+        assert_eq!(out.commands, 1);
+    }
+
+    #[test]
+    fn test_forget_repo_entries() {
+        let actions = StateWithActions(Arc::new(Vec::new()));
+        let mut srv = TestServer::build_with_state(move || {
+            let s = actions.state();
+            let collection = Collection(PageContent::collection_name().into());
+            for url in vec!["https://secret/a", "https://public/b"] {
+                let page_content = PageContent {
+                    url: url.into(),
+                    ..PageContent::default()
+                };
+                let serialized = bincode::serialize(&page_content).expect("serialize");
+                s.repo.add(&collection, &serialized).expect("repo add");
+                s.indexer.add(&page_content).expect("index add");
+            }
+            s
+        })
+        .start(|app| {
+            app.resource(net::FORGET, |r| r.method(http::Method::POST).with(create));
+        });
+
+        let out = forget_secret(&mut srv);
+        assert_eq!(out.repo_entries, 1);
+        assert_eq!(out.index_documents, 1);
+        assert_eq!(out.urls, vec![String::from("https://secret/a")]);
+    }
+}
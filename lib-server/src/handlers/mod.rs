@@ -2,8 +2,14 @@ use actix_web::App;
 use crate::app_state::ApiState;
 
 mod action_api;
+mod bookmarks;
+mod events;
+mod forget;
 mod search_api;
+mod stats;
 mod summary;
+mod sync;
+mod tags;
 mod url;
 mod url_policies;
 
@@ -23,5 +29,11 @@ pub(crate) fn config(app: App<ApiState>) -> App<ApiState> {
     let app = url_policies::config(app);
     let app = search_api::config(app);
     let app = url::config(app);
+    let app = forget::config(app);
+    let app = tags::config(app);
+    let app = bookmarks::config(app);
+    let app = stats::config(app);
+    let app = sync::config(app);
+    let app = events::config(app);
     action_api::config(app, should_log)
 }
@@ -1,43 +1,123 @@
 #![allow(clippy::needless_pass_by_value)]
 
-use actix_web::{http, App, HttpResponse, Json, Query, State};
+use actix_web::{http, App, HttpResponse, Json, Query};
 use crate::app_state::ApiState;
+use crate::tenants::Tenant;
+use crate::readability;
 use bincode;
 use lib_goo::normalize;
-use lib_db::{store_policies, pages};
+use lib_db::{store_policies, pages, replication, tags, Connection};
 use lib_error::{Result as Wesult};
-use lib_goo::entities::PageContent;
+use lib_goo::entities::{PageContent, PageHtml};
 use lib_index::repo::Collection;
 
+/// Maximum size of an uploaded page with its html, in bytes.
+const UPLOAD_LIMIT: usize = 8 * 1024 * 1024;
+
 #[derive(Debug, ::serde::Serialize, ::serde::Deserialize)]
 struct PageStatus {
     is_indexed: bool,
     summary: Option<String>,
 }
 
-fn _create((state, mut input): (State<ApiState>, Json<PageContent>)) -> Wesult<PageStatus> {
-    let repo = &*state.repo;
+/// Page sent by the extension, either the raw html or the text of the body.
+#[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize)]
+struct PageUpload {
+    url: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    body: String,
+    #[serde(default)]
+    html: Option<String>,
+}
 
-    let connection = state.sql.connection()?;
-    input.url = normalize::normalize_url(&input.url)?.into_owned();
+// Build the page content from the main text of the html, or from the body as sent.
+fn page_content(upload: &PageUpload) -> Wesult<PageContent> {
+    let url = normalize::normalize_url(&upload.url)?.into_owned();
+    match upload.html {
+        Some(ref html) if !html.trim().is_empty() => {
+            let extracted = readability::extract(&url, html);
+            let title = if upload.title.is_empty() {
+                extracted.title.unwrap_or_default()
+            } else {
+                upload.title.clone()
+            };
+            Ok(PageContent {
+                url,
+                title,
+                body: extracted.text,
+                language: extracted.language,
+                word_count: extracted.word_count,
+                excerpt: extracted.excerpt,
+                links: extracted.links,
+            })
+        }
+        _ => {
+            let (language, word_count, excerpt) = readability::describe(&upload.body);
+            Ok(PageContent {
+                url,
+                title: upload.title.clone(),
+                body: upload.body.clone(),
+                language,
+                word_count,
+                excerpt,
+                links: Vec::new(),
+            })
+        }
+    }
+}
 
-    let url_restrictions = store_policies::Restrictions::fetch(&connection)?;
-    if !url_restrictions.should_index(&input) {
-        return Ok(PageStatus {
-            is_indexed: false,
-            summary: state.indexer.summary(),
-        });
+/// Save the page content in the repo and the text index, unless the url policies forbid it.
+/// Returns false when the page was not indexed. The pages received from the peer are `sent`.
+pub(crate) fn store_page(
+    state: &ApiState,
+    connection: &Connection,
+    page_content: &PageContent,
+    sent: bool,
+) -> Wesult<bool> {
+    let url_restrictions = store_policies::Restrictions::fetch(connection)?;
+    if !url_restrictions.should_index(page_content) {
+        return Ok(false);
     }
 
-    let serialized = bincode::serialize(&*input).map_err(|_| "serializing for the repo")?;
-    repo.add(
+    let serialized = bincode::serialize(page_content).map_err(|_| "serializing for the repo")?;
+    let repo_id = state.repo.add(
         &Collection(PageContent::collection_name().into()),
         &serialized,
     )?;
     let indexer = &*(state.indexer);
-    let _id = indexer.add(&input)?;
+    let _id = indexer.add(page_content).map_err(|e| {
+        state.metrics.index_failed();
+        e
+    })?;
+
+    let _page_id =
+        pages::fetch_or_create_id(connection, &page_content.url, Some(&page_content.title))?;
+    replication::save_page_copy(connection, &page_content.url, &repo_id, sent)?;
+    Ok(true)
+}
+
+fn _create((state, input): (Tenant, Json<PageUpload>)) -> Wesult<PageStatus> {
+    let connection = state.sql.connection()?;
+    let page_content = page_content(&input)?;
 
-    let _page_id = pages::fetch_or_create_id(&connection, &input.url, Some(&input.title))?;
+    if !store_page(&state, &connection, &page_content, false)? {
+        return Ok(PageStatus {
+            is_indexed: false,
+            summary: state.indexer.summary(),
+        });
+    }
+    if let (true, Some(html)) = (state.settings.index.keep_html, input.html.as_ref()) {
+        let page_html = PageHtml {
+            url: page_content.url.clone(),
+            html: html.clone(),
+        };
+        let serialized = bincode::serialize(&page_html).map_err(|_| "serializing for the repo")?;
+        state
+            .repo
+            .add(&Collection(PageHtml::collection_name().into()), &serialized)?;
+    }
 
     Ok(PageStatus {
         is_indexed: true,
@@ -46,7 +126,7 @@ fn _create((state, mut input): (State<ApiState>, Json<PageContent>)) -> Wesult<P
 }
 
 // API used by the Chrome extension to upload content to be indexed.
-fn create(data: (State<ApiState>, Json<PageContent>)) -> HttpResponse {
+fn create(data: (Tenant, Json<PageUpload>)) -> HttpResponse {
     match _create(data) {
         Ok(ps) => HttpResponse::Ok().json(ps),
         Err(e) => {
@@ -60,17 +140,34 @@ fn create(data: (State<ApiState>, Json<PageContent>)) -> HttpResponse {
 #[derive(::serde::Deserialize)]
 struct SearchQuery {
     term: String,
+    /// Only return the pages with this tag.
+    tag: Option<String>,
 }
 
 // API used to make a query and download the matches.
-fn search((state, query): (State<ApiState>, Query<SearchQuery>)) -> String {
-    let indexer = &*state.indexer;
+fn search((state, query): (Tenant, Query<SearchQuery>)) -> String {
+    let tagged = match query.tag {
+        Some(ref tag) => match state.sql.connection().and_then(|c| tags::page_urls(&c, tag)) {
+            Ok(urls) => Some(urls),
+            Err(e) => {
+                ::log::error!("search_api tag error {:?}", e);
+                return String::new();
+            }
+        },
+        None => None,
+    };
 
-    indexer
-        .search(&query.term)
+    state
+        .search_boosted(&query.term)
         .map(|f| {
             f.matches
                 .iter()
+                .filter(|d| {
+                    tagged
+                        .as_ref()
+                        .map(|urls| tags::contains_url(urls, &d.url))
+                        .unwrap_or(true)
+                })
                 .map(|d| format!("{} {}\n", d.url, d.title))
                 .fold(String::new(), |mut a, n| {
                     a.push_str(&n);
@@ -83,7 +180,9 @@ fn search((state, query): (State<ApiState>, Query<SearchQuery>)) -> String {
 pub(crate) fn config(app: App<ApiState>) -> App<ApiState> {
     app.resource("/search", |r| {
         r.method(http::Method::GET).with(search);
-        r.method(http::Method::POST).with(create);
+        r.method(http::Method::POST).with_config(create, |cfg| {
+            cfg.1.limit(UPLOAD_LIMIT);
+        });
     })
 }
 
@@ -93,6 +192,10 @@ mod tests {
     use actix_web::test::TestServer;
     use actix_web::*;
     use crate::app_state::tests::default_test;
+    use lib_db::bookmarks;
+    use lib_db::test_helpers::SqlStoreInMemory;
+    use std::sync::Arc;
+    use serde_json as json;
 
     fn state() -> ApiState {
         let s = default_test();
@@ -101,11 +204,39 @@ mod tests {
                 url: "url foo".into(),
                 title: "title bar".into(),
                 body: "body baz".into(),
+                ..PageContent::default()
             })
             .expect("adding test PageContent");
         s
     }
 
+    #[test]
+    fn test_page_content_from_html() {
+        let upload = PageUpload {
+            url: "https://example.com/a#top".into(),
+            html: Some(
+                "<html><head><title>Notes</title></head><body><nav>Menu</nav>\
+                 <p>Some notes.</p></body></html>"
+                    .into(),
+            ),
+            ..PageUpload::default()
+        };
+        let page_content = page_content(&upload).expect("page content");
+        assert_eq!(page_content.title, "Notes");
+        assert_eq!(page_content.body, "Some notes.");
+        assert_eq!(page_content.word_count, 2);
+
+        let upload = PageUpload {
+            url: "https://example.com/b".into(),
+            title: "Text".into(),
+            body: "Sent as text".into(),
+            ..PageUpload::default()
+        };
+        let page_content = page_content(&upload).expect("page content");
+        assert_eq!(page_content.body, "Sent as text");
+        assert_eq!(page_content.excerpt, Some("Sent as text".into()));
+    }
+
     #[test]
     fn test_resource() {
         let mut srv = TestServer::build_with_state(|| state()).start(|app| {
@@ -127,4 +258,81 @@ mod tests {
         let data = String::from_utf8(bytes.to_vec()).expect("bytes");
         assert_eq!(&data, "url foo title bar\n");
     }
+
+    #[test]
+    fn test_upload_large_page() {
+        let mut srv = TestServer::build_with_state(|| state()).start(|app| {
+            app.resource("/search", |r| {
+                r.method(http::Method::POST).with_config(create, |cfg| {
+                    cfg.1.limit(UPLOAD_LIMIT);
+                });
+            });
+        });
+
+        // Larger than the 256KiB default limit of the json extractor.
+        let upload = PageUpload {
+            url: "https://example.com/large".into(),
+            html: Some(format!(
+                "<html><body><p>{}</p></body></html>",
+                "word ".repeat(100_000)
+            )),
+            ..PageUpload::default()
+        };
+        let request = srv
+            .post()
+            .uri(srv.url("/search"))
+            .json(upload)
+            .expect("request");
+        let response = srv.execute(request.send()).expect("execute send");
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).expect("execute body");
+        let status: PageStatus = json::from_slice(&bytes[..]).expect("json decode");
+        assert!(status.is_indexed);
+    }
+
+    #[test]
+    fn test_boost_before_limit() {
+        let starred = "https://example.com/starred";
+        let mut s = default_test();
+        s.sql = Arc::new(SqlStoreInMemory::build(move |c| bookmarks::star(c, starred)));
+        let limit = s.settings.index.search_limit;
+        for i in 0..limit {
+            let page_content = PageContent {
+                url: format!("https://example.com/{}", i),
+                ..PageContent::default()
+            };
+            s.indexer.add(&page_content).expect("index add");
+        }
+        // Ranked just below the limit.
+        s.indexer
+            .add(&PageContent {
+                url: starred.into(),
+                ..PageContent::default()
+            })
+            .expect("index add");
+
+        let results = s.search_boosted("example").expect("search");
+        assert_eq!(results.matches.len(), limit);
+        assert_eq!(results.matches[0].url, starred);
+    }
+
+    #[test]
+    fn test_search_with_tag() {
+        let mut srv = TestServer::build_with_state(|| state()).start(|app| {
+            app.resource("/search", |r| {
+                r.method(http::Method::GET).with(search);
+            });
+        });
+
+        let request = srv
+            .get()
+            .uri(srv.url("/search?term=1&tag=unused"))
+            .finish()
+            .expect("request");
+        let response = srv.execute(request.send()).expect("execute send");
+
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).expect("execute body");
+        assert!(bytes.is_empty());
+    }
 }
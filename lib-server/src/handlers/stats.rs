@@ -0,0 +1,61 @@
+//! Activity statistics computed from the actions, in json.
+#![allow(clippy::needless_pass_by_value)]
+use actix_web::{http, App, HttpResponse, Query};
+use crate::app_state::ApiState;
+use crate::tenants::Tenant;
+use lib_db::stats;
+use lib_goo::config::net;
+
+fn fetch((state, query): (Tenant, Query<net::StatsQuery>)) -> HttpResponse {
+    let limit = query.limit.unwrap_or(stats::DEFAULT_LIMIT);
+    match state
+        .sql
+        .connection()
+        .and_then(|c| stats::compute(&c, query.days, limit))
+    {
+        Ok(out) => HttpResponse::Ok().json(out),
+        Err(e) => {
+            let msg = format!("stats error {:?}", e);
+            ::log::error!("{}", msg);
+            HttpResponse::build(http::StatusCode::INTERNAL_SERVER_ERROR).body(msg)
+        }
+    }
+}
+
+pub(crate) fn config(app: App<ApiState>) -> App<ApiState> {
+    app.resource(net::STATS, |r| {
+        r.method(http::Method::GET).with(fetch);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_state::tests::StateWithActions;
+    use actix_web::test::TestServer;
+    use actix_web::*;
+    use serde_json as json;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_stats() {
+        let actions = StateWithActions(Arc::new(vec!["ls".to_string(), "ls".to_string()]));
+        let mut srv = TestServer::build_with_state(move || actions.state()).start(|app| {
+            app.resource(net::STATS, |r| r.method(http::Method::GET).with(fetch));
+        });
+
+        let request = srv
+            .get()
+            .uri(srv.url("/stats?limit=5"))
+            .finish()
+            .expect("request");
+        let response = srv.execute(request.send()).expect("execute send");
+        assert!(response.status().is_success());
+
+        let bytes = srv.execute(response.body()).expect("execute body");
+        let out: net::Stats = json::from_slice(&bytes[..]).expect("json decode");
+        assert_eq!(out.total, 2);
+        assert_eq!(out.hours.len(), 24);
+        assert_eq!(out.weekdays.len(), 7);
+    }
+}
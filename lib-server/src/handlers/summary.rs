@@ -1,10 +1,11 @@
 #![allow(clippy::needless_pass_by_value)]
 /// Return summary information about the stores in text format. The intent is to plug this information
 /// directly in the UI.
-use actix_web::{http, App, State};
+use actix_web::{http, App};
 use crate::app_state::ApiState;
+use crate::tenants::Tenant;
 
-fn summary(state: State<ApiState>) -> String {
+fn summary(state: Tenant) -> String {
     state.indexer.summary().unwrap_or_default()
 }
 
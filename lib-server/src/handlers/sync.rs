@@ -0,0 +1,138 @@
+//! Receive the actions and page contents pushed by a peer server.
+//!
+//! The pages whose content is already in the repo are skipped. The actions go through the
+//! do not log restrictions of this server, like the browser imports.
+#![allow(clippy::needless_pass_by_value)]
+use actix_web::{http, App, HttpResponse, Json};
+use crate::app_state::ApiState;
+use crate::tenants::Tenant;
+use lib_db::replication::{self, Received};
+use lib_db::store_policies::Restrictions;
+use lib_error::{Result as Wesult};
+use lib_goo::config::net;
+
+use super::search_api::store_page;
+
+/// Maximum size of a sync batch, in bytes.
+const BATCH_LIMIT: usize = 16 * 1024 * 1024;
+
+fn _receive((state, batch): (Tenant, Json<net::SyncBatch>)) -> Wesult<net::SyncReport> {
+    let connection = state.sql.connection()?;
+    let mut report = net::SyncReport::default();
+    for page_content in batch.pages.iter() {
+        if replication::has_page_copy(&connection, &page_content.url)? {
+            continue;
+        }
+        if store_page(&state, &connection, page_content, true)? {
+            report.pages += 1;
+        }
+    }
+    let restrictions = Restrictions::fetch(&connection)?;
+    for action in batch.actions.iter() {
+        match replication::receive(&connection, &restrictions, action)? {
+            Received::Inserted(id) => {
+                report.inserted += 1;
+                state.publish_created(&connection, id);
+            }
+            Received::Duplicate => report.duplicates += 1,
+            Received::Restricted => report.restricted += 1,
+        }
+    }
+    Ok(report)
+}
+
+fn receive(data: (Tenant, Json<net::SyncBatch>)) -> HttpResponse {
+    match _receive(data) {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => {
+            ::log::error!("sync error {:?}", e);
+            HttpResponse::build(http::StatusCode::INTERNAL_SERVER_ERROR).finish()
+        }
+    }
+}
+
+pub(crate) fn config(app: App<ApiState>) -> App<ApiState> {
+    app.resource(net::SYNC, |r| {
+        r.method(http::Method::POST).with_config(receive, |cfg| {
+            cfg.1.limit(BATCH_LIMIT);
+        });
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_state::tests::StateWithActions;
+    use actix_web::test::TestServer;
+    use actix_web::*;
+    use lib_goo::entities::{NewAction, PageContent};
+    use serde_json as json;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_receive() {
+        let actions = StateWithActions(Arc::new(Vec::new()));
+        let mut srv = TestServer::build_with_state(move || actions.state()).start(|app| {
+            app.resource(net::SYNC, |r| {
+                r.method(http::Method::POST).with(receive);
+            });
+        });
+
+        let action = || net::SyncAction {
+            action: NewAction {
+                kind: "shell".into(),
+                command: "make".into(),
+                executed: "2019-01-01T10:00:00Z".into(),
+                host: "desktop".into(),
+                ..NewAction::default()
+            },
+            ..net::SyncAction::default()
+        };
+        let batch = net::SyncBatch {
+            actions: vec![action(), action()],
+            pages: Vec::new(),
+        };
+        let request = srv
+            .post()
+            .uri(srv.url(net::SYNC))
+            .json(batch)
+            .expect("request");
+        let response = srv.execute(request.send()).expect("execute send");
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).expect("execute body");
+        let report: net::SyncReport = json::from_slice(&bytes[..]).expect("json decode");
+        assert_eq!(report.inserted, 1);
+        assert_eq!(report.duplicates, 1);
+    }
+
+    #[test]
+    fn test_receive_page_once() {
+        let actions = StateWithActions(Arc::new(Vec::new()));
+        let mut srv = TestServer::build_with_state(move || actions.state()).start(|app| {
+            app.resource(net::SYNC, |r| {
+                r.method(http::Method::POST).with(receive);
+            });
+        });
+
+        let page = || PageContent {
+            url: "https://example.com/".into(),
+            title: "example".into(),
+            body: "some text".into(),
+            ..PageContent::default()
+        };
+        let batch = net::SyncBatch {
+            actions: Vec::new(),
+            pages: vec![page(), page()],
+        };
+        let request = srv
+            .post()
+            .uri(srv.url(net::SYNC))
+            .json(batch)
+            .expect("request");
+        let response = srv.execute(request.send()).expect("execute send");
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).expect("execute body");
+        let report: net::SyncReport = json::from_slice(&bytes[..]).expect("json decode");
+        assert_eq!(report.pages, 1);
+    }
+}
@@ -0,0 +1,176 @@
+#![allow(clippy::needless_pass_by_value)]
+use actix_web::{http, App, HttpResponse, Json, Path, Query};
+use crate::app_state::ApiState;
+use crate::tenants::Tenant;
+use lib_db::tags;
+use lib_error::*;
+use lib_goo::config::net;
+
+// Reply with the json value or with the error message.
+fn reply<T: ::serde::Serialize>(context: &str, value: Result<T>) -> HttpResponse {
+    match value {
+        Ok(v) => HttpResponse::Ok().json(v),
+        Err(e) => {
+            let msg = format!("{} error {:?}", context, e);
+            ::log::error!("{}", msg);
+            HttpResponse::build(http::StatusCode::INTERNAL_SERVER_ERROR).body(msg)
+        }
+    }
+}
+
+/// List all the tags with their usage counts.
+fn list(state: Tenant) -> HttpResponse {
+    reply("tags", state.sql.connection().and_then(|c| tags::fetch_all(&c)))
+}
+
+/// List the tags of one action.
+fn action_tags((state, path): (Tenant, Path<u64>)) -> HttpResponse {
+    let id = *path as i32;
+    reply(
+        "action tags",
+        state
+            .sql
+            .connection()
+            .and_then(|c| tags::for_actions(&c, &[id]))
+            .map(|mut found| found.remove(&id).unwrap_or_default()),
+    )
+}
+
+/// Tag an action.
+fn tag_action(
+    (state, path, input): (Tenant, Path<u64>, Json<net::TagRequest>),
+) -> Result<String> {
+    tags::add_to_action(&state.sql.connection()?, *path, &input.tag)?;
+    Ok("tagged".into())
+}
+
+/// Remove a tag from an action.
+fn untag_action((state, path): (Tenant, Path<(u64, String)>)) -> HttpResponse {
+    match state
+        .sql
+        .connection()
+        .and_then(|c| tags::remove_from_action(&c, path.0, &path.1))
+    {
+        Ok(true) => HttpResponse::Ok().finish(),
+        Ok(false) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            let msg = format!("untag action error {:?}", e);
+            ::log::error!("{}", msg);
+            HttpResponse::build(http::StatusCode::INTERNAL_SERVER_ERROR).body(msg)
+        }
+    }
+}
+
+/// List the tags of the page with the given url.
+fn page_tags((state, query): (Tenant, Query<net::PageQuery>)) -> HttpResponse {
+    reply(
+        "page tags",
+        state.sql.connection().and_then(|c| tags::for_page(&c, &query.url)),
+    )
+}
+
+/// Tag a page, the page does not need to be indexed.
+fn tag_page((state, input): (Tenant, Json<net::PageTagRequest>)) -> Result<String> {
+    tags::add_to_page(&state.sql.connection()?, &input.url, &input.tag)?;
+    Ok("tagged".into())
+}
+
+/// Remove a tag from a page.
+fn untag_page((state, query): (Tenant, Query<net::PageTagRequest>)) -> HttpResponse {
+    match state
+        .sql
+        .connection()
+        .and_then(|c| tags::remove_from_page(&c, &query.url, &query.tag))
+    {
+        Ok(true) => HttpResponse::Ok().finish(),
+        Ok(false) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            let msg = format!("untag page error {:?}", e);
+            ::log::error!("{}", msg);
+            HttpResponse::build(http::StatusCode::INTERNAL_SERVER_ERROR).body(msg)
+        }
+    }
+}
+
+/// Add our routes to the Actix server configuration.
+pub(crate) fn config(app: App<ApiState>) -> App<ApiState> {
+    let app = app.resource(net::TAGS, |r| r.method(http::Method::GET).with(list));
+    let app = app.resource(&format!("{}/pages", net::TAGS), |r| {
+        r.method(http::Method::GET).with(page_tags);
+        r.method(http::Method::POST).with(tag_page);
+        r.method(http::Method::DELETE).with(untag_page);
+    });
+    let app = app.resource(
+        &format!("{}/{{id}}{}", net::ACTIONS2_BASE, net::TAGS),
+        |r| {
+            r.method(http::Method::GET).with(action_tags);
+            r.method(http::Method::POST).with(tag_action);
+        },
+    );
+    app.resource(
+        &format!("{}/{{id}}{}/{{tag}}", net::ACTIONS2_BASE, net::TAGS),
+        |r| r.method(http::Method::DELETE).with(untag_action),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestServer;
+    use actix_web::*;
+    use crate::app_state::tests::StateWithActions;
+    use serde_json as json;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_tag_action() {
+        let actions = StateWithActions(Arc::new(vec!["make deploy".to_string()]));
+        let mut srv = TestServer::build_with_state(move || actions.state()).start(|app| {
+            app.resource(net::TAGS, |r| r.method(http::Method::GET).with(list));
+            app.resource("/v2/actions/{id}/tags", |r| {
+                r.method(http::Method::GET).with(action_tags);
+                r.method(http::Method::POST).with(tag_action);
+            });
+            app.resource("/v2/actions/{id}/tags/{tag}", |r| {
+                r.method(http::Method::DELETE).with(untag_action);
+            });
+        });
+
+        let request = srv
+            .post()
+            .uri(srv.url("/v2/actions/1/tags"))
+            .json(net::TagRequest {
+                tag: "Deploy".into(),
+            })
+            .expect("request");
+        let response = srv.execute(request.send()).expect("execute send");
+        assert!(response.status().is_success());
+
+        let request = srv
+            .get()
+            .uri(srv.url("/v2/actions/1/tags"))
+            .finish()
+            .expect("request");
+        let response = srv.execute(request.send()).expect("execute send");
+        let bytes = srv.execute(response.body()).expect("execute body");
+        let found: Vec<String> = json::from_slice(&bytes[..]).expect("json decode");
+        assert_eq!(found, vec!["deploy"]);
+
+        let request = srv.get().uri(srv.url(net::TAGS)).finish().expect("request");
+        let response = srv.execute(request.send()).expect("execute send");
+        let bytes = srv.execute(response.body()).expect("execute body");
+        let all: Vec<net::TagCount> = json::from_slice(&bytes[..]).expect("json decode");
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].actions, 1);
+
+        let untag = |srv: &mut TestServer| {
+            let request = srv
+                .client(http::Method::DELETE, "/v2/actions/1/tags/deploy")
+                .finish()
+                .expect("request");
+            srv.execute(request.send()).expect("execute send")
+        };
+        assert!(untag(&mut srv).status().is_success());
+        assert_eq!(untag(&mut srv).status(), http::StatusCode::NOT_FOUND);
+    }
+}
@@ -1,8 +1,10 @@
 /// Log access to a given url.
-use actix_web::{http, App, Json, State};
+use actix_web::{http, App, HttpResponse, Json, Path};
 use crate::app_state::ApiState;
-use lib_db::actions2;
+use crate::tenants::Tenant;
+use lib_db::sessions;
 use lib_error::*;
+use lib_goo::config::net;
 use lib_goo::entities::NewAction;
 
 #[derive(::serde::Serialize, ::serde::Deserialize, Default)]
@@ -10,17 +12,74 @@ use lib_goo::entities::NewAction;
 pub struct BrowserAction {
     pub url: String,
     pub transition_type: String,
+    /// Browser tab showing the page.
+    #[serde(default)]
+    pub tab_id: Option<i32>,
+    /// Page which linked to this one, or the opener of a new tab.
+    #[serde(default)]
+    pub referrer: Option<String>,
 }
 
-fn create((state, b_action): (State<ApiState>, Json<BrowserAction>)) -> Result<String> {
+/// A page gaining or losing the focus in the browser.
+#[derive(::serde::Serialize, ::serde::Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusEvent {
+    pub url: String,
+    pub tab_id: i32,
+    /// Either focus or blur.
+    pub event: String,
+}
+
+fn create((state, b_action): (Tenant, Json<BrowserAction>)) -> Result<String> {
     let connection = state.sql.connection()?;
-    let action = NewAction::build_from_url(&b_action.url, b_action.transition_type.as_str(), None)?;
-    let code = actions2::insert(&connection, &action)?;
-    Ok(format!("{}", code))
+    let action = NewAction::build_from_url(&b_action.url, None)?;
+    let id = sessions::insert_visit(
+        &connection,
+        &action,
+        Some(&b_action.transition_type),
+        b_action.tab_id,
+        b_action.referrer.as_ref().map(|r| r.as_str()),
+    )
+    .map_err(|e| {
+        state.metrics.insert_failed();
+        e
+    })?;
+    state.publish_created(&connection, id);
+    Ok(format!("{}", id))
+}
+
+fn focus((state, event): (Tenant, Json<FocusEvent>)) -> Result<String> {
+    let connection = state.sql.connection()?;
+    let action_id = sessions::record_focus(&connection, &event.url, event.tab_id, &event.event)?;
+    Ok(action_id.map(|id| format!("{}", id)).unwrap_or_default())
+}
+
+/// The trail of pages leading to an action, with the time spent on each of them.
+fn trail((state, path): (Tenant, Path<u64>)) -> HttpResponse {
+    match state
+        .sql
+        .connection()
+        .and_then(|c| sessions::trail(&c, *path))
+    {
+        Ok(ref steps) if steps.is_empty() => HttpResponse::NotFound().finish(),
+        Ok(steps) => HttpResponse::Ok().json(steps),
+        Err(e) => {
+            let msg = format!("trail error {:?}", e);
+            ::log::error!("{}", msg);
+            HttpResponse::build(http::StatusCode::INTERNAL_SERVER_ERROR).body(msg)
+        }
+    }
 }
 
 pub(crate) fn config(app: App<ApiState>) -> App<ApiState> {
-    app.resource("/url", |r| {
+    let app = app.resource("/url", |r| {
         r.method(http::Method::POST).with(create);
-    })
+    });
+    let app = app.resource("/url/focus", |r| {
+        r.method(http::Method::POST).with(focus);
+    });
+    app.resource(
+        &format!("{}/{{id}}{}", net::ACTIONS2_BASE, net::TRAIL),
+        |r| r.method(http::Method::GET).with(trail),
+    )
 }
@@ -1,6 +1,7 @@
 #![allow(clippy::needless_pass_by_value)]
-use actix_web::{http, App, HttpResponse, Json, State};
+use actix_web::{http, App, HttpResponse, Json};
 use crate::app_state::ApiState;
+use crate::tenants::Tenant;
 use lib_db::store_policies;
 use lib_db::url_restrictions;
 use lib_error::*;
@@ -23,7 +24,7 @@ fn build_urls(all: &[store_policies::DocumentMatcher]) -> Vec<String> {
 
 /// Fetch the URL policies from the database.
 /// Do not return the do_not_index entries since this may be a privacy issue.
-fn fetch(state: State<ApiState>) -> HttpResponse {
+fn fetch(state: Tenant) -> HttpResponse {
     match state
         .sql
         .connection()
@@ -51,24 +52,56 @@ fn fetch(state: State<ApiState>) -> HttpResponse {
 pub struct UrlRestriction {
     pub url: String,
     pub kind: String,
+    /// One of glob, regex, domain or prefix, a regex when not given.
+    #[serde(default)]
+    pub pattern_kind: Option<String>,
 }
 
-/// Process a new URL policy from the client:
-///  - save in the db
-///  - remove any entry from the text search index.
-fn create((state, input): (State<ApiState>, Json<UrlRestriction>)) -> Result<String> {
-    let connection = state.sql.connection()?;
+// Check the input, the message of the error is returned to the client.
+fn parse(input: &UrlRestriction) -> Result<url_restrictions::UrlRestriction> {
     let policy = input.kind.parse::<url_restrictions::StorePolicy>()?;
+    let pattern_kind = input
+        .pattern_kind
+        .as_ref()
+        .map(|k| k.as_str())
+        .unwrap_or(url_restrictions::REGEX)
+        .parse::<url_restrictions::PatternKind>()?;
+    let restriction =
+        url_restrictions::UrlRestriction::with_pattern(&policy, &pattern_kind, &input.url);
+    restriction.validate()?;
+    Ok(restriction)
+}
+
+fn _create(
+    state: &Tenant,
+    input: &UrlRestriction,
+    restriction: url_restrictions::UrlRestriction,
+) -> Result<()> {
+    let connection = state.sql.connection()?;
     ::log::debug!("marked private {}", input.url);
-    url_restrictions::insert(
-        &connection,
-        url_restrictions::UrlRestriction::with_url(&policy, &input.url),
-    )?;
+    url_restrictions::insert(&connection, restriction)?;
 
     let indexer = &*(state.indexer);
     indexer.delete(&input.url)?;
+    Ok(())
+}
 
-    Ok("created".into())
+/// Process a new URL policy from the client:
+///  - reject invalid patterns
+///  - save in the db
+///  - remove any entry from the text search index.
+fn create((state, input): (Tenant, Json<UrlRestriction>)) -> HttpResponse {
+    let restriction = match parse(&input) {
+        Ok(r) => r,
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+    match _create(&state, &input, restriction) {
+        Ok(()) => HttpResponse::Ok().body("created"),
+        Err(e) => {
+            ::log::error!("store_policies error {:?}", e);
+            HttpResponse::build(http::StatusCode::INTERNAL_SERVER_ERROR).finish()
+        }
+    }
 }
 
 /// Add our routes to the Actix server configuration.
@@ -0,0 +1,91 @@
+//! Liveness and readiness checks of the server and of its stores.
+use crate::app_state::ApiState;
+use lib_db::{actions2, has_pending_migrations};
+use lib_error::*;
+use lib_goo::config::net::{HealthReport, StoreStatus};
+use lib_goo::entities::{NewAction, PageContent};
+use lib_index::repo::Collection;
+
+fn status(name: &str, required: bool, outcome: Result<()>) -> StoreStatus {
+    let message = outcome.err().map(|e| format!("{}", e));
+    StoreStatus {
+        name: name.into(),
+        ok: message.is_none(),
+        required,
+        message,
+    }
+}
+
+fn report(stores: Vec<StoreStatus>) -> HealthReport {
+    HealthReport {
+        ok: stores.iter().all(|s| s.ok || !s.required),
+        stores,
+    }
+}
+
+/// The server is alive as long as it can answer, the stores are not checked.
+pub(crate) fn liveness() -> HealthReport {
+    report(vec![status("server", true, Ok(()))])
+}
+
+fn check_sqlite(state: &ApiState) -> Result<()> {
+    let connection = state.sql.connection()?;
+    actions2::count(&connection)?;
+    Ok(())
+}
+
+fn check_migrations(state: &ApiState) -> Result<()> {
+    if has_pending_migrations(&state.sql.connection()?)? {
+        return Err("pending migrations, run `weaver-data setup`".into());
+    }
+    Ok(())
+}
+
+fn check_repo(state: &ApiState) -> Result<()> {
+    for name in &[NewAction::collection_name(), PageContent::collection_name()] {
+        state
+            .repo
+            .verify(&Collection((*name).into()))
+            .map_err(|e| WeaverError::from(format!("{} collection: {}", name, e)))?;
+    }
+    Ok(())
+}
+
+fn check_topics(state: &ApiState) -> Result<()> {
+    match *state.topic_store {
+        Some(_) => Ok(()),
+        None => Err("no topics, they are computed by weaver-recommend".into()),
+    }
+}
+
+/// Check all the stores used by the server. The topics are optional.
+pub(crate) fn readiness(state: &ApiState) -> HealthReport {
+    report(vec![
+        status("sqlite", true, check_sqlite(state)),
+        status("migrations", true, check_migrations(state)),
+        status("index", true, state.indexer.stats().map(|_| ())),
+        status("repo", true, check_repo(state)),
+        status("topics", false, check_topics(state)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_state::tests::{default_test, StateWithActions};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_readiness() {
+        let ready = readiness(&StateWithActions(Arc::new(Vec::new())).state());
+        assert!(ready.ok, "{:?}", ready);
+        let topics = ready.stores.iter().find(|s| s.name == "topics").expect("topics");
+        assert!(!topics.ok);
+
+        // The default test state cannot open sqlite.
+        let failing = readiness(&default_test());
+        assert!(!failing.ok);
+        let sqlite = failing.stores.iter().find(|s| s.name == "sqlite").expect("sqlite");
+        assert!(sqlite.message.is_some());
+    }
+}
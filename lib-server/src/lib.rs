@@ -6,7 +6,15 @@ pub use crate::server::Server;
 mod analyses;
 mod asset_map;
 mod app_state;
+mod auth;
+mod events;
 mod handlers;
+mod health;
+mod metrics;
 mod pages;
+mod readability;
+mod replication;
+mod retention;
 mod server;
 mod template_engine;
+mod tenants;
@@ -0,0 +1,252 @@
+//! Request and store metrics, exposed in the Prometheus text format on `/metrics`.
+use actix_web::middleware::{Finished, Middleware, Started};
+use actix_web::{HttpRequest, HttpResponse, Result as AwResult};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Upper bounds of the latency buckets, in seconds.
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+// Latency histogram of one route.
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; 11],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (bound, bucket) in BUCKETS.iter().zip(self.buckets.iter_mut()) {
+            if seconds <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+// Labels identifying a route: app, method and route pattern.
+type Route = (String, String, String);
+
+/// Process wide metrics, shared by all the apps.
+#[derive(Default)]
+pub(crate) struct Metrics {
+    requests: Mutex<BTreeMap<(Route, u16), u64>>,
+    latencies: Mutex<BTreeMap<Route, Histogram>>,
+    insert_failures: AtomicUsize,
+    index_failures: AtomicUsize,
+}
+
+/// Escape a label value as required by the text format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl Metrics {
+    /// Record one request.
+    pub fn observe(&self, route: Route, status: u16, seconds: f64) {
+        if let Ok(mut latencies) = self.latencies.lock() {
+            latencies
+                .entry(route.clone())
+                .or_insert_with(Histogram::default)
+                .observe(seconds);
+        }
+        if let Ok(mut requests) = self.requests.lock() {
+            *requests.entry((route, status)).or_insert(0) += 1;
+        }
+    }
+
+    /// Count an action which could not be saved.
+    pub fn insert_failed(&self) {
+        self.insert_failures.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Count a page which could not be indexed.
+    pub fn index_failed(&self) {
+        self.index_failures.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Write the request metrics and the failure counters.
+    pub fn render(&self, out: &mut String) {
+        out.push_str("# HELP weaver_http_requests_total Number of http requests.\n");
+        out.push_str("# TYPE weaver_http_requests_total counter\n");
+        if let Ok(requests) = self.requests.lock() {
+            for (((app, method, route), status), count) in requests.iter() {
+                writeln!(
+                    out,
+                    "weaver_http_requests_total{{app=\"{}\",method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                    app,
+                    method,
+                    escape(route),
+                    status,
+                    count
+                )
+                .expect("write to string");
+            }
+        }
+
+        out.push_str("# HELP weaver_http_request_duration_seconds Latency of the http requests.\n");
+        out.push_str("# TYPE weaver_http_request_duration_seconds histogram\n");
+        if let Ok(latencies) = self.latencies.lock() {
+            for ((app, method, route), histogram) in latencies.iter() {
+                let labels = format!(
+                    "app=\"{}\",method=\"{}\",route=\"{}\"",
+                    app,
+                    method,
+                    escape(route)
+                );
+                for (bound, count) in BUCKETS.iter().zip(histogram.buckets.iter()) {
+                    writeln!(
+                        out,
+                        "weaver_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                        labels, bound, count
+                    )
+                    .expect("write to string");
+                }
+                writeln!(
+                    out,
+                    "weaver_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                    labels, histogram.count
+                )
+                .expect("write to string");
+                writeln!(
+                    out,
+                    "weaver_http_request_duration_seconds_sum{{{}}} {}",
+                    labels, histogram.sum
+                )
+                .expect("write to string");
+                writeln!(
+                    out,
+                    "weaver_http_request_duration_seconds_count{{{}}} {}",
+                    labels, histogram.count
+                )
+                .expect("write to string");
+            }
+        }
+
+        counter(
+            out,
+            "weaver_insert_failures_total",
+            "Number of actions which could not be saved.",
+            self.insert_failures.load(Ordering::SeqCst),
+        );
+        counter(
+            out,
+            "weaver_index_failures_total",
+            "Number of pages which could not be indexed.",
+            self.index_failures.load(Ordering::SeqCst),
+        );
+    }
+}
+
+// Write a metric without labels.
+fn single(out: &mut String, kind: &str, name: &str, help: &str, value: u64) {
+    writeln!(out, "# HELP {} {}", name, help).expect("write to string");
+    writeln!(out, "# TYPE {} {}", name, kind).expect("write to string");
+    writeln!(out, "{} {}", name, value).expect("write to string");
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: usize) {
+    single(out, "counter", name, help, value as u64);
+}
+
+/// Write a gauge without labels.
+pub(crate) fn gauge(out: &mut String, name: &str, help: &str, value: u64) {
+    single(out, "gauge", name, help, value);
+}
+
+/// Write a gauge with one label, the values are (label value, gauge value).
+pub(crate) fn labeled_gauge(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    label: &str,
+    values: &[(String, u64)],
+) {
+    writeln!(out, "# HELP {} {}", name, help).expect("write to string");
+    writeln!(out, "# TYPE {} gauge", name).expect("write to string");
+    for (key, value) in values {
+        writeln!(out, "{}{{{}=\"{}\"}} {}", name, label, escape(key), value)
+            .expect("write to string");
+    }
+}
+
+// Saved in the request extensions when the request starts.
+struct RequestStart(Instant);
+
+/// Measure the requests of one app, the route is the matched resource pattern.
+pub(crate) struct RequestMetrics {
+    app: &'static str,
+    metrics: Arc<Metrics>,
+}
+
+impl RequestMetrics {
+    pub fn new(app: &'static str, metrics: Arc<Metrics>) -> Self {
+        Self { app, metrics }
+    }
+}
+
+impl<S> Middleware<S> for RequestMetrics {
+    fn start(&self, req: &HttpRequest<S>) -> AwResult<Started> {
+        req.extensions_mut().insert(RequestStart(Instant::now()));
+        Ok(Started::Done)
+    }
+
+    fn finish(&self, req: &HttpRequest<S>, resp: &HttpResponse) -> Finished {
+        if let Some(start) = req.extensions().get::<RequestStart>() {
+            let elapsed = start.0.elapsed();
+            let seconds = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+            // Use the pattern, not the path, to keep the number of series bounded.
+            let route = req
+                .resource()
+                .rdef()
+                .map(|r| r.pattern().to_owned())
+                .unwrap_or_else(|| "unmatched".into());
+            self.metrics.observe(
+                (self.app.to_owned(), req.method().to_string(), route),
+                resp.status().as_u16(),
+                seconds,
+            );
+        }
+        Finished::Done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        let route = || ("api".to_string(), "GET".to_string(), "/v2/actions".to_string());
+        metrics.observe(route(), 200, 0.02);
+        metrics.observe(route(), 200, 3.0);
+        metrics.observe(route(), 500, 0.001);
+        metrics.insert_failed();
+
+        let mut out = String::new();
+        metrics.render(&mut out);
+        assert!(out.contains(
+            "weaver_http_requests_total{app=\"api\",method=\"GET\",route=\"/v2/actions\",status=\"200\"} 2"
+        ));
+        assert!(out.contains(
+            "weaver_http_request_duration_seconds_bucket{app=\"api\",method=\"GET\",route=\"/v2/actions\",le=\"0.025\"} 2"
+        ));
+        assert!(out.contains(
+            "weaver_http_request_duration_seconds_count{app=\"api\",method=\"GET\",route=\"/v2/actions\"} 3"
+        ));
+        assert!(out.contains("weaver_insert_failures_total 1\n"));
+        assert!(out.contains("weaver_index_failures_total 0\n"));
+    }
+}
@@ -0,0 +1,87 @@
+#![allow(clippy::needless_pass_by_value)]
+use super::PageState;
+use crate::template_engine::build_context;
+use actix_web::{App, Error, HttpResponse, Query, State};
+use lib_db::stats;
+use lib_goo::config::net::{NamedCount, StatsQuery};
+
+// Window used when the days are not passed in.
+const DEFAULT_DAYS: u32 = 30;
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+// One bar in the activity charts, as used by the template.
+#[derive(::serde::Serialize)]
+struct Bar {
+    label: String,
+    count: usize,
+    percent: usize,
+}
+
+// One list of top entries, as used by the template.
+#[derive(::serde::Serialize)]
+struct Top<'a> {
+    title: &'a str,
+    entries: &'a [NamedCount],
+}
+
+// Scale the counts against the largest one.
+fn bars(counts: &[usize], label: impl Fn(usize) -> String) -> Vec<Bar> {
+    let max = counts.iter().cloned().max().unwrap_or(0).max(1);
+    counts
+        .iter()
+        .enumerate()
+        .map(|(i, count)| Bar {
+            label: label(i),
+            count: *count,
+            percent: count * 100 / max,
+        })
+        .collect()
+}
+
+/// Render the statistics over the last days, 0 days means all the history.
+fn handle((state, query): (State<PageState>, Query<StatsQuery>)) -> Result<HttpResponse, Error> {
+    let days = match query.days.unwrap_or(DEFAULT_DAYS) {
+        0 => None,
+        d => Some(d),
+    };
+    let limit = query.limit.unwrap_or(stats::DEFAULT_LIMIT);
+    let connection = state.api.sql.connection()?;
+    let stats = stats::compute(&connection, days, limit)?;
+
+    let mut ctx = build_context(&state.analyses);
+    ctx.insert("days", &days.unwrap_or(0));
+    ctx.insert("hours", &bars(&stats.hours, |h| format!("{:02}", h)));
+    ctx.insert(
+        "weekdays",
+        &bars(&stats.weekdays, |d| WEEKDAYS[d].to_owned()),
+    );
+    ctx.insert("total", &stats.total);
+    ctx.insert(
+        "tops",
+        &vec![
+            Top {
+                title: "Commands",
+                entries: &stats.commands,
+            },
+            Top {
+                title: "Domains",
+                entries: &stats.domains,
+            },
+            Top {
+                title: "Directories",
+                entries: &stats.directories,
+            },
+            Top {
+                title: "Epics",
+                entries: &stats.epics,
+            },
+        ],
+    );
+    let rendered = state.template.render("dashboard.html", &ctx)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
+}
+
+pub(crate) fn config(app: App<PageState>) -> App<PageState> {
+    app.resource("/dashboard", |r| r.with(handle))
+}
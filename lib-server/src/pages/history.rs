@@ -8,21 +8,27 @@ use lib_goo::entities::ActionId;
 use std::collections::HashMap;
 use std::time::Instant;
 
-/// Render the history page.
+/// Render the history page, the optional tag parameter restricts it to the tagged actions.
 fn handle(
-    (state, _query): (State<PageState>, Query<HashMap<String, String>>),
+    (state, query): (State<PageState>, Query<HashMap<String, String>>),
 ) -> Result<HttpResponse, Error> {
     let template = &state.template;
     let mut ctx = build_context(&state.analyses);
     ctx.insert("term", &" ".to_owned());
+    let tag = query.get("tag").map(|t| t.trim()).filter(|t| !t.is_empty());
+    ctx.insert("tag", &tag);
 
     let connection = state.api.sql.connection()?;
     let count = actions2::count(&connection)? as i64;
-    let pagination = Pagination {
-        start: Some(count - 200),
-        length: Some(200),
+    let pagination = if tag.is_some() {
+        Pagination::default()
+    } else {
+        Pagination {
+            start: Some(count - 200),
+            length: Some(200),
+        }
     };
-    let mut fetched = actions2::fetch(&connection, None, &pagination)?;
+    let mut fetched = actions2::fetch(&connection, None, tag, &pagination)?;
     fetched.reverse();
     let results = PaginatedActions {
         entries: fetched,
@@ -67,7 +73,7 @@ fn hud((state, query): (State<PageState>, Query<HudQuery>)) -> Result<HttpRespon
         }
     };
     let fetch_start = Instant::now();
-    let mut fetched = actions2::fetch(&connection, term, &pagination)?;
+    let mut fetched = actions2::fetch(&connection, term, None, &pagination)?;
     let duration = fetch_start.elapsed();
     ::log::info!(
         "fetched {} actions for term {:?} in {}.{}",
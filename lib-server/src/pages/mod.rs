@@ -8,7 +8,9 @@ use std::sync::Arc;
 use crate::template_engine::TemplateEngine;
 
 mod canned;
+mod dashboard;
 mod history;
+mod opensearch;
 mod search_form;
 pub mod static_assets;
 mod system;
@@ -34,5 +36,13 @@ pub(crate) fn config(app: App<PageState>) -> App<PageState> {
     let app = canned::config(app, should_log);
     let app = search_form::config(app);
     let app = system::config(app);
+    let app = dashboard::config(app);
+    let app = opensearch::config(app);
     history::config(app)
 }
+
+/// Configure the pages of the multi-tenant mode, the other pages have no authentication
+/// and would show the stores of the server.
+pub(crate) fn config_tenants(app: App<PageState>) -> App<PageState> {
+    system::config(app)
+}
@@ -0,0 +1,197 @@
+//! OpenSearch description and suggestions, to search weaver from the address bar.
+//!
+//! The suggestions use the OpenSearch JSON format: the typed text, the completions,
+//! their descriptions and their urls.
+#![allow(clippy::needless_pass_by_value)]
+use super::PageState;
+use actix_web::{http, App, Error, HttpRequest, HttpResponse, Query, State};
+use lib_db::suggestions;
+use std::collections::HashMap;
+use url::Url;
+
+/// Query parameter holding the typed text.
+const TEXT_PARAM: &str = "q";
+
+// The scheme, host and port the browser used to reach the server, then the base url the
+// pages are served under.
+fn origin(req: &HttpRequest<PageState>) -> String {
+    let info = req.connection_info();
+    let base_url = &req.state().api.settings.server.base_url;
+    format!(
+        "{}://{}{}",
+        info.scheme(),
+        info.host(),
+        base_url.trim_end_matches('/')
+    )
+}
+
+/// The description document, the urls point to the server as seen by the browser.
+fn descriptor(
+    (req, state): (HttpRequest<PageState>, State<PageState>),
+) -> Result<HttpResponse, Error> {
+    let mut ctx = tera::Context::new();
+    ctx.insert("origin", &origin(&req));
+    let rendered = state.template.render("opensearch.xml", &ctx)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/opensearchdescription+xml")
+        .body(rendered))
+}
+
+// The search page of the server for the term, where the past searches lead.
+fn search_url(origin: &str, term: &str) -> String {
+    let encoded = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("term", term)
+        .finish();
+    format!("{}/?{}", origin, encoded)
+}
+
+// The hosts and ports of the search page: the configured address and server, and the ones
+// the browser used for this request, which may go through a proxy.
+fn search_origins(req: &HttpRequest<PageState>) -> (Vec<String>, Vec<u16>) {
+    let settings = &req.state().api.settings;
+    let mut hosts = vec![settings.server.address.clone()];
+    let mut ports = vec![settings.server.http_port, settings.server.https_port];
+    let info = req.connection_info();
+    let mut addresses = vec![format!("{}://{}", info.scheme(), info.host())];
+    if let Some(ref server) = settings.client.server {
+        addresses.push(format!("https://{}", server));
+    }
+    for address in addresses {
+        if let Ok(url) = Url::parse(&address) {
+            hosts.extend(url.host_str().map(String::from));
+            ports.extend(url.port_or_known_default());
+        }
+    }
+    (hosts, ports)
+}
+
+/// The suggestions for the typed text, an empty list when there is none.
+fn suggest(
+    (req, state, query): (
+        HttpRequest<PageState>,
+        State<PageState>,
+        Query<HashMap<String, String>>,
+    ),
+) -> Result<HttpResponse, Error> {
+    let text = query.get(TEXT_PARAM).cloned().unwrap_or_default();
+    let (hosts, ports) = search_origins(&req);
+    let found = suggestions::suggest(
+        &state.api.sql.connection()?,
+        &text,
+        &hosts,
+        &ports,
+        &state.api.settings.server.base_url,
+        suggestions::DEFAULT_LIMIT,
+    )?;
+
+    let origin = origin(&req);
+    let texts = found
+        .iter()
+        .map(|s| s.text.clone())
+        .collect::<Vec<String>>();
+    let descriptions = found
+        .iter()
+        .map(|s| s.description.clone())
+        .collect::<Vec<String>>();
+    let urls = found
+        .iter()
+        .map(|s| {
+            s.url
+                .clone()
+                .unwrap_or_else(|| search_url(&origin, &s.text))
+        })
+        .collect::<Vec<String>>();
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-suggestions+json")
+        .json((text, texts, descriptions, urls)))
+}
+
+pub(crate) fn config(app: App<PageState>) -> App<PageState> {
+    let app = app.resource("/opensearch.xml", |r| {
+        r.method(http::Method::GET).with(descriptor);
+    });
+    app.resource("/suggest", |r| {
+        r.method(http::Method::GET).with(suggest);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestServer;
+    use crate::app_state::tests::default_test;
+    use crate::asset_map::AssetMap;
+    use crate::template_engine::TemplateEngine;
+    use lib_db::actions2;
+    use lib_db::test_helpers::SqlStoreInMemory;
+    use lib_goo::config::Settings;
+    use lib_goo::entities::NewAction;
+    use serde_json as json;
+    use std::sync::Arc;
+
+    fn state(base_url: &str) -> PageState {
+        let mut settings = Settings::default();
+        settings.server.base_url = String::from(base_url);
+        let mut s = default_test();
+        s.settings = Arc::new(settings);
+        PageState {
+            api: s,
+            analyses: None,
+            assets: Arc::new(AssetMap::default()),
+            template: Arc::new(TemplateEngine::build().unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_descriptor_base_url() {
+        let mut srv = TestServer::build_with_state(|| state("/wr/")).start(|app| {
+            app.resource("/opensearch.xml", |r| r.with(descriptor));
+        });
+
+        let request = srv
+            .get()
+            .uri(srv.url("/opensearch.xml"))
+            .finish()
+            .expect("request");
+        let response = srv.execute(request.send()).expect("execute send");
+
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).expect("execute body");
+        let data = String::from_utf8(bytes.to_vec()).expect("bytes");
+        assert!(data.contains("/wr/assets/favicon.ico"));
+        assert!(data.contains("/wr/?term={searchTerms}"));
+        assert!(data.contains("/wr/suggest?q={searchTerms}"));
+        assert!(data.contains("/wr/opensearch.xml"));
+    }
+
+    #[test]
+    fn test_suggest_configured_server() {
+        let mut srv = TestServer::build_with_state(|| {
+            let mut s = state("/wr/");
+            let mut settings = (*s.api.settings).clone();
+            settings.client.server = Some(String::from("weaver.example.com"));
+            s.api.settings = Arc::new(settings);
+            s.api.sql = Arc::new(SqlStoreInMemory::build(|connection| {
+                let url = "https://weaver.example.com/wr/?term=rust+traits";
+                actions2::insert(connection, &NewAction::build_from_url(url, None)?)?;
+                Ok(())
+            }));
+            s
+        })
+        .start(|app| {
+            app.resource("/suggest", |r| r.with(suggest));
+        });
+
+        let request = srv
+            .get()
+            .uri(srv.url("/suggest?q=ru"))
+            .finish()
+            .expect("request");
+        let response = srv.execute(request.send()).expect("execute send");
+        assert!(response.status().is_success());
+        let bytes = srv.execute(response.body()).expect("execute body");
+        let (_, texts, _, _): (String, Vec<String>, Vec<String>, Vec<String>) =
+            json::from_slice(&bytes[..]).expect("json decode");
+        assert_eq!(texts, vec![String::from("rust traits")]);
+    }
+}
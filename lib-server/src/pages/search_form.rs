@@ -1,6 +1,6 @@
 use super::PageState;
 use actix_web::{App, Error, HttpResponse, Query, State};
-use lib_db::{actions2, store_policies};
+use lib_db::{actions2, store_policies, tags};
 use lib_goo::date;
 use lib_goo::entities::lda;
 use lib_index::Results;
@@ -13,6 +13,7 @@ struct Data<'a> {
     title: &'a str,
     url: &'a str,
     last_access: String,
+    starred: bool,
     topic_ids: Vec<&'a lda::RelTopic>,
 }
 
@@ -69,15 +70,25 @@ fn _handle(
     let topic_store = &*state.api.topic_store;
     let mut ctx = build_context(&state.analyses);
     let rendered = if let Some(term) = query.get("term") {
-        let indexer = &*state.api.indexer;
-
-        // Fetch results from indexer
-        let mut results = indexer.search(term).unwrap_or_else(|_| Results::default());
+        // Fetch results from indexer, the starred pages come first.
+        let mut results = state
+            .api
+            .search_boosted(term)
+            .unwrap_or_else(|_| Results::default());
 
         // Process the hidden output and topics
         let connection = state.api.sql.connection()?;
         let restrictions = store_policies::Restrictions::fetch(&connection)?;
 
+        // Keep only the pages with the requested tag.
+        let tag = query.get("tag").map(|t| t.trim()).filter(|t| !t.is_empty());
+        if let Some(tag) = tag {
+            let urls = tags::page_urls(&connection, tag)?;
+            results.matches.retain(|m| tags::contains_url(&urls, &m.url));
+            results.total = results.matches.len() as u64;
+        }
+        ctx.insert("tag", &tag);
+
         let hidden_title = String::from("********");
         let mut datum = Datum {
             total: results.total,
@@ -115,6 +126,7 @@ fn _handle(
                 title,
                 url: &result.url,
                 last_access,
+                starred: tags::contains_url(&starred, &result.url),
                 topic_ids,
             };
             datum.matches.push(data);
@@ -164,6 +176,7 @@ mod tests {
                 url: "url foo".into(),
                 title: "title bar".into(),
                 body: "body baz".into(),
+                ..PageContent::default()
             })
             .expect("adding test PageContent");
         s.sql = Arc::new(SqlStoreInMemory::build(|_| Ok(())));
@@ -1,38 +1,21 @@
 #![allow(clippy::needless_pass_by_value)]
 use actix_web::http::StatusCode;
-use actix_web::{error, http, App, HttpRequest, HttpResponse, Responder, State};
-use crate::asset_map::AssetMap;
+use actix_web::{error, http, App, HttpResponse, Path, Responder, State};
+use crate::asset_map::{self, AssetMap};
 use std::sync::Arc;
 
-const FAVICON: &[u8] = include_bytes!("../../assets/favicon.ico");
-const SVGS: &[u8] = include_bytes!("../../assets/inline.svg");
-
-/// favicon handler
-fn favicon(_: &HttpRequest<Arc<AssetMap>>) -> impl Responder {
-    HttpResponse::build(StatusCode::OK)
-        .content_type("image/x-icon")
-        .body(FAVICON)
-}
-
-fn svgs(_: &HttpRequest<Arc<AssetMap>>) -> impl Responder {
-    HttpResponse::build(StatusCode::OK)
-        .content_type("image/svg+xml")
-        .body(SVGS)
-}
-
-fn css(state: State<Arc<AssetMap>>) -> impl Responder {
-    match state.asset("weaver.css") {
-        Ok(css) => Ok(HttpResponse::build(StatusCode::OK)
-            .content_type("text/css")
-            .body(css)),
-        Err(_) => Err(error::ErrorNotFound("missing css file")),
+/// Serve the asset, the user folder may replace the embedded ones.
+fn asset((state, name): (State<Arc<AssetMap>>, Path<String>)) -> impl Responder {
+    match state.asset(&name) {
+        Ok(content) => Ok(HttpResponse::build(StatusCode::OK)
+            .content_type(asset_map::content_type(&name))
+            .body(content)),
+        Err(_) => Err(error::ErrorNotFound("missing asset")),
     }
 }
 
 pub(crate) fn config(app: App<Arc<AssetMap>>) -> App<Arc<AssetMap>> {
-    let app = app.resource("/favicon.ico", |r| r.f(favicon));
-    let app = app.resource("/inline.svg", |r| r.f(svgs));
-    app.resource("weaver.css", |r| r.method(http::Method::GET).with(css))
+    app.resource("/{name:.+}", |r| r.method(http::Method::GET).with(asset))
 }
 
 #[cfg(test)]
@@ -44,12 +27,12 @@ mod tests {
     #[test]
     fn test_svg() {
         let mut srv = TestServer::build_with_state(|| Arc::new(AssetMap::build())).start(|app| {
-            app.resource("/index.html", |r| r.f(svgs));
+            app.resource("/{name:.+}", |r| r.method(http::Method::GET).with(asset));
         });
 
         let request = srv
             .get()
-            .uri(srv.url("/index.html"))
+            .uri(srv.url("/inline.svg"))
             .finish()
             .expect("request");
         let response = srv.execute(request.send()).expect("execute send");
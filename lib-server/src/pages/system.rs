@@ -1,7 +1,13 @@
 #![allow(clippy::needless_pass_by_value)]
 use super::PageState;
+use crate::health;
+use crate::metrics;
 /// APIs to manage the overall system.
-use actix_web::{http, App, Error, State};
+use actix_web::{http, App, Error, HttpResponse, State};
+use lib_db::stats;
+use lib_goo::config::net::{self, HealthReport};
+use lib_goo::entities::{NewAction, PageContent};
+use lib_index::repo::Collection;
 
 
 fn reload(state: State<PageState>) -> Result<String, Error> {
@@ -11,7 +17,86 @@ fn reload(state: State<PageState>) -> Result<String, Error> {
     Ok(one)
 }
 
+/// Render the metrics in the Prometheus text format, outside of the /api app so that
+/// the scraper does not need a token.
+fn render_metrics(state: State<PageState>) -> Result<HttpResponse, Error> {
+    let api = &state.api;
+    let mut out = String::new();
+    api.metrics.render(&mut out);
+
+    let index = api.indexer.stats()?;
+    metrics::gauge(
+        &mut out,
+        "weaver_index_documents",
+        "Number of documents in the text index.",
+        index.documents,
+    );
+    metrics::gauge(
+        &mut out,
+        "weaver_index_segments",
+        "Number of segments in the text index.",
+        index.segments as u64,
+    );
+
+    let mut entries = Vec::new();
+    for name in &[NewAction::collection_name(), PageContent::collection_name()] {
+        let count = api.repo.list(&Collection((*name).into()))?.len();
+        entries.push(((*name).to_owned(), count as u64));
+    }
+    metrics::labeled_gauge(
+        &mut out,
+        "weaver_repo_entries",
+        "Number of documents in the repo.",
+        "collection",
+        &entries,
+    );
+
+    let rows = stats::row_counts(&api.sql.connection()?)?
+        .into_iter()
+        .map(|(table, count)| (table.to_owned(), count as u64))
+        .collect::<Vec<_>>();
+    metrics::labeled_gauge(
+        &mut out,
+        "weaver_sqlite_rows",
+        "Number of rows in the sqlite tables.",
+        "table",
+        &rows,
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(out))
+}
+
+// Reply 200 when healthy, 503 otherwise, always with the details in json.
+fn health_response(report: HealthReport) -> HttpResponse {
+    if report.ok {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+/// Liveness, the server answers.
+fn health(_state: State<PageState>) -> HttpResponse {
+    health_response(health::liveness())
+}
+
+/// Readiness, all the required stores can be used.
+fn ready(state: State<PageState>) -> HttpResponse {
+    health_response(health::readiness(&state.api))
+}
+
 pub(crate) fn config(app: App<PageState>) -> App<PageState> {
+    let app = app.resource("/metrics", |r| {
+        r.method(http::Method::GET).with(render_metrics);
+    });
+    let app = app.resource(net::HEALTH, |r| {
+        r.method(http::Method::GET).with(health);
+    });
+    let app = app.resource(net::READY, |r| {
+        r.method(http::Method::GET).with(ready);
+    });
     app.resource("/reload", |r| {
         r.method(http::Method::GET).with(reload);
     })
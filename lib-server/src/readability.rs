@@ -0,0 +1,393 @@
+//! Main content extraction from the html sent by the extension, in the spirit of readability.
+//!
+//! The paragraphs score their parent and grandparent, the element with the best score and
+//! the fewest links is the main content. Scripts, navigation, cookie banners and the like
+//! are skipped. Short pages fall back to the whole body.
+use scraper::{ElementRef, Html, Node, Selector};
+use std::collections::HashMap;
+use url::Url;
+
+/// Elements never part of the content.
+const IGNORED_TAGS: &[&str] = &[
+    "aside", "button", "canvas", "dialog", "footer", "form", "header", "iframe", "nav", "noscript",
+    "script", "select", "style", "svg", "template",
+];
+
+/// Parts of the class names and ids of the navigation and banners.
+const BOILERPLATE_NAMES: &[&str] = &[
+    "ad",
+    "ads",
+    "advert",
+    "banner",
+    "breadcrumb",
+    "breadcrumbs",
+    "comment",
+    "comments",
+    "consent",
+    "cookie",
+    "cookies",
+    "footer",
+    "gdpr",
+    "masthead",
+    "menu",
+    "modal",
+    "nav",
+    "navbar",
+    "navigation",
+    "newsletter",
+    "popup",
+    "promo",
+    "related",
+    "share",
+    "sidebar",
+    "social",
+    "subscribe",
+];
+
+/// Aria roles of the navigation and banners.
+const BOILERPLATE_ROLES: &[&str] = &[
+    "alertdialog",
+    "banner",
+    "complementary",
+    "contentinfo",
+    "dialog",
+    "navigation",
+];
+
+/// Elements separating lines of text.
+const BLOCK_TAGS: &[&str] = &[
+    "article",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dt",
+    "figcaption",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "li",
+    "main",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "td",
+    "th",
+    "tr",
+    "ul",
+];
+
+/// Paragraphs shorter than this do not count towards the score of their parents.
+const MIN_PARAGRAPH_CHARS: usize = 25;
+/// Below this size the main content is most likely wrong, the body is used instead.
+const MIN_CONTENT_CHARS: usize = 250;
+const EXCERPT_WORDS: usize = 40;
+const MAX_LINKS: usize = 200;
+
+/// What is extracted from a page.
+#[derive(Debug, Default, PartialEq)]
+pub struct Extracted {
+    pub title: Option<String>,
+    /// Main text, one line per paragraph.
+    pub text: String,
+    pub language: Option<String>,
+    pub word_count: usize,
+    pub excerpt: Option<String>,
+    pub links: Vec<String>,
+}
+
+fn selector(s: &str) -> Selector {
+    Selector::parse(s).expect("static selector")
+}
+
+fn is_boilerplate_name(name: &str) -> bool {
+    name.to_lowercase()
+        .split(|c| c == '-' || c == '_')
+        .any(|part| BOILERPLATE_NAMES.contains(&part))
+}
+
+fn is_ignored(element: &ElementRef) -> bool {
+    let value = element.value();
+    let name = value.name();
+    if IGNORED_TAGS.contains(&name) || value.attr("hidden").is_some() {
+        return true;
+    }
+    if value.attr("aria-hidden") == Some("true") {
+        return true;
+    }
+    if let Some(role) = value.attr("role") {
+        if BOILERPLATE_ROLES.contains(&role) {
+            return true;
+        }
+    }
+    // The main elements are trusted whatever their classes.
+    if ["html", "body", "main", "article"].contains(&name) {
+        return false;
+    }
+    value.id().map(is_boilerplate_name).unwrap_or(false) || value.classes().any(is_boilerplate_name)
+}
+
+fn inside_ignored(element: &ElementRef) -> bool {
+    element
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .any(|a| is_ignored(&a))
+}
+
+fn collect_text(element: ElementRef, out: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => out.push_str(text),
+            Node::Element(_) => {
+                if let Some(child) = ElementRef::wrap(child) {
+                    if is_ignored(&child) {
+                        continue;
+                    }
+                    let block = BLOCK_TAGS.contains(&child.value().name());
+                    if block {
+                        out.push('\n');
+                    }
+                    collect_text(child, out);
+                    if block {
+                        out.push('\n');
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// The visible text of the element, with the whitespace collapsed and one line per block.
+fn text_of(element: ElementRef) -> String {
+    let mut raw = String::new();
+    collect_text(element, &mut raw);
+    raw.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn char_count(text: &str) -> usize {
+    text.chars().filter(|c| !c.is_whitespace()).count()
+}
+
+// Share of the text of the element which is in links.
+fn link_density(element: ElementRef, text_chars: usize) -> f64 {
+    if text_chars == 0 {
+        return 1.0;
+    }
+    let link_chars: usize = element
+        .select(&selector("a"))
+        .map(|a| char_count(&text_of(a)))
+        .sum();
+    link_chars as f64 / text_chars as f64
+}
+
+fn tag_bonus(name: &str) -> f64 {
+    match name {
+        "article" | "main" => 10.0,
+        "div" => 5.0,
+        "blockquote" | "pre" | "td" => 3.0,
+        _ => 0.0,
+    }
+}
+
+// The element holding the main content, none when no paragraph was found.
+fn main_content(document: &Html) -> Option<ElementRef> {
+    let mut scores = HashMap::new();
+    for paragraph in document.select(&selector("p, pre, td, blockquote")) {
+        if inside_ignored(&paragraph) {
+            continue;
+        }
+        let text = text_of(paragraph);
+        let chars = char_count(&text);
+        if chars < MIN_PARAGRAPH_CHARS {
+            continue;
+        }
+        let score = 1.0 + text.matches(',').count() as f64 + (chars / 100).min(3) as f64;
+        let parents = paragraph.ancestors().filter_map(ElementRef::wrap).take(2);
+        for (level, parent) in parents.enumerate() {
+            let entry = scores
+                .entry(parent.id())
+                .or_insert_with(|| (parent, tag_bonus(parent.value().name())));
+            entry.1 += if level == 0 { score } else { score / 2.0 };
+        }
+    }
+    scores
+        .values()
+        .map(|(element, score)| {
+            let chars = char_count(&text_of(*element));
+            (*element, score * (1.0 - link_density(*element, chars)))
+        })
+        .fold(
+            None,
+            |best: Option<(ElementRef, f64)>, candidate| match best {
+                Some(b) if b.1 >= candidate.1 => Some(b),
+                _ => Some(candidate),
+            },
+        )
+        .map(|(element, _)| element)
+}
+
+fn meta_content(document: &Html, query: &str) -> Option<String> {
+    document
+        .select(&selector(query))
+        .filter_map(|m| m.value().attr("content"))
+        .map(|c| c.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .find(|c| !c.is_empty())
+}
+
+// Absolute http(s) links of the content, without fragments and duplicates.
+fn links_of(content: ElementRef, page_url: &str) -> Vec<String> {
+    let base = match Url::parse(page_url) {
+        Ok(base) => base,
+        Err(_) => return Vec::new(),
+    };
+    let mut out: Vec<String> = Vec::new();
+    for anchor in content.select(&selector("a[href]")) {
+        if out.len() >= MAX_LINKS {
+            break;
+        }
+        if inside_ignored(&anchor) {
+            continue;
+        }
+        let href = anchor.value().attr("href").unwrap_or_default();
+        let mut link = match base.join(href.trim()) {
+            Ok(link) => link,
+            Err(_) => continue,
+        };
+        if link.scheme() != "http" && link.scheme() != "https" {
+            continue;
+        }
+        link.set_fragment(None);
+        let link = link.into_string();
+        if link != page_url && !out.contains(&link) {
+            out.push(link);
+        }
+    }
+    out
+}
+
+/// The language, the number of words and the first words of a text.
+pub fn describe(text: &str) -> (Option<String>, usize, Option<String>) {
+    let language = whatlang::detect(text)
+        .filter(|info| info.is_reliable())
+        .map(|info| info.lang().code().to_string());
+    let words = text.split_whitespace().collect::<Vec<&str>>();
+    let excerpt = if words.is_empty() {
+        None
+    } else if words.len() > EXCERPT_WORDS {
+        Some(format!("{}...", words[..EXCERPT_WORDS].join(" ")))
+    } else {
+        Some(words.join(" "))
+    };
+    (language, words.len(), excerpt)
+}
+
+/// Extract the main content of the page and its details.
+pub fn extract(page_url: &str, html: &str) -> Extracted {
+    let document = Html::parse_document(html);
+    let body = document
+        .select(&selector("body"))
+        .next()
+        .unwrap_or_else(|| document.root_element());
+
+    let content = main_content(&document)
+        .filter(|c| char_count(&text_of(*c)) >= MIN_CONTENT_CHARS)
+        .unwrap_or(body);
+    let text = text_of(content);
+    let (language, word_count, first_words) = describe(&text);
+    let title = document
+        .select(&selector("title"))
+        .map(text_of)
+        .find(|t| !t.is_empty())
+        .or_else(|| meta_content(&document, "meta[property=\"og:title\"]"));
+    let excerpt = meta_content(&document, "meta[name=\"description\"]")
+        .or_else(|| meta_content(&document, "meta[property=\"og:description\"]"))
+        .or(first_words);
+    Extracted {
+        title,
+        links: links_of(content, page_url),
+        text,
+        language,
+        word_count,
+        excerpt,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARTICLE: &str = r#"
+        <html><head><title>Ownership</title><script>var tracking = 1;</script></head>
+        <body>
+          <nav><a href="/">Home</a> <a href="/blog">Blog</a></nav>
+          <div class="cookie-banner">We use cookies, accept them all.</div>
+          <div id="content">
+            <article>
+              <h1>Understanding ownership</h1>
+              <p>Ownership is the most unique feature of Rust, and it enables memory safety
+                 guarantees without needing a garbage collector.</p>
+              <p>Each value has a variable that is called its owner, there can only be one
+                 owner at a time, and the value is dropped when the owner goes out of scope.</p>
+              <p>Read the <a href="https://doc.rust-lang.org/book/#ownership">book</a> or the
+                 <a href="/reference">reference</a> for all the details about borrowing.</p>
+            </article>
+            <div class="sidebar"><a href="/ads">Buy now</a></div>
+          </div>
+          <footer>Copyright, all rights reserved, no part may be reproduced.</footer>
+        </body></html>"#;
+
+    #[test]
+    fn test_extract_article() {
+        let extracted = extract("https://example.com/blog/ownership", ARTICLE);
+        assert_eq!(extracted.title, Some("Ownership".into()));
+        assert!(extracted.text.starts_with("Understanding ownership\n"));
+        assert!(extracted.text.contains("garbage collector"));
+        for noise in &["cookies", "Home", "Buy now", "Copyright", "tracking"] {
+            assert!(
+                !extracted.text.contains(noise),
+                "{} in {}",
+                noise,
+                extracted.text
+            );
+        }
+        assert_eq!(extracted.language, Some("eng".into()));
+        assert_eq!(
+            extracted.word_count,
+            extracted.text.split_whitespace().count()
+        );
+        assert!(extracted
+            .excerpt
+            .as_ref()
+            .map(|e| e.starts_with("Understanding ownership Ownership is"))
+            .unwrap_or(false));
+        assert_eq!(
+            extracted.links,
+            vec![
+                "https://doc.rust-lang.org/book/".to_string(),
+                "https://example.com/reference".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_short_page_uses_body() {
+        let html = r#"<html><head><meta name="description" content="A  short page">
+            </head><body><span>Hello</span> <a href="mailto:me@example.com">mail</a></body></html>"#;
+        let extracted = extract("https://example.com/", html);
+        assert_eq!(extracted.title, None);
+        assert_eq!(extracted.text, "Hello mail");
+        assert_eq!(extracted.word_count, 2);
+        assert_eq!(extracted.excerpt, Some("A short page".into()));
+        assert!(extracted.links.is_empty());
+    }
+}
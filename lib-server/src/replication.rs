@@ -0,0 +1,110 @@
+//! Background job pushing the unsent actions, and optionally their page contents, to the peer.
+use lib_db::{replication, SqlProvider, SqlStore};
+use lib_error::*;
+use lib_goo::config::net::{SyncBatch, SyncReport};
+use lib_goo::config::{Destination, SyncSettings};
+use lib_goo::entities::PageContent;
+use lib_index::repo::{decode_page, Collection, EncryptedRepo, Repo};
+use lib_rpc::client;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Number of actions in one batch.
+const ACTIONS_BATCH: usize = 200;
+
+/// Number of page contents in one batch, they are much larger than the actions.
+const PAGES_BATCH: usize = 20;
+
+// The page contents saved under the repo ids, the missing documents are skipped.
+fn page_contents(repo: &Repo, repo_ids: &[(i32, String)]) -> Vec<PageContent> {
+    let pages = Collection(PageContent::collection_name().into());
+    let mut out = Vec::with_capacity(repo_ids.len());
+    for (page_id, repo_id) in repo_ids {
+        match repo.read(&pages, repo_id).and_then(|d| decode_page(&d)) {
+            Ok(page_content) => out.push(page_content),
+            Err(e) => ::log::warn!("skipping the content of page {}: {:?}", page_id, e),
+        }
+    }
+    out
+}
+
+/// Push the whole backlog to the peer, the actions and pages are marked as sent once the peer
+/// saved them.
+pub(crate) fn push(
+    settings: &SyncSettings,
+    destination: &Destination,
+    store: &SqlProvider,
+    repo: &Repo,
+) -> Result<SyncReport> {
+    let connection = store.connection()?;
+    let token = settings.token.as_ref().map(|t| t.as_str());
+    let mut total = SyncReport::default();
+
+    while settings.pages {
+        let pending = replication::unsent_pages(&connection, PAGES_BATCH)?;
+        if pending.is_empty() {
+            break;
+        }
+        let batch = SyncBatch {
+            actions: Vec::new(),
+            pages: page_contents(repo, &pending),
+        };
+        total.pages += client::push_sync(destination, token, &batch)?.pages;
+        let ids: Vec<i32> = pending.into_iter().map(|(id, _)| id).collect();
+        replication::mark_pages_sent(&connection, &ids)?;
+    }
+
+    loop {
+        let pending = replication::unsent(&connection, ACTIONS_BATCH)?;
+        if pending.is_empty() {
+            break;
+        }
+        let (ids, actions): (Vec<i32>, _) = pending.into_iter().unzip();
+        let report = client::push_sync(
+            destination,
+            token,
+            &SyncBatch {
+                actions,
+                pages: Vec::new(),
+            },
+        )?;
+        replication::mark_sent(&connection, &ids)?;
+        total.inserted += report.inserted;
+        total.duplicates += report.duplicates;
+        total.restricted += report.restricted;
+    }
+    Ok(total)
+}
+
+/// Start a thread pushing to the peer periodically, nothing is started without a peer.
+///
+/// The returned lock is held while pushing, take it before exiting to let the job finish.
+pub(crate) fn start(
+    settings: SyncSettings,
+    destination: Option<Destination>,
+    store: Arc<SqlStore>,
+    repo: Arc<EncryptedRepo>,
+) -> Arc<Mutex<()>> {
+    let running = Arc::new(Mutex::new(()));
+    let destination = match destination {
+        Some(d) => d,
+        None => {
+            ::log::debug!("no sync peer");
+            return running;
+        }
+    };
+    ::log::info!("starting the sync job to {:?}", settings.peer);
+    let job = running.clone();
+    thread::spawn(move || loop {
+        {
+            let _running = job.lock();
+            match push(&settings, &destination, &*store, &*repo) {
+                Ok(report) => ::log::info!("sync job {:?}", report),
+                Err(e) => ::log::error!("sync job error {:?}", e),
+            }
+        }
+        thread::sleep(Duration::from_secs(settings.interval_secs));
+    });
+    running
+}
@@ -0,0 +1,44 @@
+//! Background job enforcing the retention rules.
+use lib_db::{SqlProvider, SqlStore};
+use lib_goo::config::Retention;
+use lib_index::prune;
+use lib_index::repo::EncryptedRepo;
+use lib_index::TantivyIndexer;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How often to run the retention job, in seconds.
+const INTERVAL_SECS: u64 = 3600;
+
+/// Start a thread pruning the stores periodically, nothing is started when there are no rules.
+///
+/// The returned lock is held while pruning, take it before exiting to let the job finish.
+pub(crate) fn start(
+    retention: Retention,
+    store: Arc<SqlStore>,
+    repo: Arc<EncryptedRepo>,
+    indexer: Arc<TantivyIndexer>,
+) -> Arc<Mutex<()>> {
+    let running = Arc::new(Mutex::new(()));
+    if retention.is_empty() {
+        ::log::debug!("no retention rules");
+        return running;
+    }
+    ::log::info!("starting the retention job {:?}", retention);
+    let job = running.clone();
+    thread::spawn(move || loop {
+        {
+            let _running = job.lock();
+            match store
+                .connection()
+                .and_then(|c| prune::prune(&c, &*repo, &*indexer, &retention, false))
+            {
+                Ok(report) => ::log::info!("retention job {:?}", report),
+                Err(e) => ::log::error!("retention job error {:?}", e),
+            }
+        }
+        thread::sleep(Duration::from_secs(INTERVAL_SECS));
+    });
+    running
+}
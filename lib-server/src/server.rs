@@ -1,64 +1,64 @@
 use crate::analyses::load_analyses;
 use crate::app_state::ApiState;
 use crate::asset_map::AssetMap;
+use crate::auth::TokenAuth;
+use crate::events::Broadcaster;
 use crate::handlers;
+use crate::metrics::{Metrics, RequestMetrics};
 use crate::pages;
+use crate::replication;
+use crate::retention;
 use crate::template_engine::TemplateEngine;
+use crate::tenants::Tenants;
 use actix_web::middleware::Logger;
 use actix_web::{server, App};
-use lib_db::{topics, SqlStore};
+use lib_db::{api_tokens, topics, SqlProvider, SqlStore};
 use lib_error::*;
+use lib_goo::config::db::PasswordSource;
+use lib_goo::config::Settings;
+#[cfg(any(feature = "tls", feature = "rust-tls"))]
+use lib_goo::config::TlsSettings;
 use lib_index::repo::EncryptedRepo;
 use lib_index::TantivyIndexer;
 use std::sync::Arc;
 
 #[cfg(feature = "tls")]
-fn config_tls() -> Result<server::NativeTlsAcceptor> {
-    use lib_goo::config::file_utils;
+fn config_tls(tls: &TlsSettings) -> Result<server::NativeTlsAcceptor> {
     use native_tls::{Identity, TlsAcceptor};
     use std::fs::File;
     use std::io::Read;
 
-    let mut cert_path = file_utils::app_folder()?;
-    cert_path.push("server");
-    cert_path.push("localhost.pfx");
+    let cert_path = tls.pfx_path()?;
     let mut identity = vec![];
     File::open(&cert_path)?
         .read_to_end(&mut identity)
         .map_err(|e| format!("cert open {:?}", e))?;
-    let identity = Identity::from_pkcs12(&identity, "1234").context("ssl".into())?;
+    let identity = Identity::from_pkcs12(&identity, tls.pfx_password()).context("ssl".into())?;
     let acceptor = TlsAcceptor::new(identity).context("acceptor")?;
 
     Ok(server::NativeTlsAcceptor::new(acceptor))
 }
 
 #[cfg(feature = "rust-tls")]
-fn config_tls() -> Result<rustls::ServerConfig> {
-    use lib_goo::config::file_utils;
+fn config_tls(tls: &TlsSettings) -> Result<rustls::ServerConfig> {
     use rustls::internal::pemfile::{certs, pkcs8_private_keys};
     use rustls::{NoClientAuth, ServerConfig};
     use std::fs::File;
     use std::io::BufReader;
     use std::io::Read;
 
-    let mut cert_path = file_utils::app_folder()?;
-    cert_path.push("server");
-
     let cert_chain = {
-        cert_path.push("localhost.crt");
         let mut cert = vec![];
-        File::open(&cert_path)?
+        File::open(&tls.cert_path()?)?
             .read_to_end(&mut cert)
             .context("cert open".into())?;
-        cert_path.pop();
         let mut buf_cert = BufReader::new(&cert[..]);
         certs(&mut buf_cert).map_err(|e| WeaverError::from(format!("cert decode {:?}", e)))?
     };
 
     let mut keys = {
-        cert_path.push("localhost.key");
         let mut key = vec![];
-        File::open(&cert_path)?
+        File::open(&tls.key_path()?)?
             .read_to_end(&mut key)
             .context("key open".into())?;
         let mut buf_key = BufReader::new(&key[..]);
@@ -77,6 +77,9 @@ fn config_tls() -> Result<rustls::ServerConfig> {
     Ok(config)
 }
 
+/// Seconds given to the pending requests to complete when stopping.
+const SHUTDOWN_TIMEOUT: u16 = 30;
+
 /// Placeholder struct for further expansion.
 pub struct Server {}
 
@@ -88,44 +91,95 @@ impl Server {
         base_url: String,
         store: Arc<SqlStore>,
         repo: Arc<EncryptedRepo>,
+        password_source: &PasswordSource,
     ) -> Result<Server> {
-        let indexer = Arc::new(TantivyIndexer::build()?);
-        let template = Arc::new(TemplateEngine::build()?);
+        let mut settings = Settings::load()?;
+        // The pages build their urls from the settings, with the base url of the apps.
+        settings.server.base_url = base_url.clone();
+        let indexer = Arc::new(TantivyIndexer::build(settings.index.clone())?);
+        let template = Arc::new(TemplateEngine::with_overrides(
+            settings.ui.templates_path()?,
+        )?);
         let topic_store = Arc::new(topics::TopicStore::load()?);
-        let asset_map = Arc::new(AssetMap::build());
+        let asset_map = Arc::new(AssetMap::with_overrides(settings.ui.assets_path()?)?);
+        let events = Arc::new(Broadcaster::default());
+        let metrics = Arc::new(Metrics::default());
+        let tenant_mode = settings.tenants.enabled;
+        if api_tokens::count(&store.connection()?)? == 0 {
+            if tenant_mode {
+                ::log::warn!("no api tokens, run `weaver-data token create <tenant>` for each tenant");
+            } else if settings.server.open_api {
+                ::log::warn!("no api tokens, the api is open, run `weaver-data token create <name>`");
+            } else {
+                ::log::warn!("no api tokens, the api is closed, run `weaver-data token create <name>`");
+            }
+        }
+        let retention_job = retention::start(
+            settings.retention.clone(),
+            store.clone(),
+            repo.clone(),
+            indexer.clone(),
+        );
+        let sync_job = replication::start(
+            settings.sync.clone(),
+            settings.sync.destination(&settings.tls)?,
+            store.clone(),
+            repo.clone(),
+        );
+        let app_settings = Arc::new(settings.clone());
+        let tenants = if tenant_mode {
+            Some(Arc::new(Tenants::build(
+                app_settings.clone(),
+                metrics.clone(),
+                password_source,
+                store.has_cipher()?,
+            )?))
+        } else {
+            None
+        };
+        let app_tenants = tenants.clone();
         let apps_factory = move || {
             let assets_url = format!("{}/assets", base_url);
+            let api_state = ApiState {
+                events: events.clone(),
+                metrics: metrics.clone(),
+                sql: store.clone(),
+                indexer: indexer.clone(),
+                repo: repo.clone(),
+                settings: app_settings.clone(),
+                topic_store: topic_store.clone(),
+                tenants: app_tenants.clone(),
+            };
+            let pages_config = if tenant_mode {
+                pages::config_tenants
+            } else {
+                pages::config
+            };
             vec![
                 App::with_state(asset_map.clone())
                     .prefix(assets_url)
                     .middleware(Logger::new("%t %P \"%r\" %s %b %T"))
+                    .middleware(RequestMetrics::new("assets", metrics.clone()))
                     .configure(pages::static_assets::config)
                     .boxed(),
-                App::with_state(ApiState {
-                    sql: store.clone(),
-                    indexer: indexer.clone(),
-                    repo: repo.clone(),
-                    topic_store: topic_store.clone(),
-                })
-                .prefix(format!("{}/api", base_url))
-                .middleware(Logger::new("%t %P \"%r\" %s %b %T"))
-                .configure(handlers::config)
-                .boxed(),
+                App::with_state(api_state.clone())
+                    .prefix(format!("{}/api", base_url))
+                    .middleware(Logger::new("%t %P \"%r\" %s %b %T"))
+                    .middleware(RequestMetrics::new("api", metrics.clone()))
+                    .middleware(TokenAuth)
+                    .configure(handlers::config)
+                    .boxed(),
                 App::with_state(pages::PageState {
                     template: template.clone(),
                     assets: asset_map.clone(),
                     analyses: load_analyses().ok(),
-                    api: ApiState {
-                        sql: store.clone(),
-                        indexer: indexer.clone(),
-                        repo: repo.clone(),
-                        topic_store: topic_store.clone(),
-                    },
+                    api: api_state,
                 })
                 .prefix(base_url.clone())
                 .middleware(Logger::new("%t %P \"%r\" %s %b %T"))
+                .middleware(RequestMetrics::new("pages", metrics.clone()))
                 // Add the html pages
-                .configure(pages::config)
+                .configure(pages_config)
                 .boxed(),
             ]
         };
@@ -134,19 +188,28 @@ impl Server {
         s = s.bind(format!("{}:{}", address, http_port))?;
         #[cfg(any(feature = "tls", feature = "rust-tls"))]
         {
-            match config_tls() {
+            match config_tls(&settings.tls) {
                 Ok(config) => {
                     ::log::info!("Initializing TLS on port {}", https_port);
                     let acceptor = server::RustlsAcceptor::with_flags(
                         config,
                         server::ServerFlags::HTTP1 | server::ServerFlags::HTTP2,
                     );
-                    s = s.bind_with(format!("0.0.0.0:{}", https_port), move || acceptor.clone())?;
+                    s = s.bind_with(format!("{}:{}", address, https_port), move || acceptor.clone())?;
                 }
                 Err(e) => ::log::error!("Cannot start TLS {:?}", e),
             }
         }
+        // On SIGTERM or SIGINT actix stops accepting connections and lets the pending
+        // requests complete before returning.
+        s = s.shutdown_timeout(SHUTDOWN_TIMEOUT);
         s.run();
+        ::log::info!("server stopped, waiting for the background jobs");
+        let _done = retention_job.lock();
+        let _synced = sync_job.lock();
+        if let Some(tenants) = tenants {
+            tenants.wait_jobs();
+        }
         Ok(Server {})
     }
 }
@@ -2,75 +2,153 @@ use actix_web::{error, Error};
 use crate::analyses::*;
 use lib_error::{Result as Wesult, WeaverError};
 use lib_goo::config::file_utils;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use tera;
 use walkdir::WalkDir;
 
+/// Templates embedded in the binary, by name.
+const TEMPLATES: &[(&str, &str)] = &[
+    // Define the basic structure of the page.
+    ("base.html", include_str!("../templates/base.html")),
+    // Display reports pre-generated on the disk.
+    ("canned.raw", include_str!("../templates/canned.raw")),
+    // Search across all the documents in the repo.
+    (
+        "search-form.html",
+        include_str!("../templates/search-form.html"),
+    ),
+    // Display the search results.
+    (
+        "search-results.html",
+        include_str!("../templates/search-results.html"),
+    ),
+    // Display a lot of all the actions.
+    ("history.html", include_str!("../templates/history.html")),
+    // Display a brief list of all the actions.
+    ("hud.html", include_str!("../templates/hud.html")),
+    // Display the activity statistics.
+    (
+        "dashboard.html",
+        include_str!("../templates/dashboard.html"),
+    ),
+    // Describe the search engine to the browsers.
+    (
+        "opensearch.xml",
+        include_str!("../templates/opensearch.xml"),
+    ),
+];
+
+/// Templates of the source checkout, used when the server runs from it.
+const DEV_FOLDER: &str = "lib-server/templates";
+
 /// Template engine providing reload functionality and more integrated error
 /// loading on top of the tera engine.
-pub struct TemplateEngine(Mutex<tera::Tera>);
+pub struct TemplateEngine {
+    tera: Mutex<tera::Tera>,
+    /// Folder of the user templates, replacing the embedded ones with the same name.
+    overrides: Option<PathBuf>,
+}
+
+// The templates in the folder, named by their path relative to it with `/` separators, none
+// if the folder does not exist.
+fn read_folder(folder: &Path) -> Wesult<Vec<(String, String)>> {
+    let mut out = Vec::new();
+    if !folder.is_dir() {
+        return Ok(out);
+    }
+    for entry in WalkDir::new(folder) {
+        let entry = entry.map_err(|_| "listing templates")?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry
+            .path()
+            .strip_prefix(folder)
+            .map_err(|_| "template outside of its folder")?;
+        let name = relative
+            .components()
+            .filter_map(|c| c.as_os_str().to_str())
+            .collect::<Vec<&str>>()
+            .join("/");
+        if name.ends_with(".html") || name.ends_with(".raw") || name.ends_with(".xml") {
+            out.push((name, file_utils::read_content(entry.path())?));
+        }
+    }
+    Ok(out)
+}
+
+// Build the engine from the embedded templates replaced by the ones in the folders, in order.
+fn load(folders: &[&Path]) -> Wesult<(tera::Tera, Vec<String>)> {
+    let mut templates: Vec<(String, String)> = TEMPLATES
+        .iter()
+        .map(|(name, content)| ((*name).to_owned(), (*content).to_owned()))
+        .collect();
+    let mut replaced = Vec::new();
+    for folder in folders {
+        for (name, content) in read_folder(folder)? {
+            match templates.iter().position(|(n, _)| *n == name) {
+                Some(pos) => templates[pos].1 = content,
+                None => templates.push((name.clone(), content)),
+            }
+            replaced.push(name);
+        }
+    }
+    // All the templates are added at once, the inheritance is resolved across the folders.
+    let mut tera = tera::Tera::default();
+    tera.add_raw_templates(
+        templates
+            .iter()
+            .map(|(name, content)| (name.as_str(), content.as_str()))
+            .collect(),
+    )
+    .map_err(|e| WeaverError::from(format!("template error {:?}", e)))?;
+    Ok((tera, replaced))
+}
 
 impl TemplateEngine {
-    /// Initialize the Tera template system.
+    /// Initialize the Tera template system with the embedded templates.
     pub fn build() -> Wesult<Self> {
-        let mut tera = tera::Tera::default();
-
-        // Programmatically add all the templates.
-        tera.add_raw_templates(vec![
-            // Define the basic structure of the page.
-            ("base.html", include_str!("../templates/base.html")),
-            // Display reports pre-generated on the disk.
-            ("canned.raw", include_str!("../templates/canned.raw")),
-            // Search across all the documents in the repo.
-            (
-                "search-form.html",
-                include_str!("../templates/search-form.html"),
-            ),
-            // Display the search results.
-            (
-                "search-results.html",
-                include_str!("../templates/search-results.html"),
-            ),
-            // Display a lot of all the actions.
-            ("history.html", include_str!("../templates/history.html")),
-            // Display a brief list of all the actions.
-            ("hud.html", include_str!("../templates/hud.html")),
-        ]).map_err(|e| WeaverError::from(format!("template error {:?}", e)))?;
+        let (tera, _) = load(&[])?;
+        Ok(TemplateEngine {
+            tera: Mutex::new(tera),
+            overrides: None,
+        })
+    }
 
-        Ok(TemplateEngine(Mutex::new(tera)))
+    /// Initialize the Tera template system, the templates in the folder replace the
+    /// embedded ones with the same name.
+    pub fn with_overrides(folder: PathBuf) -> Wesult<Self> {
+        let (tera, replaced) = load(&[&folder])?;
+        if !replaced.is_empty() {
+            ::log::info!("templates from {:?}: {}", folder, replaced.join(" "));
+        }
+        Ok(TemplateEngine {
+            tera: Mutex::new(tera),
+            overrides: Some(folder),
+        })
     }
 
-    /// Reload the template files, only in dev mode
+    /// Reload the templates from the source checkout, when present, then from the user
+    /// folder. Returns the names of the templates read from the folders.
     pub fn reload(&self) -> Wesult<String> {
-        use std::path::Path;
-        let mut processed = Vec::new();
-
-        for entry in WalkDir::new(Path::new("lib-server/templates")) {
-            let entry = entry.map_err(|_| "listing templates")?;
-            let path = entry.path();
-            if let Some(os_name) = path.file_name() {
-                if let Some(name) = os_name.to_str() {
-                    if entry.file_type().is_file()
-                        && (name.ends_with(".html") || name.ends_with(".raw"))
-                    {
-                        let content = file_utils::read_content(&path)?;
-                        self.0
-                            .lock()
-                            .unwrap()
-                            .add_raw_template(name, &content)
-                            .map_err(|_| "adding template")?;
-                        processed.push(name.to_owned());
-                    }
-                }
-            }
+        let dev = Path::new(DEV_FOLDER);
+        let mut folders = vec![dev];
+        if let Some(ref overrides) = self.overrides {
+            folders.push(overrides.as_path());
         }
-        Ok(processed.join(" "))
+        let (tera, replaced) = load(&folders)?;
+        *self
+            .tera
+            .lock()
+            .map_err(|e| WeaverError::from(format!("lock templates {:?}", e)))? = tera;
+        Ok(replaced.join(" "))
     }
 
     // Pass-through the render function to the underlying engine.
     pub fn render(&self, name: &str, ctx: &tera::Context) -> Result<String, Error> {
         let lock = self
-            .0
+            .tera
             .lock()
             .map_err(|_e| error::ErrorInternalServerError("cannot lock the rendering engine"))?;
         lock.render(name, ctx).map_err(|e| {
@@ -92,3 +170,45 @@ pub fn build_context(canned: &Option<Vec<Analysis>>) -> tera::Context {
     }
     ctx
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_overrides() {
+        let folder = env::temp_dir().join(format!("weaver-templates-{}", std::process::id()));
+        fs::create_dir_all(&folder).expect("create folder");
+        fs::write(
+            folder.join("base.html"),
+            "<main>{% block content %}{% endblock content %}</main>",
+        )
+        .expect("write template");
+
+        let engine = TemplateEngine::with_overrides(folder.clone()).expect("engine");
+        let mut ctx = build_context(&None);
+        ctx.insert("origin", "https://localhost:8467");
+        let rendered = engine.render("opensearch.xml", &ctx).expect("render");
+        assert!(rendered.contains("<ShortName>weaver</ShortName>"));
+        // The embedded pages extend the user base template.
+        ctx.insert("term", " ");
+        let rendered = engine.render("search-form.html", &ctx).expect("render");
+        assert!(rendered.starts_with("<main>"));
+
+        // A template in a sub folder keeps its path and does not replace the embedded one.
+        fs::create_dir_all(folder.join("old")).expect("create sub folder");
+        fs::write(folder.join("old").join("base.html"), "old").expect("write template");
+        engine.reload().expect("reload");
+        let rendered = engine.render("search-form.html", &ctx).expect("render");
+        assert!(rendered.starts_with("<main>"));
+        assert_eq!(engine.render("old/base.html", &ctx).expect("render"), "old");
+
+        fs::remove_file(folder.join("base.html")).expect("remove template");
+        engine.reload().expect("reload");
+        let rendered = engine.render("search-form.html", &ctx).expect("render");
+        assert!(rendered.starts_with("<!DOCTYPE html>"));
+        fs::remove_dir_all(&folder).expect("cleanup");
+    }
+}
@@ -0,0 +1,264 @@
+//! Multi-tenant mode, each api token names a tenant with its own stores.
+//!
+//! The stores of a tenant are in the `<name>` sub folder of `tenants.folder`: the sqlite
+//! database, the repo with its own salt and the text index. They are created on the first
+//! request of the tenant and stay open afterwards, the requests of the other tenants are not
+//! blocked while they are opened. The handlers take a `Tenant` instead of the `State` of the
+//! app to use them.
+//!
+//! When the database of the server has encrypted columns, the databases of the tenants have
+//! them as well, with the key of the tenant repo.
+use actix_web::error::ErrorInternalServerError;
+use actix_web::{Error, FromRequest, HttpRequest};
+use crate::app_state::ApiState;
+use crate::events::Broadcaster;
+use crate::metrics::Metrics;
+use crate::retention;
+use lib_db::{crypto, SqlProvider, SqlStore};
+use lib_error::*;
+use lib_goo::config::db::PasswordSource;
+use lib_goo::config::file_utils::DEFAULT_DB_NAME;
+use lib_goo::config::Settings;
+use lib_index::repo::EncryptedRepo;
+use lib_index::TantivyIndexer;
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Maximum length of a tenant name.
+const MAX_NAME_LENGTH: usize = 64;
+
+/// Tenant names are used as folder and key file names, keep them simple.
+pub(crate) fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+// The stores of a tenant, shared by the workers.
+#[derive(Clone)]
+struct Stores {
+    events: Arc<Broadcaster>,
+    indexer: Arc<TantivyIndexer>,
+    repo: Arc<EncryptedRepo>,
+    sql: Arc<SqlStore>,
+}
+
+/// The opened stores, by tenant name.
+pub(crate) struct Tenants {
+    folder: PathBuf,
+    password_source: PasswordSource,
+    encrypted_columns: bool,
+    settings: Arc<Settings>,
+    metrics: Arc<Metrics>,
+    // The stores are None until the tenant is opened, a failed opening is retried.
+    stores: Mutex<HashMap<String, Arc<Mutex<Option<Stores>>>>>,
+    jobs: Mutex<Vec<Arc<Mutex<()>>>>,
+}
+
+impl Tenants {
+    /// The registry of the tenants, `password_source` unlocks the repos when there are no
+    /// tenant key files. `encrypted_columns` tells if the server database is encrypted.
+    /// The tenants are opened by the requests, the password cannot be prompted for.
+    pub fn build(
+        settings: Arc<Settings>,
+        metrics: Arc<Metrics>,
+        password_source: &PasswordSource,
+        encrypted_columns: bool,
+    ) -> Result<Tenants> {
+        if *password_source == PasswordSource::Prompt {
+            return Err("the multi-tenant mode cannot prompt for the password, \
+                        use another password source"
+                .into());
+        }
+        let folder = settings.tenants.folder_path()?;
+        fs::create_dir_all(&folder)?;
+        ::log::info!("multi-tenant mode, stores in {:?}", folder);
+        Ok(Tenants {
+            folder,
+            password_source: password_source.clone(),
+            encrypted_columns,
+            settings,
+            metrics,
+            stores: Mutex::new(HashMap::new()),
+            jobs: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// The state of the tenant, its stores are opened or created on the first call.
+    pub fn state(&self, name: &str) -> Result<ApiState> {
+        if !is_valid_name(name) {
+            return Err(WeaverError::from(format!("invalid tenant name {:?}", name)));
+        }
+        let slot = self
+            .stores
+            .lock()
+            .map_err(|e| WeaverError::from(format!("lock tenants {:?}", e)))?
+            .entry(name.to_owned())
+            .or_insert_with(|| Arc::new(Mutex::new(None)))
+            .clone();
+        // Opening under the lock of the tenant keeps a single index writer per tenant.
+        let mut opened = slot
+            .lock()
+            .map_err(|e| WeaverError::from(format!("lock tenant {} {:?}", name, e)))?;
+        let stores = match *opened {
+            Some(ref stores) => stores.clone(),
+            None => {
+                let stores = self.open(name)?;
+                *opened = Some(stores.clone());
+                stores
+            }
+        };
+        Ok(ApiState {
+            events: stores.events,
+            indexer: stores.indexer,
+            metrics: self.metrics.clone(),
+            repo: stores.repo,
+            settings: self.settings.clone(),
+            sql: stores.sql,
+            topic_store: Arc::new(None),
+            tenants: None,
+        })
+    }
+
+    fn open(&self, name: &str) -> Result<Stores> {
+        let folder = self.folder.join(name);
+        fs::create_dir_all(&folder)?;
+        let source = self
+            .settings
+            .tenants
+            .password_source(name, &self.password_source);
+        let repo = Arc::new(EncryptedRepo::build_in(folder.join("text-repo"), &source)?);
+        let sql = Arc::new(SqlStore::at(folder.join(DEFAULT_DB_NAME))?);
+        if self.encrypted_columns {
+            sql.set_cipher(repo.field_cipher())?;
+            let connection = sql.connection()?;
+            if !crypto::is_enabled(&connection)? {
+                crypto::encrypt_database(&connection)?;
+            }
+        }
+        let indexer = Arc::new(TantivyIndexer::build_in(
+            &folder.join("text-index"),
+            self.settings.index.clone(),
+        )?);
+        let job = retention::start(
+            self.settings.retention.clone(),
+            sql.clone(),
+            repo.clone(),
+            indexer.clone(),
+        );
+        self.jobs
+            .lock()
+            .map_err(|e| WeaverError::from(format!("lock tenant jobs {:?}", e)))?
+            .push(job);
+        ::log::info!("opened the stores of tenant {}", name);
+        Ok(Stores {
+            events: Arc::new(Broadcaster::default()),
+            indexer,
+            repo,
+            sql,
+        })
+    }
+
+    /// Wait for the running retention jobs of the tenants.
+    pub fn wait_jobs(&self) {
+        if let Ok(jobs) = self.jobs.lock() {
+            for job in jobs.iter() {
+                let _done = job.lock();
+            }
+        }
+    }
+}
+
+/// The state of the tenant of the request, resolved by `TokenAuth`. Outside of the
+/// multi-tenant mode this is the state of the app.
+#[derive(Clone)]
+pub(crate) struct Tenant(pub ApiState);
+
+impl Deref for Tenant {
+    type Target = ApiState;
+
+    fn deref(&self) -> &ApiState {
+        &self.0
+    }
+}
+
+impl FromRequest<ApiState> for Tenant {
+    type Config = ();
+    type Result = std::result::Result<Tenant, Error>;
+
+    fn from_request(req: &HttpRequest<ApiState>, _cfg: &Self::Config) -> Self::Result {
+        if let Some(tenant) = req.extensions().get::<Tenant>() {
+            return Ok(tenant.clone());
+        }
+        if req.state().tenants.is_some() {
+            // The middleware rejects the requests without a tenant, this is a bug.
+            ::log::error!("no tenant resolved for {}", req.path());
+            return Err(ErrorInternalServerError("no tenant"));
+        }
+        Ok(Tenant(req.state().clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib_db::pages;
+    use std::env;
+
+    #[test]
+    fn test_valid_names() {
+        assert!(is_valid_name("alice"));
+        assert!(is_valid_name("team_2-bob"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("Alice"));
+        assert!(!is_valid_name("../alice"));
+        assert!(!is_valid_name("-alice"));
+        assert!(!is_valid_name("a b"));
+        assert!(!is_valid_name(&"a".repeat(MAX_NAME_LENGTH + 1)));
+    }
+
+    #[test]
+    fn test_no_prompt() {
+        let mut settings = Settings::default();
+        settings.tenants.enabled = true;
+        assert!(Tenants::build(
+            Arc::new(settings),
+            Arc::new(Metrics::default()),
+            &PasswordSource::Prompt,
+            false,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_encrypted_columns() {
+        let folder = env::temp_dir().join(format!("weaver-tenants-cipher-{}", std::process::id()));
+        let mut settings = Settings::default();
+        settings.tenants.enabled = true;
+        settings.tenants.folder = Some(folder.clone());
+        let tenants = Tenants::build(
+            Arc::new(settings),
+            Arc::new(Metrics::default()),
+            &PasswordSource::PassIn("secret".into()),
+            true,
+        )
+        .expect("tenants");
+
+        let state = tenants.state("alice").expect("tenant state");
+        let connection = state.sql.connection().expect("connection");
+        assert!(crypto::is_enabled(&connection).unwrap());
+        let id = pages::fetch_or_create_id(&connection, "https://example.com/", Some("example"))
+            .expect("page");
+        assert_eq!(
+            pages::fetch_id(&connection, "https://example.com/").unwrap(),
+            Some(id)
+        );
+        fs::remove_dir_all(&folder).expect("cleanup");
+    }
+}
@@ -0,0 +1,33 @@
+-- This file should undo anything in `up.sql`
+-- The encrypted values are left as they are, they cannot be read without the settings.
+DROP TRIGGER IF EXISTS CommandsBlindWords;
+DROP TRIGGER IF EXISTS PagesBlindWords;
+drop table blind_words;
+drop table settings;
+
+-- Sqlite cannot drop the indexed blind index columns, the tables are rebuilt without them.
+DROP INDEX IF EXISTS CommandsBlindIndex;
+DROP INDEX IF EXISTS PagesBlindIndex;
+
+create table commands_plain (
+  id INTEGER PRIMARY key,
+  kind VARCHAR not null,
+  command VARCHAR not null,
+  page_id integer references pages(id)
+);
+insert into commands_plain (id, kind, command, page_id)
+  select id, kind, command, page_id from commands;
+drop table commands;
+alter table commands_plain rename to commands;
+
+create table pages_plain (
+  id INTEGER PRIMARY key,
+  normalized_url VARCHAR not null,
+  title varchar
+);
+insert into pages_plain (id, normalized_url, title)
+  select id, normalized_url, title from pages;
+drop table pages;
+alter table pages_plain rename to pages;
+
+CREATE INDEX IF NOT EXISTS AccessedUrlsNormalizedName ON pages(normalized_url);
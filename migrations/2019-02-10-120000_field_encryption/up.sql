@@ -0,0 +1,31 @@
+-- Your SQL goes here
+alter table commands add column command_bidx varchar;
+alter table pages add column normalized_url_bidx varchar;
+
+CREATE INDEX IF NOT EXISTS CommandsBlindIndex ON commands(command_bidx);
+CREATE INDEX IF NOT EXISTS PagesBlindIndex ON pages(normalized_url_bidx);
+
+create table settings (
+  name VARCHAR PRIMARY key,
+  value VARCHAR not null
+);
+
+-- Keyed hashes of the words of the encrypted commands and titles, used by the searches.
+create table blind_words (
+  kind VARCHAR not null,
+  row_id INTEGER not null,
+  word_bidx VARCHAR not null,
+  PRIMARY KEY (kind, row_id, word_bidx)
+);
+
+CREATE INDEX IF NOT EXISTS BlindWordsIndex ON blind_words(word_bidx);
+
+CREATE TRIGGER IF NOT EXISTS CommandsBlindWords AFTER DELETE ON commands
+BEGIN
+  DELETE FROM blind_words WHERE kind = 'command' AND row_id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS PagesBlindWords AFTER DELETE ON pages
+BEGIN
+  DELETE FROM blind_words WHERE kind = 'title' AND row_id = old.id;
+END;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+create table api_tokens (
+  id INTEGER PRIMARY key,
+  name VARCHAR not null unique,
+  token_hash VARCHAR not null unique,
+  created VARCHAR not null
+);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+drop table page_tags;
+drop table action_tags;
+drop table tags;
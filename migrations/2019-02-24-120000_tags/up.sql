@@ -0,0 +1,20 @@
+-- Your SQL goes here
+create table tags (
+  id INTEGER PRIMARY key,
+  name VARCHAR not null unique
+);
+
+create table action_tags (
+  action_id INTEGER not null,
+  tag_id INTEGER not null,
+  PRIMARY KEY (action_id, tag_id)
+);
+
+create table page_tags (
+  page_id INTEGER not null,
+  tag_id INTEGER not null,
+  PRIMARY KEY (page_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS ActionTagsByTag ON action_tags(tag_id);
+CREATE INDEX IF NOT EXISTS PageTagsByTag ON page_tags(tag_id);
@@ -0,0 +1,13 @@
+-- This file should undo anything in `up.sql`
+-- The transition types go back to the location of the visits.
+insert into locations (location)
+  select distinct transition from navigations
+  where transition is not null and transition not in (select location from locations);
+update actions2 set location_id = (
+    select l.id from navigations n join locations l on l.location = n.transition
+    where n.action_id = actions2.id
+  )
+  where id in (select action_id from navigations where transition is not null);
+
+drop table focus_events;
+drop table navigations;
@@ -0,0 +1,26 @@
+-- Your SQL goes here
+create table navigations (
+  action_id INTEGER PRIMARY KEY not null,
+  tab_id INTEGER,
+  referrer_action_id INTEGER,
+  transition VARCHAR
+);
+
+create table focus_events (
+  id INTEGER PRIMARY key,
+  action_id INTEGER not null,
+  kind VARCHAR not null,
+  happened VARCHAR not null
+);
+
+CREATE INDEX IF NOT EXISTS NavigationsByTab ON navigations(tab_id);
+CREATE INDEX IF NOT EXISTS FocusEventsByAction ON focus_events(action_id);
+
+-- The transition types of the visits were saved as their location.
+insert into navigations (action_id, transition)
+  select a.id, l.location from actions2 a
+  join commands c on a.command_id = c.id
+  join locations l on a.location_id = l.id
+  where c.kind = 'url';
+update actions2 set location_id = null
+  where command_id in (select id from commands where kind = 'url');
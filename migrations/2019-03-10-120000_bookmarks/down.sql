@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+drop table reading_list_pages;
+drop table reading_lists;
+drop table stars;
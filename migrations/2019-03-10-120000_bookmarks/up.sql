@@ -0,0 +1,20 @@
+-- Your SQL goes here
+create table stars (
+  page_id INTEGER PRIMARY KEY not null,
+  created VARCHAR not null
+);
+
+create table reading_lists (
+  id INTEGER PRIMARY key,
+  name VARCHAR not null unique,
+  created VARCHAR not null
+);
+
+create table reading_list_pages (
+  list_id INTEGER not null,
+  page_id INTEGER not null,
+  added VARCHAR not null,
+  PRIMARY KEY (list_id, page_id)
+);
+
+CREATE INDEX IF NOT EXISTS ReadingListPagesByPage ON reading_list_pages(page_id);
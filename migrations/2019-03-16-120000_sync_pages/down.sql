@@ -0,0 +1,22 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS PageCopiesUnsent;
+drop table page_copies;
+
+-- Sqlite cannot drop columns, the table is rebuilt without the parent id.
+create table actions2_plain (
+  id INTEGER PRIMARY key,
+  command_id integer REFERENCES commands(id),
+  executed TEXT not null,
+  location_id integer references locations(id),
+  epic_id integer references epics(id),
+  sent boolean,
+  annotation text,
+  host_id integer references hosts(id),
+  status integer
+);
+insert into actions2_plain (id, command_id, executed, location_id, epic_id, sent, annotation,
+    host_id, status)
+  select id, command_id, executed, location_id, epic_id, sent, annotation, host_id, status
+  from actions2;
+drop table actions2;
+alter table actions2_plain rename to actions2;
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+alter table actions2 add column parent_id varchar;
+
+-- The repo document with the newest content of a page, and if it was pushed to the peer.
+create table page_copies (
+  page_id INTEGER PRIMARY key references pages(id),
+  repo_id VARCHAR not null,
+  sent boolean not null
+);
+
+CREATE INDEX IF NOT EXISTS PageCopiesUnsent ON page_copies(sent);
@@ -0,0 +1,31 @@
+-- This file should undo anything in `up.sql`
+-- The older versions read every pattern as a regex, the other kinds are replaced by the
+-- equivalent regex before the column is dropped. The regex characters are escaped first.
+update url_restrictions set url_expr = replace(replace(replace(replace(replace(replace(replace(
+  replace(replace(replace(replace(replace(replace(url_expr,
+  '\', '\\'), '.', '\.'), '+', '\+'), '?', '\?'), '(', '\('), ')', '\)'), '|', '\|'),
+  '[', '\['), ']', '\]'), '{', '\{'), '}', '\}'), '^', '\^'), '$', '\$')
+where pattern_kind in ('glob', 'prefix');
+
+update url_restrictions set url_expr = '^' || replace(url_expr, '*', '.*') || '$'
+where pattern_kind = 'glob';
+
+update url_restrictions set url_expr = '^' || replace(url_expr, '*', '\*')
+where pattern_kind = 'prefix';
+
+update url_restrictions set url_expr =
+  '^[a-z][a-z0-9+.-]*://([^/?#@]*@)?([^/?#@]*\.)?' || replace(lower(url_expr), '.', '\.') ||
+  '(:[0-9]+)?([/?#]|$)'
+where pattern_kind = 'domain';
+
+create table url_restrictions_regex (
+  id INTEGER PRIMARY key,
+  kind VARCHAR not null,
+  url_expr VARCHAR not null,
+  title_match varchar,
+  body_match varchar
+);
+insert into url_restrictions_regex (id, kind, url_expr, title_match, body_match)
+  select id, kind, url_expr, title_match, body_match from url_restrictions;
+drop table url_restrictions;
+alter table url_restrictions_regex rename to url_restrictions;
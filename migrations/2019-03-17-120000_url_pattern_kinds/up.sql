@@ -0,0 +1,12 @@
+-- Your SQL goes here
+alter table url_restrictions add column pattern_kind varchar not null default 'regex';
+
+-- The patterns with a wildcard and no regex syntax were written as globs.
+update url_restrictions set pattern_kind = 'glob'
+where instr(url_expr, '*') > 0
+  and instr(url_expr, '.*') = 0
+  and instr(url_expr, '\') = 0
+  and instr(url_expr, '(') = 0
+  and instr(url_expr, '[') = 0
+  and instr(url_expr, '^') = 0
+  and instr(url_expr, '$') = 0;
@@ -1,9 +1,11 @@
-use bincode;
+use crate::certificates;
 use crate::cli::{parse, ConfigAndCommand, DataSubCommand};
-use lib_db::{self, pages, setup, topics, SqlProvider, SqlStore};
+use lib_db::{
+    self, api_tokens, browser_import, crypto, pages, replication, setup, topics, SqlProvider,
+    SqlStore,
+};
 use lib_error::*;
-use lib_goo::config::db::PasswordSource;
-use lib_goo::config::file_utils;
+use lib_goo::config::{api_token, file_utils, Settings};
 use lib_goo::entities::PageContent;
 use lib_index::repo::Repo;
 use lib_index::{self, repo, Indexer, TantivyIndexer};
@@ -20,7 +22,11 @@ pub fn run() -> Result<()> {
         command,
     } = parse();
     ::log::debug!("Executing cli command {:?}", command);
-    let password_source = password_source.unwrap_or(PasswordSource::Keyring);
+    let settings = Settings::load()?;
+    let password_source = match password_source {
+        Some(source) => source,
+        None => settings.store.password_source()?,
+    };
 
     // Initialize the crypto environment.
     lib_index::init()?;
@@ -62,6 +68,12 @@ pub fn run() -> Result<()> {
             let store = SqlStore::build()?;
             setup::populate_data(&store.connection()?)?;
             TantivyIndexer::setup_if_needed()?;
+            if certificates::setup_if_needed(&settings)? {
+                println!(
+                    "Created the server certificate, the clients need to trust {}",
+                    settings.tls.ca_path()?.display()
+                );
+            }
             Ok(())
         }
         Decrypt(collection, handle) => {
@@ -88,6 +100,21 @@ pub fn run() -> Result<()> {
             }
             Ok(())
         }
+        EncryptDb => {
+            let repo = repo::EncryptedRepo::build(&password_source)?;
+            let store = SqlStore::build()?;
+            let connection = store.connection()?;
+            if crypto::is_enabled(&connection)? {
+                println!("The database columns are already encrypted.");
+                return Ok(());
+            }
+            let name = SqlStore::backup_database()?;
+            println!("Backup: {}", name.to_str().unwrap());
+            store.set_cipher(repo.field_cipher())?;
+            let count = crypto::encrypt_database(&store.connection()?)?;
+            println!("Encrypted {} rows", count);
+            Ok(())
+        }
         Encrypt(collection, filename) => {
             let repo = repo::EncryptedRepo::build(&password_source)?;
 
@@ -102,7 +129,67 @@ pub fn run() -> Result<()> {
             println!("{}", handle);
             Ok(())
         }
+        Forget(pattern) => {
+            let repo = repo::EncryptedRepo::build(&password_source)?;
+            let store = SqlStore::build()?;
+            install_field_cipher(&store, &repo)?;
+            let indexer = TantivyIndexer::build(settings.index.clone())?;
+
+            let report = lib_index::forget::forget(&store.connection()?, &repo, &indexer, &pattern)?;
+            println!("Deleted {} actions", report.actions);
+            println!("Deleted {} commands", report.commands);
+            println!("Deleted {} pages", report.pages);
+            println!("Deleted {} repo entries", report.repo_entries);
+            println!("Deleted {} index documents", report.index_documents);
+            for url in report.urls {
+                println!("  {}", url);
+            }
+            Ok(())
+        }
+        ImportBrowser(browser, path) => {
+            let history = browser_import::open(&path)?;
+            let mut ignored = 0;
+            let visits = browser_import::read_visits(&history, browser, &mut ignored)?;
+
+            let repo = repo::EncryptedRepo::build(&password_source)?;
+            let store = SqlStore::build()?;
+            install_field_cipher(&store, &repo)?;
+            let connection = store.connection()?;
+            let restrictions = lib_db::store_policies::Restrictions::fetch(&connection)?;
+
+            let report = browser_import::import(&connection, &visits, &restrictions)?;
+            println!("Imported {} visits", report.imported);
+            println!("Skipped {} visits already present", report.existing);
+            println!("Skipped {} visits of urls not logged", report.not_logged);
+            println!("Skipped {} visits of frames or other schemes", report.ignored + ignored);
+            println!("Created or found {} pages", report.pages);
+            Ok(())
+        }
         Noop => Ok(()),
+        Prune(dry_run) => {
+            let retention = settings.retention.clone();
+            if retention.is_empty() {
+                println!("No retention rules, nothing to prune.");
+                return Ok(());
+            }
+            let repo = repo::EncryptedRepo::build(&password_source)?;
+            let store = SqlStore::build()?;
+            install_field_cipher(&store, &repo)?;
+            let indexer = TantivyIndexer::build(settings.index.clone())?;
+
+            let report = lib_index::prune::prune(
+                &store.connection()?,
+                &repo,
+                &indexer,
+                &retention,
+                dry_run,
+            )?;
+            let verb = if dry_run { "Would delete" } else { "Deleted" };
+            println!("{} {} actions", verb, report.actions);
+            println!("{} {} repo entries", verb, report.repo_entries);
+            println!("{} {} index documents", verb, report.index_documents);
+            Ok(())
+        }
         LinkCommandPages => {
             let store = SqlStore::build()?;
             let connection = store.connection()?;
@@ -116,16 +203,16 @@ pub fn run() -> Result<()> {
             lib_index::init()?;
             let repo = repo::EncryptedRepo::build(&password_source)?;
             let store = SqlStore::build()?;
+            install_field_cipher(&store, &repo)?;
             let connection = store.connection()?;
 
             TantivyIndexer::delete_all()?;
             TantivyIndexer::setup_if_needed()?;
-            let indexer = TantivyIndexer::build()?;
+            let indexer = TantivyIndexer::build(settings.index.clone())?;
 
-            for entry in repo.list(&repo::Collection(PageContent::collection_name().into()))? {
+            for entry in repo.entries(&repo::Collection(PageContent::collection_name().into()))? {
                 let decrypted = entry?;
-                let page_content = bincode::deserialize::<PageContent>(decrypted.as_slice())
-                    .map_err(|_| "cannot bindecode")?;
+                let page_content = lib_index::repo::decode_page(decrypted.as_slice())?;
 
                 // add to the indexer
                 let handle = indexer.add(&page_content)?;
@@ -146,9 +233,65 @@ pub fn run() -> Result<()> {
             Ok(())
         }
         Sqlite => execute_sqlite(),
+        SyncStatus => {
+            match settings.sync.peer {
+                Some(ref peer) => {
+                    let scheme = if settings.sync.https { "https" } else { "http" };
+                    println!("Peer: {}://{}", scheme, peer);
+                    if settings.sync.token.is_none() {
+                        println!("No token for the peer, set token in the [sync] section.");
+                    }
+                }
+                None => println!("No peer, set peer in the [sync] section."),
+            }
+            let store = SqlStore::build()?;
+            let backlog = replication::backlog(&store.connection()?)?;
+            println!("Unsent actions: {}", backlog.actions);
+            if let Some(oldest) = backlog.oldest {
+                println!("Oldest unsent: {}", oldest);
+            }
+            if settings.sync.pages {
+                println!("Unsent page contents: {}", backlog.pages);
+            }
+            Ok(())
+        }
+        TokenCreate(name, save) => {
+            let store = SqlStore::build()?;
+            let token = api_tokens::create(&store.connection()?, &name)?;
+            if save {
+                let path = api_token::save(&token)?;
+                println!("Token {} saved in {}", name, path.display());
+            } else {
+                println!("{}", token);
+                println!("Save this token, it cannot be displayed again.");
+            }
+            Ok(())
+        }
+        TokenList => {
+            let store = SqlStore::build()?;
+            for token in api_tokens::fetch_all(&store.connection()?)? {
+                println!("{}\t{}", token.name, token.created);
+            }
+            Ok(())
+        }
+        TokenRevoke(name) => {
+            let store = SqlStore::build()?;
+            if api_tokens::revoke(&store.connection()?, &name)? {
+                println!("Revoked {}", name);
+                Ok(())
+            } else {
+                Err(format!("no token named {}", name).into())
+            }
+        }
     }
 }
 
+/// Use the repo key for the encrypted columns, if the database was encrypted.
+fn install_field_cipher(store: &SqlStore, repo: &repo::EncryptedRepo) -> Result<()> {
+    crypto::install_if_enabled(store, || Ok(repo.field_cipher()))?;
+    Ok(())
+}
+
 fn execute_sqlite() -> Result<()> {
     if let Some(db_path) = file_utils::default_database()?.to_str() {
         let open_cmd = format!(".open {}", db_path);
@@ -0,0 +1,255 @@
+//! Local certificate authority and server certificate, used for https between the clients
+//! and the server.
+//!
+//! The authority is generated once and reused, the clients trust only it through `tls.ca` in the
+//! settings. The server certificate is saved as PEM for rustls and as a PKCS12 bundle for
+//! native-tls. The private keys are only readable by the current user.
+use chrono::{Duration, Utc};
+use lib_error::*;
+use lib_goo::config::Settings;
+use p12_keystore::{KeyStore, KeyStoreEntry, PrivateKeyChain};
+use pem::Pem;
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType,
+    ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose, SanType,
+};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+/// Validity of the authority.
+const CA_DAYS: i64 = 10 * 365;
+/// Validity of the server certificate, browsers reject longer ones.
+const SERVER_DAYS: i64 = 825;
+
+fn rcgen_error(context: &str, e: rcgen::RcgenError) -> WeaverError {
+    WeaverError::from(format!("{} {:?}", context, e))
+}
+
+// The private key of the authority is saved next to its certificate.
+fn ca_key_path(ca_path: &Path) -> PathBuf {
+    ca_path.with_extension("key")
+}
+
+fn pem_error(context: &str, e: pem::PemError) -> WeaverError {
+    WeaverError::from(format!("{} {:?}", context, e))
+}
+
+// The mode only applies to new files, an existing file is restricted before it is rewritten.
+fn write_private(path: &Path, content: &[u8]) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    let mut file = options.open(path)?;
+    file.write_all(content)?;
+    Ok(())
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        if !parent.exists() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    Ok(())
+}
+
+// Load the existing authority or create a new one.
+fn authority(ca_path: &Path) -> Result<Certificate> {
+    let key_path = ca_key_path(ca_path);
+    if ca_path.exists() && key_path.exists() {
+        let key = KeyPair::from_pem(&fs::read_to_string(&key_path)?)
+            .map_err(|e| rcgen_error("ca key", e))?;
+        let params = CertificateParams::from_ca_cert_pem(&fs::read_to_string(ca_path)?, key)
+            .map_err(|e| rcgen_error("ca certificate", e))?;
+        return Certificate::from_params(params).map_err(|e| rcgen_error("ca", e));
+    }
+
+    let mut params = CertificateParams::default();
+    let mut name = DistinguishedName::new();
+    name.push(DnType::OrganizationName, "Weaver");
+    name.push(DnType::CommonName, "Weaver local authority");
+    params.distinguished_name = name;
+    params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+    params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+    params.not_before = Utc::now() - Duration::days(1);
+    params.not_after = Utc::now() + Duration::days(CA_DAYS);
+    let ca = Certificate::from_params(params).map_err(|e| rcgen_error("ca", e))?;
+
+    create_parent(ca_path)?;
+    fs::write(
+        ca_path,
+        ca.serialize_pem().map_err(|e| rcgen_error("ca pem", e))?,
+    )?;
+    write_private(&key_path, ca.serialize_private_key_pem().as_bytes())?;
+    Ok(ca)
+}
+
+// The names of the server: localhost, the loopback addresses and the configured address.
+fn subject_names(address: &str) -> Vec<SanType> {
+    let mut names = vec![
+        SanType::DnsName("localhost".into()),
+        SanType::IpAddress("127.0.0.1".parse().expect("loopback v4")),
+        SanType::IpAddress("::1".parse().expect("loopback v6")),
+    ];
+    match address.parse::<IpAddr>() {
+        Ok(ip) if !ip.is_unspecified() && !ip.is_loopback() => names.push(SanType::IpAddress(ip)),
+        Ok(_) => {}
+        Err(_) if address != "localhost" => names.push(SanType::DnsName(address.into())),
+        Err(_) => {}
+    }
+    names
+}
+
+// Bundle the key and the certificate chain, server first, in the PKCS12 file read by native-tls.
+fn write_pfx(settings: &Settings, key_der: &[u8], chain: &[Vec<u8>]) -> Result<()> {
+    let mut certificates = Vec::new();
+    for der in chain {
+        certificates.push(
+            p12_keystore::Certificate::from_der(der)
+                .map_err(|e| WeaverError::from(format!("pfx certificate {:?}", e)))?,
+        );
+    }
+    let mut store = KeyStore::new();
+    store.add_entry(
+        "localhost",
+        KeyStoreEntry::PrivateKeyChain(PrivateKeyChain::new(key_der, b"localhost", certificates)),
+    );
+    let pfx = store
+        .writer(settings.tls.pfx_password())
+        .write()
+        .map_err(|e| WeaverError::from(format!("pfx {:?}", e)))?;
+    let pfx_path = settings.tls.pfx_path()?;
+    create_parent(&pfx_path)?;
+    write_private(&pfx_path, &pfx)
+}
+
+// Create the pfx bundle of an existing certificate, for the setups made before it was generated.
+fn pfx_if_needed(settings: &Settings, cert_path: &Path) -> Result<bool> {
+    let key_path = settings.tls.key_path()?;
+    if settings.tls.pfx_path()?.exists() || !key_path.exists() {
+        return Ok(false);
+    }
+    let chain = pem::parse_many(fs::read_to_string(cert_path)?)
+        .map_err(|e| pem_error("server certificate", e))?
+        .into_iter()
+        .map(|p| p.contents)
+        .collect::<Vec<_>>();
+    let key = pem::parse(fs::read_to_string(&key_path)?).map_err(|e| pem_error("server key", e))?;
+    write_pfx(settings, &key.contents, &chain)?;
+    Ok(true)
+}
+
+/// Generate the authority and the server certificate, unless the server certificate exists.
+/// Returns true when new certificates were created.
+pub fn setup_if_needed(settings: &Settings) -> Result<bool> {
+    let cert_path = settings.tls.cert_path()?;
+    if cert_path.exists() {
+        return pfx_if_needed(settings, &cert_path);
+    }
+    let ca_path = settings.tls.ca_path()?;
+    let ca = authority(&ca_path)?;
+
+    let mut params = CertificateParams::default();
+    let mut name = DistinguishedName::new();
+    name.push(DnType::OrganizationName, "Weaver");
+    name.push(DnType::CommonName, "localhost");
+    params.distinguished_name = name;
+    params.subject_alt_names = subject_names(&settings.server.address);
+    params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+    params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+    params.use_authority_key_identifier_extension = true;
+    params.not_before = Utc::now() - Duration::days(1);
+    params.not_after = Utc::now() + Duration::days(SERVER_DAYS);
+    let server = Certificate::from_params(params).map_err(|e| rcgen_error("server", e))?;
+
+    // The chain sent by the server: its certificate followed by the authority.
+    let server_der = server
+        .serialize_der_with_signer(&ca)
+        .map_err(|e| rcgen_error("server der", e))?;
+    let ca_pem = fs::read_to_string(&ca_path)?;
+    let ca_der = pem::parse(&ca_pem)
+        .map_err(|e| pem_error("ca certificate", e))?
+        .contents;
+    let server_pem = pem::encode(&Pem {
+        tag: "CERTIFICATE".into(),
+        contents: server_der.clone(),
+    });
+    create_parent(&cert_path)?;
+    fs::write(&cert_path, format!("{}{}", server_pem, ca_pem))?;
+    let key_path = settings.tls.key_path()?;
+    create_parent(&key_path)?;
+    write_private(&key_path, server.serialize_private_key_pem().as_bytes())?;
+    write_pfx(
+        settings,
+        &server.serialize_private_key_der(),
+        &[server_der, ca_der],
+    )?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_subject_names() {
+        let names = subject_names("0.0.0.0");
+        assert_eq!(names.len(), 3);
+        assert!(names.contains(&SanType::DnsName("localhost".into())));
+
+        let names = subject_names("192.168.1.10");
+        assert_eq!(names.len(), 4);
+        assert!(names.contains(&SanType::IpAddress("192.168.1.10".parse().unwrap())));
+
+        let names = subject_names("weaver.lan");
+        assert_eq!(names.len(), 4);
+        assert!(names.contains(&SanType::DnsName("weaver.lan".into())));
+
+        assert_eq!(subject_names("localhost").len(), 3);
+        assert_eq!(subject_names("127.0.0.1").len(), 3);
+    }
+
+    #[test]
+    fn test_setup() {
+        let folder = env::temp_dir().join(format!("weaver-certificates-{}", std::process::id()));
+        let mut settings = Settings::default();
+        settings.tls.ca = Some(folder.join("ca.crt"));
+        settings.tls.cert = Some(folder.join("localhost.crt"));
+        settings.tls.key = Some(folder.join("localhost.key"));
+        settings.tls.pfx = Some(folder.join("localhost.pfx"));
+
+        assert!(setup_if_needed(&settings).expect("setup"));
+        assert!(!setup_if_needed(&settings).expect("second setup"));
+        let chain = pem::parse_many(fs::read_to_string(folder.join("localhost.crt")).unwrap())
+            .expect("chain");
+        assert_eq!(chain.len(), 2);
+        let pfx = fs::read(folder.join("localhost.pfx")).expect("pfx");
+        let store = KeyStore::from_pkcs12(&pfx, settings.tls.pfx_password()).expect("pkcs12");
+        let (_, key_chain) = store.private_key_chain().expect("key chain");
+        assert_eq!(key_chain.chain().len(), 2);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for name in &["ca.key", "localhost.key", "localhost.pfx"] {
+                let mode = fs::metadata(folder.join(name)).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600, "{}", name);
+            }
+        }
+
+        // The pfx of an earlier setup is created from the existing certificate.
+        fs::remove_file(folder.join("localhost.pfx")).expect("remove pfx");
+        assert!(setup_if_needed(&settings).expect("pfx setup"));
+        assert!(folder.join("localhost.pfx").exists());
+        fs::remove_dir_all(&folder).expect("cleanup");
+    }
+}
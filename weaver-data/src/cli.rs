@@ -1,7 +1,9 @@
-use clap::{App, Arg, SubCommand};
+use clap::{App, Arg, ArgGroup, SubCommand};
+use lib_db::browser_import::Browser;
 use lib_goo::config::db;
 use lib_goo::config::file_utils::set_app_location;
 use lib_index::repo::Collection;
+use std::path::PathBuf;
 
 pub const APP_NAME: &str = env!["CARGO_PKG_NAME"];
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -20,18 +22,35 @@ pub enum DataSubCommand {
     Decrypt(Collection, String),
     /// Dump the content of the url policies (restrictions) table.
     DumpUrlPolicies,
+    /// Encrypt the sensitive columns of the sqlite database with the repo key.
+    EncryptDb,
     /// Encrypt the file with the given name and save in the repo.
     Encrypt(Collection, String),
+    /// Delete everything matching the pattern from all the stores.
+    Forget(String),
+    /// Import the visits from the history database of the browser.
+    ImportBrowser(Browser, PathBuf),
     Noop,
+    /// Delete the data older than the retention rules, only report when the flag is set.
+    Prune(bool),
     /// Delete the text index and rebuilds it by replaying the document in the store.
     RebuildIndex,
     /// Link the commands and pages tables.
     LinkCommandPages,
     /// Run the sqlite shell on the weaver db
     Sqlite,
+    /// Show the actions waiting to be pushed to the sync peer.
+    SyncStatus,
+    /// Create an api token with the given name, optionally save it for the local clients.
+    TokenCreate(String, bool),
+    /// List the names of the api tokens.
+    TokenList,
+    /// Revoke the api token with the given name.
+    TokenRevoke(String),
 }
 
 pub struct ConfigAndCommand {
+    /// Set when passed on the command line, otherwise taken from the settings.
     pub password_source: Option<db::PasswordSource>,
     pub command: DataSubCommand,
 }
@@ -58,9 +77,14 @@ pub fn parse() -> ConfigAndCommand {
             Arg::with_name("password")
                 .short("P")
                 .takes_value(true)
-                .possible_values(&["prompt", "keyring", "environment"])
+                .value_name("SOURCE")
+                .validator(|v| {
+                    v.parse::<db::PasswordSource>()
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                })
                 .long("password")
-                .help("Prompt for password - instead of the default of taking from keyring"),
+                .help("Password source: prompt, keyring (default), environment or key-file:<PATH>"),
         )
         .subcommand(
             SubCommand::with_name("backup").about("Create a backup of the existing database"),
@@ -93,7 +117,53 @@ pub fn parse() -> ConfigAndCommand {
                 .arg(Arg::with_name("NAME").index(1))
                 .about("Decrypt the handle"),
         )
+        .subcommand(
+            SubCommand::with_name("encrypt-db")
+                .about("Encrypt the commands, urls, titles and annotations in the sqlite database"),
+        )
         .subcommand(SubCommand::with_name("check").about("Validate the state of the various repos"))
+        .subcommand(
+            SubCommand::with_name("forget")
+                .arg(
+                    Arg::with_name("PATTERN")
+                        .index(1)
+                        .required(true)
+                        .help("Part of the url or command to forget"),
+                )
+                .about("Delete the matching actions, pages and documents from all the stores"),
+        )
+        .subcommand(
+            SubCommand::with_name("import-browser")
+                .arg(
+                    Arg::with_name("chrome")
+                        .long("chrome")
+                        .takes_value(true)
+                        .value_name("HISTORY")
+                        .help("Path of the History file in the Chrome profile"),
+                )
+                .arg(
+                    Arg::with_name("firefox")
+                        .long("firefox")
+                        .takes_value(true)
+                        .value_name("PLACES")
+                        .help("Path of the places.sqlite file in the Firefox profile"),
+                )
+                .group(
+                    ArgGroup::with_name("browser")
+                        .args(&["chrome", "firefox"])
+                        .required(true),
+                )
+                .about("Import the browsing history of Chrome or Firefox, close the browser first"),
+        )
+        .subcommand(
+            SubCommand::with_name("prune")
+                .arg(
+                    Arg::with_name("dry-run")
+                        .long("dry-run")
+                        .help("Only report what would be deleted"),
+                )
+                .about("Delete the data older than the retention rules in user-data/retention.json"),
+        )
         .subcommand(
             SubCommand::with_name("rebuild-index")
                 .about("Rebuild the text search index from the files in the encrypted repo"),
@@ -105,6 +175,39 @@ pub fn parse() -> ConfigAndCommand {
         .subcommand(
             SubCommand::with_name("dump-url-policies").about("Show the current url policies"),
         )
+        .subcommand(
+            SubCommand::with_name("token")
+                .about("Manage the tokens used by the clients to access the api")
+                .subcommand(
+                    SubCommand::with_name("create")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .index(1)
+                                .required(true)
+                                .help("Name of the client using the token"),
+                        )
+                        .arg(
+                            Arg::with_name("save")
+                                .long("save")
+                                .help("Save the token in user-data/api-token for the local clients"),
+                        )
+                        .about("Create a token, it is only displayed once"),
+                )
+                .subcommand(SubCommand::with_name("list").about("List the token names"))
+                .subcommand(
+                    SubCommand::with_name("revoke")
+                        .arg(Arg::with_name("NAME").index(1).required(true))
+                        .about("Revoke the token with the given name"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sync")
+                .about("Replication to the peer configured in the [sync] settings")
+                .subcommand(
+                    SubCommand::with_name("status")
+                        .about("Show the peer and the actions waiting to be pushed"),
+                ),
+        )
         .get_matches();
 
     if let Some(location) = matches.value_of("location") {
@@ -117,16 +220,9 @@ pub fn parse() -> ConfigAndCommand {
             command: DataSubCommand::Noop,
         };
     }
-    let password_source = if let Some(source) = matches.value_of("password") {
-        match source {
-            "prompt" => db::PasswordSource::Prompt,
-            "keyring" => db::PasswordSource::Keyring,
-            "environment" => db::PasswordSource::Environment,
-            _ => db::PasswordSource::Keyring,
-        }
-    } else {
-        db::PasswordSource::Keyring
-    };
+    let password_source = matches
+        .value_of("password")
+        .and_then(|source| source.parse::<db::PasswordSource>().ok());
     let command = if matches.subcommand_matches("backup").is_some() {
         DataSubCommand::Backup
     } else if matches.subcommand_matches("sqlite").is_some() {
@@ -135,6 +231,8 @@ pub fn parse() -> ConfigAndCommand {
         DataSubCommand::Create
     } else if matches.subcommand_matches("check").is_some() {
         DataSubCommand::Check
+    } else if matches.subcommand_matches("encrypt-db").is_some() {
+        DataSubCommand::EncryptDb
     } else if let Some(encrypt) = matches.subcommand_matches("encrypt") {
         let name = encrypt.value_of("NAME").unwrap();
         let collection = encrypt.value_of("collection").unwrap();
@@ -143,17 +241,42 @@ pub fn parse() -> ConfigAndCommand {
         let name = decrypt.value_of("NAME").unwrap();
         let collection = decrypt.value_of("collection").unwrap();
         DataSubCommand::Decrypt(Collection(collection.into()), name.to_string())
+    } else if let Some(forget) = matches.subcommand_matches("forget") {
+        let pattern = forget.value_of("PATTERN").unwrap();
+        DataSubCommand::Forget(pattern.to_string())
+    } else if let Some(import) = matches.subcommand_matches("import-browser") {
+        match import.value_of("chrome") {
+            Some(path) => DataSubCommand::ImportBrowser(Browser::Chrome, PathBuf::from(path)),
+            None => DataSubCommand::ImportBrowser(
+                Browser::Firefox,
+                PathBuf::from(import.value_of("firefox").unwrap()),
+            ),
+        }
+    } else if let Some(prune) = matches.subcommand_matches("prune") {
+        DataSubCommand::Prune(prune.is_present("dry-run"))
     } else if matches.subcommand_matches("rebuild-index").is_some() {
         DataSubCommand::RebuildIndex
     } else if matches.subcommand_matches("link-commands-pages").is_some() {
         DataSubCommand::LinkCommandPages
     } else if matches.subcommand_matches("dump-url-policies").is_some() {
         DataSubCommand::DumpUrlPolicies
+    } else if let Some(token) = matches.subcommand_matches("token") {
+        if let Some(create) = token.subcommand_matches("create") {
+            let name = create.value_of("NAME").unwrap();
+            DataSubCommand::TokenCreate(name.to_string(), create.is_present("save"))
+        } else if let Some(revoke) = token.subcommand_matches("revoke") {
+            let name = revoke.value_of("NAME").unwrap();
+            DataSubCommand::TokenRevoke(name.to_string())
+        } else {
+            DataSubCommand::TokenList
+        }
+    } else if matches.subcommand_matches("sync").is_some() {
+        DataSubCommand::SyncStatus
     } else {
         unreachable!()
     };
     ConfigAndCommand {
-        password_source: Some(password_source),
+        password_source,
         command,
     }
 }
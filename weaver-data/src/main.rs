@@ -16,6 +16,7 @@
 //!
 
 mod app;
+mod certificates;
 mod cli;
 
 fn main() {
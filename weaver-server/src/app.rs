@@ -2,13 +2,16 @@
 //!
 use chrono::prelude::*;
 use crate::cli::ServerRun;
-use crate::cli::{parse, CommandAndConfig, ServerSubCommand};
+use crate::cli::{parse, CommandAndConfig, ServerSubCommand, VERSION};
+use crate::lifecycle::{self, ServerInfo};
 use daemonize::{self, Daemonize};
-use lib_db::SqlStore;
+use lib_db::{SqlProvider, SqlStore};
 use lib_error::*;
 use lib_goo::config::db::PasswordSource;
-use lib_goo::config::{file_utils, ServerConfig};
+use lib_goo::config::net::{self, HealthReport};
+use lib_goo::config::{file_utils, Destination, ServerConfig};
 use lib_index;
+use lib_rpc::client;
 use lib_server;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -22,14 +25,80 @@ pub fn run() -> Result<()> {
         command,
         server_config,
         password_source,
-    } = parse();
+    } = parse()?;
     ::log::debug!("Executing cli command {:?}", command);
     match command {
         Noop => Ok(()),
         ServerSubCommand::Start(ref mode) => {
             start(mode, &server_config, &password_source).map(|_| ())
         }
-        ServerSubCommand::Check => server_config.check(),
+        ServerSubCommand::Check(wait) => check(&server_config, wait),
+        ServerSubCommand::Stop(timeout) => stop(timeout).map(|_| ()),
+        ServerSubCommand::Restart(timeout, retries) => {
+            stop(timeout)?;
+            start(
+                &ServerRun::Daemonize(retries),
+                &server_config,
+                &password_source,
+            )
+            .map(|_| ())
+        }
+        ServerSubCommand::Status => lifecycle::status(&server_folder()?, &pid_file()?).map(|_| ()),
+    }
+}
+
+/// Stop the running server, if any.
+fn stop(timeout: u64) -> Result<bool> {
+    let stopped = lifecycle::stop(&pid_file()?, timeout)?;
+    if stopped {
+        println!("Server stopped.");
+    } else {
+        println!("Server not running.");
+    }
+    Ok(stopped)
+}
+
+// Ask the server for its readiness, retry until the deadline.
+fn fetch_readiness(config: &ServerConfig, wait: u64) -> Result<HealthReport> {
+    let destination = Destination::Remote(config.actix_address());
+    let deadline = time::Instant::now() + time::Duration::from_secs(wait);
+    loop {
+        let outcome = client::health(&destination, &config.base_url, net::READY);
+        let ready = outcome.as_ref().map(|r| r.ok).unwrap_or(false);
+        if ready || time::Instant::now() >= deadline {
+            return outcome;
+        }
+        thread::sleep(time::Duration::from_secs(1));
+    }
+}
+
+/// Print the readiness of the server and of each store, fail when not ready.
+fn check(config: &ServerConfig, wait: u64) -> Result<()> {
+    let report = fetch_readiness(config, wait).map_err(|e| {
+        WeaverError::from(format!(
+            "server not answering on {}: {}",
+            config.actix_address(),
+            e
+        ))
+    })?;
+    for store in &report.stores {
+        let state = match (store.ok, store.required) {
+            (true, _) => "ok",
+            (false, true) => "FAILED",
+            (false, false) => "unavailable",
+        };
+        println!(
+            "{:<12} {:<12} {}",
+            store.name,
+            state,
+            store.message.as_ref().map(|m| m.as_str()).unwrap_or("")
+        );
+    }
+    if report.ok {
+        println!("Server ready.");
+        Ok(())
+    } else {
+        Err("the server is not ready".into())
     }
 }
 
@@ -78,11 +147,22 @@ fn start(
     let store = Arc::new(SqlStore::build()?);
     lib_index::init()?;
     let repo = Arc::new(lib_index::repo::EncryptedRepo::build(password_source)?);
+    if lib_db::crypto::install_if_enabled(&store, || Ok(repo.field_cipher()))? {
+        ::log::info!("encrypted database columns");
+    }
+
+    if let Some(pid) = lifecycle::running_pid(&pid_file()?)? {
+        return Err(format!("server already running, pid {}", pid).into());
+    }
 
     match run {
         ServerRun::Foreground => {}
         ServerRun::Daemonize(retries) => {
             let pid_file_ = pid_file()?;
+            // A stale pid file from a crashed server would make daemonize fail.
+            if pid_file_.exists() {
+                fs::remove_file(&pid_file_)?;
+            }
             let server_folder_ = server_folder()?;
             let timestamp = Utc::now().format("%Y-%m-%d_%H_%M_%S").to_string();
             rename_files(&server_folder_, "out", &timestamp)?;
@@ -114,7 +194,25 @@ fn start(
             println!("Started in daemon mode");
         }
     }
-    let _actix = lib_server::Server::start(config.http_port, config.https_port, &config.address, config.base_url.clone(), store, repo)?;
+    // Describe the final process, after the optional fork.
+    let (server_folder_, pid_file_) = (server_folder()?, pid_file()?);
+    lifecycle::save(
+        &server_folder_,
+        &pid_file_,
+        &ServerInfo::current(config, VERSION)?,
+    )?;
+    // Blocks until the server is stopped.
+    let outcome = lib_server::Server::start(
+        config.http_port,
+        config.https_port,
+        &config.address,
+        config.base_url.clone(),
+        store,
+        repo,
+        password_source,
+    );
+    lifecycle::clear(&server_folder_, &pid_file_);
+    outcome?;
 
     Ok(Server)
 }
@@ -3,9 +3,10 @@
 use clap::{App, Arg, ArgMatches, SubCommand};
 use lib_goo::config::db::PasswordSource;
 use lib_goo::config::file_utils::set_app_location;
-use lib_goo::config::{db, ServerConfig};
+use lib_error::*;
+use lib_goo::config::{db, ServerConfig, Settings};
 
-const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 const DESCRIPTION: &str = env!["CARGO_PKG_DESCRIPTION"];
 
 pub const APP_NAME: &str = env!["CARGO_PKG_NAME"];
@@ -22,7 +23,33 @@ pub enum ServerRun {
 pub enum ServerSubCommand {
     Noop,
     Start(ServerRun),
-    Check,
+    /// Check the readiness, wait up to the given number of seconds for the server to come up.
+    Check(u64),
+    /// Stop the server, wait up to the given number of seconds for it to exit.
+    Stop(u64),
+    /// Stop the server if running then start it in daemon mode.
+    Restart(u64, Option<u32>),
+    /// Report the pid, uptime, version, ports and stores of the running server.
+    Status,
+}
+
+/// Seconds to wait for the server to exit, more than the time given to the pending requests.
+const STOP_TIMEOUT: u64 = 45;
+
+fn timeout_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("timeout")
+        .long("timeout")
+        .takes_value(true)
+        .value_name("SECONDS")
+        .validator(|v| v.parse::<u64>().map(|_| ()).map_err(|e| e.to_string()))
+        .help("Seconds to wait for the server to exit")
+}
+
+fn timeout_value(matches: &ArgMatches) -> u64 {
+    matches
+        .value_of("timeout")
+        .and_then(|t| t.parse::<u64>().ok())
+        .unwrap_or(STOP_TIMEOUT)
 }
 
 pub struct CommandAndConfig {
@@ -31,8 +58,8 @@ pub struct CommandAndConfig {
     pub password_source: PasswordSource,
 }
 
-/// Parse a Command from the command line options.
-pub fn parse() -> CommandAndConfig {
+/// Parse a Command from the command line options, the flags override the settings.
+pub fn parse() -> Result<CommandAndConfig> {
     let matches = App::new(APP_NAME)
         .version(VERSION)
         .about(DESCRIPTION)
@@ -53,9 +80,14 @@ pub fn parse() -> CommandAndConfig {
             Arg::with_name("password")
                 .short("P")
                 .takes_value(true)
-                .possible_values(&["prompt", "keyring", "environment"])
+                .value_name("SOURCE")
+                .validator(|v| {
+                    v.parse::<db::PasswordSource>()
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                })
                 .long("password")
-                .help("Prompt for password - instead of the default of taking from keyring"),
+                .help("Password source: prompt, keyring (default), environment or key-file:<PATH>"),
         )
         .arg(
             Arg::with_name("port")
@@ -82,7 +114,18 @@ pub fn parse() -> CommandAndConfig {
                 .value_name("BASE_URL")
                 .help("Select base_url to host the URLs under"),
         )
-        .subcommand(SubCommand::with_name("check").about("Check to see that the server exists"))
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Check that the server and its stores are ready")
+                .arg(
+                    Arg::with_name("wait")
+                        .long("wait")
+                        .takes_value(true)
+                        .value_name("SECONDS")
+                        .validator(|v| v.parse::<u64>().map(|_| ()).map_err(|e| e.to_string()))
+                        .help("Wait for the server to be ready"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("start")
                 .about("Start the server")
@@ -100,47 +143,67 @@ pub fn parse() -> CommandAndConfig {
                         .help("Number of times to retry the start"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("stop")
+                .about("Stop the server gracefully")
+                .arg(timeout_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("restart")
+                .about("Stop the server then start it in daemon mode")
+                .arg(timeout_arg())
+                .arg(
+                    Arg::with_name("retries")
+                        .long("retry")
+                        .takes_value(true)
+                        .value_name("COUNT")
+                        .help("Number of times to retry the start"),
+                ),
+        )
+        .subcommand(SubCommand::with_name("status").about("Display the state of the server"))
         .get_matches();
 
-    let address = matches.value_of("address").unwrap_or("127.0.0.1");
-    let base_url = matches.value_of("base-url").unwrap_or("/");
-    // Check if port is present and is in int format.
-    let server_config = match matches.value_of("port").and_then(|p| p.parse::<u16>().ok()) {
-        Some(port) => ServerConfig {
-            http_port: port,
-            https_port: port + 1,
-            address: String::from(address),
-            base_url: String::from(base_url),
-        },
-        None => ServerConfig::current(),
-    };
-    let password_source = if let Some(source) = matches.value_of("password") {
-        match source {
-            "prompt" => db::PasswordSource::Prompt,
-            "keyring" => db::PasswordSource::Keyring,
-            "environment" => db::PasswordSource::Environment,
-            _ => db::PasswordSource::Keyring,
-        }
-    } else {
-        db::PasswordSource::Keyring
+    // The location selects the settings file, it needs to be set first.
+    if let Some(location) = matches.value_of("location") {
+        set_app_location(location);
+    }
+    let settings = Settings::load()?;
+    let mut server_config = settings.server.clone();
+    if let Some(port) = matches.value_of("port").and_then(|p| p.parse::<u16>().ok()) {
+        server_config.http_port = port;
+        server_config.https_port = port + 1;
+    }
+    if let Some(address) = matches.value_of("address") {
+        server_config.address = String::from(address);
+    }
+    if let Some(base_url) = matches.value_of("base-url") {
+        server_config.base_url = String::from(base_url);
+    }
+    let password_source = match matches
+        .value_of("password")
+        .and_then(|source| source.parse::<db::PasswordSource>().ok())
+    {
+        Some(source) => source,
+        None => settings.store.password_source()?,
     };
-    CommandAndConfig {
+    Ok(CommandAndConfig {
         command: parse_command(&matches),
         server_config,
         password_source,
-    }
+    })
 }
 
 fn parse_command(matches: &ArgMatches) -> ServerSubCommand {
-    if let Some(location) = matches.value_of("location") {
-        set_app_location(location);
-    }
     if matches.is_present("version") {
         println!("{}", VERSION);
         return ServerSubCommand::Noop;
     }
-    if matches.subcommand_matches("check").is_some() {
-        return ServerSubCommand::Check;
+    if let Some(check) = matches.subcommand_matches("check") {
+        let wait = check
+            .value_of("wait")
+            .and_then(|w| w.parse::<u64>().ok())
+            .unwrap_or(0);
+        return ServerSubCommand::Check(wait);
     }
     if let Some(start) = matches.subcommand_matches("start") {
         let server_run = if start.is_present("foreground") {
@@ -151,5 +214,17 @@ fn parse_command(matches: &ArgMatches) -> ServerSubCommand {
         };
         return ServerSubCommand::Start(server_run);
     }
-    return ServerSubCommand::Check;
+    if let Some(stop) = matches.subcommand_matches("stop") {
+        return ServerSubCommand::Stop(timeout_value(stop));
+    }
+    if let Some(restart) = matches.subcommand_matches("restart") {
+        let count = restart
+            .value_of("retries")
+            .map(|c| c.parse::<u32>().unwrap());
+        return ServerSubCommand::Restart(timeout_value(restart), count);
+    }
+    if matches.subcommand_matches("status").is_some() {
+        return ServerSubCommand::Status;
+    }
+    return ServerSubCommand::Check(0);
 }
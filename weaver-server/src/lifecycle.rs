@@ -0,0 +1,152 @@
+//! Stop, status and restart of the server through its pid file.
+//!
+//! The running server also saves a description of itself next to the pid file, this is used
+//! to report the uptime, version, ports and stores.
+use chrono::prelude::*;
+use lib_error::*;
+use lib_goo::config::{file_utils, ServerConfig};
+use lib_goo::date;
+use lib_index::repo::EncryptedRepo;
+use lib_index::TantivyIndexer;
+use serde_json as json;
+use std::fs;
+use std::path::PathBuf;
+use std::{thread, time};
+
+/// Description of the running server, saved in `server.json`.
+#[derive(Debug, ::serde::Deserialize, ::serde::Serialize)]
+pub struct ServerInfo {
+    pub pid: i32,
+    pub started: String,
+    pub version: String,
+    pub http_port: u16,
+    pub https_port: u16,
+    pub address: String,
+    pub base_url: String,
+    pub database: PathBuf,
+    pub repo: PathBuf,
+    pub index: PathBuf,
+}
+
+impl ServerInfo {
+    /// Describe the current process.
+    pub fn current(config: &ServerConfig, version: &str) -> Result<ServerInfo> {
+        Ok(ServerInfo {
+            pid: std::process::id() as i32,
+            started: Local::now().to_rfc3339(),
+            version: version.to_owned(),
+            http_port: config.http_port,
+            https_port: config.https_port,
+            address: config.address.clone(),
+            base_url: config.base_url.clone(),
+            database: file_utils::default_database()?,
+            repo: EncryptedRepo::location()?,
+            index: TantivyIndexer::location()?,
+        })
+    }
+}
+
+// The file where the running server describes itself.
+fn info_file(server_folder: &PathBuf) -> PathBuf {
+    server_folder.join("server.json")
+}
+
+/// Save the pid file and the description, called once the server process is final.
+/// The pid file is also written in foreground mode, the daemon mode already has the same content.
+pub fn save(server_folder: &PathBuf, pid_file: &PathBuf, info: &ServerInfo) -> Result<()> {
+    fs::write(pid_file, format!("{}", info.pid))?;
+    let content = json::to_string_pretty(info)
+        .map_err(|e| WeaverError::from(format!("server info {:?}", e)))?;
+    fs::write(info_file(server_folder), content)?;
+    Ok(())
+}
+
+/// Remove the pid file and the description after a clean shutdown.
+pub fn clear(server_folder: &PathBuf, pid_file: &PathBuf) {
+    for path in &[pid_file.clone(), info_file(server_folder)] {
+        if path.exists() {
+            if let Err(e) = fs::remove_file(path) {
+                ::log::warn!("cannot remove {:?} {:?}", path, e);
+            }
+        }
+    }
+}
+
+/// Read the pid of the server, if the pid file exists and the process is alive.
+pub fn running_pid(pid_file: &PathBuf) -> Result<Option<i32>> {
+    if !pid_file.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(pid_file)?;
+    let pid = content
+        .trim()
+        .parse::<i32>()
+        .map_err(|_| WeaverError::from(format!("bad pid file {:?}", pid_file)))?;
+    Ok(if is_alive(pid) { Some(pid) } else { None })
+}
+
+fn is_alive(pid: i32) -> bool {
+    unsafe { libc::kill(pid, 0) == 0 }
+}
+
+/// Ask the server to shut down gracefully and wait for it to exit.
+/// Returns false when no server was running.
+pub fn stop(pid_file: &PathBuf, timeout: u64) -> Result<bool> {
+    let pid = match running_pid(pid_file)? {
+        Some(pid) => pid,
+        None => return Ok(false),
+    };
+    // SIGTERM lets actix finish the pending requests before the stores are closed.
+    if unsafe { libc::kill(pid, libc::SIGTERM) } != 0 {
+        return Err(WeaverError::from(format!(
+            "cannot signal the server {}",
+            pid
+        )));
+    }
+    let deadline = time::Instant::now() + time::Duration::from_secs(timeout);
+    while is_alive(pid) {
+        if time::Instant::now() >= deadline {
+            return Err(WeaverError::from(format!(
+                "server {} still running after {} seconds",
+                pid, timeout
+            )));
+        }
+        thread::sleep(time::Duration::from_millis(200));
+    }
+    Ok(true)
+}
+
+/// Print the state of the server.
+pub fn status(server_folder: &PathBuf, pid_file: &PathBuf) -> Result<bool> {
+    let pid = match running_pid(pid_file)? {
+        Some(pid) => pid,
+        None => {
+            println!("Server not running.");
+            return Ok(false);
+        }
+    };
+    let info = fs::read_to_string(info_file(server_folder))
+        .ok()
+        .and_then(|content| json::from_str::<ServerInfo>(&content).ok());
+    match info {
+        Some(ref info) if info.pid == pid => {
+            let uptime = date::age(&info.started).unwrap_or(-1);
+            println!("Server running, pid {}.", pid);
+            println!("version   {}", info.version);
+            println!("uptime    {}", date::short_diff(uptime));
+            println!(
+                "http      {}:{}{}",
+                info.address, info.http_port, info.base_url
+            );
+            println!(
+                "https     {}:{}{}",
+                info.address, info.https_port, info.base_url
+            );
+            println!("database  {}", info.database.display());
+            println!("repo      {}", info.repo.display());
+            println!("index     {}", info.index.display());
+        }
+        _ => println!("Server running, pid {}, no details available.", pid),
+    }
+    Ok(true)
+}
@@ -19,6 +19,7 @@ use std::io::{self, Write};
 
 mod app;
 mod cli;
+mod lifecycle;
 
 fn main() {
     // Setup the logger on the env variable WEAVER.
@@ -61,6 +61,7 @@ pub fn fetch_recommendations(
             start: None,
             length: None,
             term,
+            tag: None,
         },
     )?;
     // rebase the command folders on the current work dir. This simplifies the UI interpretation.
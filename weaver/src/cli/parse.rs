@@ -1,6 +1,7 @@
 use super::APP_NAME;
 use clap::{App, Arg, ArgGroup, ArgMatches, SubCommand};
-use lib_goo::config::{OutputKind, ServerConfig};
+use lib_error::*;
+use lib_goo::config::{OutputKind, Settings};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const DESCRIPTION: &str = env!["CARGO_PKG_DESCRIPTION"];
@@ -9,6 +10,7 @@ const DESCRIPTION: &str = env!["CARGO_PKG_DESCRIPTION"];
 #[derive(Debug)]
 pub enum Command {
     ActionHistory(OutputKind),
+    ConfigShow,
     FlowRecommend,
     FlowCreate(String, bool),
     FlowRun(String),
@@ -19,18 +21,20 @@ pub enum Command {
 
 pub struct CommandAndConfig {
     pub command: Command,
-    pub server_config: ServerConfig,
+    /// The settings with the command line overrides applied.
+    pub settings: Settings,
 }
 
 // Constants for command names
 const COMMAND_ACTIONS: &str = "actions";
+const COMMAND_CONFIG: &str = "config";
 const COMMAND_RUN: &str = "run";
 const COMMAND_CREATE: &str = "create";
 const COMMAND_EPIC: &str = "epic";
 const COMMAND_PROMPT: &str = "prompt";
 
-/// Parse a Command from the command line options.
-pub fn parse() -> CommandAndConfig {
+/// Parse a Command from the command line options, the flags override the settings.
+pub fn parse() -> Result<CommandAndConfig> {
     let matches = App::new(APP_NAME)
         .version(VERSION)
         .about(DESCRIPTION)
@@ -61,6 +65,10 @@ pub fn parse() -> CommandAndConfig {
                 .args(&["run", "copy", "print"])))
 
 
+        .subcommand(SubCommand::with_name(COMMAND_CONFIG)
+            .about("Inspect the settings shared by weaver, weaver-server and weaver-data")
+            .subcommand(SubCommand::with_name("show")
+                .about("print the effective settings")))
         .subcommand(SubCommand::with_name(COMMAND_RUN)
             .about("run the flow with the given name")
             .arg(Arg::with_name("NAME")
@@ -88,20 +96,19 @@ pub fn parse() -> CommandAndConfig {
                 .help("validate the setup")))
         .get_matches();
 
-    // Check if port is present and is in int format.
-    let server_config = match matches.value_of("port").and_then(|p| p.parse::<u16>().ok()) {
-        Some(port) => ServerConfig {
-            http_port: port,
-            https_port: port + 1,
-            .. ServerConfig::default()
-        },
-        None => ServerConfig::current(),
-    };
+    // A broken settings file should not break the shell prompt.
+    let mut settings = Settings::load_or_default();
+    // Check if port is present and is in int format, the port selects the local server.
+    if let Some(port) = matches.value_of("port").and_then(|p| p.parse::<u16>().ok()) {
+        settings.server.http_port = port;
+        settings.server.https_port = port + 1;
+        settings.client.server = None;
+    }
 
-    CommandAndConfig {
+    Ok(CommandAndConfig {
         command: parse_command(&matches),
-        server_config,
-    }
+        settings,
+    })
 }
 
 fn parse_command(matches: &ArgMatches) -> Command {
@@ -120,6 +127,9 @@ fn parse_command(matches: &ArgMatches) -> Command {
         };
         return Command::ActionHistory(OutputKind { channel });
     }
+    if matches.subcommand_matches(COMMAND_CONFIG).is_some() {
+        return Command::ConfigShow;
+    }
     if let Some(run) = matches.subcommand_matches(COMMAND_CREATE) {
         let name = run.value_of("NAME").unwrap();
         let global = run.is_present("global");
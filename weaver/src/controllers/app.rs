@@ -1,9 +1,9 @@
-use super::{flows, history, shell_prompt};
+use super::{flows, history, settings, shell_prompt};
 use crate::cli::parse;
 use crate::cli::Command::*;
 use crate::cli::CommandAndConfig;
 use lib_error::*;
-use lib_goo::config::{file_utils, Destination, Environment};
+use lib_goo::config::{file_utils, Environment};
 use crate::local_store::epics;
 use std::sync::Arc;
 
@@ -11,9 +11,9 @@ use std::sync::Arc;
 pub fn run() -> Result<()> {
     let CommandAndConfig {
         command,
-        server_config,
-    } = parse();
-    let destination = Destination::Remote(server_config.actix_address());
+        settings: effective,
+    } = parse()?;
+    let destination = effective.destination()?;
     ::log::debug!("Executing cli command {:?}", command);
     let epic = epics::epic()?;
     let env = Arc::new(Environment::build(epic)?);
@@ -25,6 +25,7 @@ pub fn run() -> Result<()> {
             let actions = file_utils::read_stdin(50)?;
             flows::create(name, global, actions)
         }
+        ConfigShow => settings::show(&effective),
         EpicActivate(name) => epics::save_epic(name),
         Noop => Ok(()),
         ShellPrompt(check) => {
@@ -1,6 +1,7 @@
 /// The controllers execute the actual commands passed in through the CLI.
 mod flows;
 mod history;
+mod settings;
 mod shell_prompt;
 mod shell_proxy;
 
@@ -0,0 +1,24 @@
+use lib_error::*;
+use lib_goo::config::{Settings, ENV_VARS};
+use std::env;
+
+/// Print the effective settings, the settings file used and the active environment overrides.
+pub fn show(settings: &Settings) -> Result<()> {
+    let path = Settings::path()?;
+    if path.exists() {
+        println!("# settings file {}", path.display());
+    } else {
+        println!(
+            "# no settings file at {}, using the defaults",
+            path.display()
+        );
+    }
+    for env_var in ENV_VARS.iter() {
+        if env::var(env_var.name).is_ok() {
+            println!("# {} overrides {}", env_var.name, env_var.setting);
+        }
+    }
+    println!();
+    print!("{}", settings.show()?);
+    Ok(())
+}
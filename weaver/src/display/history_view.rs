@@ -25,6 +25,9 @@ impl ActionListViewItem<BasicColumn> for Row {
                 BasicColumn::Name => Some(r.name.to_string()),
                 BasicColumn::Detail => if is_focussed && r.location.is_some() {
                     Some(r.location.as_ref().unwrap().clone())
+                } else if !r.tags.is_empty() {
+                    // Tags are shown when the location is not.
+                    Some(format!("[{}]", r.tags.join(",")))
                 } else {
                     None
                 },
@@ -59,7 +59,7 @@ fn create_command_edit(tx: channel::Sender<Msg>) -> EditView {
 // Create a line containing some instructions
 fn create_help(width: usize) -> TextView {
     use cursive::theme::Effect;
-    let txt = format!("{:width$}", "Type to filter| UP/DOWN to change selection | LEFT/RIGHT for folder | ENTER to select", width=width);
+    let txt = format!("{:width$}", "Type to filter| UP/DOWN to change selection | LEFT/RIGHT for folder | CTRL-D to delete | ENTER to select", width=width);
     TextView::new(txt).effect(Effect::Reverse)
 }
 
@@ -68,6 +68,7 @@ fn setup_global_keys(siv: &mut Cursive, ch: channel::Sender<Msg>) {
         (Event::CtrlChar('g'), Msg::JumpToSelection),
         (Event::CtrlChar('p'), Msg::JumpToPrevMatch),
         (Event::CtrlChar('n'), Msg::JumpToNextMatch),
+        (Event::CtrlChar('d'), Msg::DeleteSelection),
     ];
     for (cursive_ev, processor_msg) in mapping {
         let my_ch = ch.clone();
@@ -2,10 +2,10 @@ use super::output_selector;
 use super::{history_view, UserSelection};
 use crate::api::{fetch_recommendations, Row};
 use crossbeam_channel as channel;
-use cursive::views::EditView;
+use cursive::views::{Dialog, EditView};
 use cursive::{CbFunc as CursiveCbFunc, Cursive};
 use lib_goo::config::Destination;
-use lib_goo::entities::{FormattedAction, RecommendReason};
+use lib_goo::entities::{ActionId, FormattedAction, RecommendReason};
 use lib_goo::{config, date, FilteredVec};
 use lib_rpc::client as rpc_client;
 use std::sync::{Arc, Mutex};
@@ -44,6 +44,8 @@ pub enum Msg {
     // AnnotationSubmit(Option<String>),
 
     // Global events
+    DeleteSelection,
+    ConfirmDelete(ActionId),
     ShowOutputSelector,
     JumpToSelection,
     JumpToPrevMatch,
@@ -253,6 +255,38 @@ impl Processor {
         }
     }
 
+    // Ask before deleting the selected action, nothing is sent without a selection.
+    fn confirm_delete(&mut self) {
+        let (id, name) = match self.formatted_action.as_ref() {
+            Some(s) if s.id.format().is_some() => (s.id.clone(), s.name.clone()),
+            _ => return,
+        };
+        let my_tx = self.self_tx.clone();
+        let show_dialog = move |siv: &mut Cursive| {
+            siv.add_layer(
+                Dialog::text(format!("Delete {} from the history?", name))
+                    .title("Delete action")
+                    .dismiss_button("Cancel")
+                    .button("Delete", move |s| {
+                        my_tx.send(Msg::ConfirmDelete(id.clone()));
+                        s.pop_layer();
+                    }),
+            );
+        };
+        self.cursive_sink.send(Box::new(show_dialog));
+    }
+
+    // Delete the action from the server and refresh the table.
+    fn delete_selected(&mut self, selection: &ActionId) {
+        if let Err(e) = rpc_client::delete(&self.destination, selection) {
+            ::log::error!("deleting action {:?}", e);
+            self.show_error("DELETE FAILED".into());
+            return;
+        }
+        let search = self.search_string.clone();
+        self.filter(search.as_ref().map(|s| s.as_str()), None);
+    }
+
     fn exit(&mut self) {
         self.cursive_sink.send(Box::new(|siv: &mut Cursive| {
             siv.quit();
@@ -343,6 +377,12 @@ impl ProcessorThread {
                     Some(Msg::JumpToPrevMatch) => {
                         processor.jump_to_prev();
                     }
+                    Some(Msg::DeleteSelection) => {
+                        processor.confirm_delete();
+                    }
+                    Some(Msg::ConfirmDelete(id)) => {
+                        processor.delete_selected(&id);
+                    }
                     Some(Msg::ShowOutputSelector) => {
                         processor.show_output_selector();
                     }
@@ -10,8 +10,6 @@ pub(crate) struct Weaver {
     pub active_flow: Option<String>,
     // Active flow being executed.
     milestones: Vec<Epic>,
-    // Start in server mode if it doesn't run.
-    pub start_server: Option<bool>,
 }
 
 impl Default for Weaver {
@@ -20,7 +18,6 @@ impl Default for Weaver {
             active_epic: None,
             active_flow: None,
             milestones: Vec::new(),
-            start_server: Some(true),
         }
     }
 }
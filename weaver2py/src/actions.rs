@@ -0,0 +1,60 @@
+use cpython::{PyDict, PyList, PyObject, PyResult, Python, PythonObject};
+use error::as_py_err;
+use lib_db::{actions2, crypto, SqlProvider, SqlStore};
+use lib_goo::config::net::Pagination;
+use lib_goo::entities::{FormattedAction, RecommendReason};
+use lib_index::repo::EncryptedRepo;
+use repo::password_source;
+
+/// Fetch the actions with the same filters as the REST api: an optional search term, an
+/// optional tag and the pagination range. The password is only used when the database is
+/// encrypted.
+pub fn actions(
+    py: Python,
+    password: String,
+    term: Option<String>,
+    start: Option<i64>,
+    length: Option<i64>,
+    tag: Option<String>,
+) -> PyResult<PyList> {
+    let store = SqlStore::build().map_err(|e| as_py_err(py, e))?;
+    crypto::install_if_enabled(&store, || {
+        EncryptedRepo::build(&password_source(password)).map(|repo| repo.field_cipher())
+    })
+    .map_err(|e| as_py_err(py, e))?;
+    let connection = store.connection().map_err(|e| as_py_err(py, e))?;
+
+    let pagination = Pagination { start, length };
+    let fetched = actions2::fetch(
+        &connection,
+        term.as_ref().map(|t| &**t),
+        tag.as_ref().map(|t| &**t),
+        &pagination,
+    )
+    .map_err(|e| as_py_err(py, e))?;
+    let mut entries: Vec<PyObject> = Vec::with_capacity(fetched.len());
+    for action in fetched {
+        entries.push(formatted_action_to_py(py, action)?);
+    }
+    Ok(PyList::new(py, entries.as_slice()))
+}
+
+/// Create a Python Object from a Formatted Action.
+fn formatted_action_to_py(py: Python, action: FormattedAction) -> PyResult<PyObject> {
+    let one = PyDict::new(py);
+    one.set_item(py, "id", action.id.format())?;
+    one.set_item(py, "kind", action.kind)?;
+    one.set_item(py, "name", action.name)?;
+    one.set_item(py, "location", action.location)?;
+    one.set_item(py, "epic", action.epic)?;
+    one.set_item(py, "annotation", action.annotation)?;
+    one.set_item(py, "when", action.when.map(|w| w.to_js()))?;
+    let reason = match action.reason {
+        RecommendReason::Historical => "historical",
+        RecommendReason::CorrelatedMostRecent(_) => "correlated-most-recent",
+        RecommendReason::CorrelatedMostFrequent(_) => "correlated-most-frequent",
+        RecommendReason::UserSelected => "user-selected",
+    };
+    one.set_item(py, "reason", reason)?;
+    Ok(one.into_object())
+}
@@ -1,4 +1,4 @@
-//! Provide Python access the encrypted repo.
+//! Provide Python access the encrypted repo, the actions, the text index and the topics.
 //!
 //! Install with `python setup.py install` (after setting up your virtual environment).
 //! Use like the following, you should prompt the user for the Repo
@@ -10,19 +10,50 @@
 //!
 //! print(weaver.repo_list(password))
 //!
+//! for page in weaver.repo_iter(password):
+//!     print(page["url"])
+//!
+//! df = pandas.DataFrame(weaver.actions(password, term="cargo", length=100))
 //! ```
 
 #![allow(dead_code)]
 
-mod repo;
+mod actions;
 mod error;
+mod repo;
+mod search;
+mod topics;
 
-use repo::repo_list;
+use actions::actions;
+use repo::{repo_iter, repo_list};
+use search::search;
+use topics::topics;
 
 // add bindings to the generated python module
 py_module_initializer!(weaver2py, initweaver2py, PyInit_weaver2py, |py, m| {
-    try!(m.add(py, "__doc__", "Interface to the Weaver encrypted text repo."));
+    try!(m.add(py, "__doc__", "Interface to the Weaver stores."));
     try!(m.add(py, "repo_list", py_fn!(py, repo_list(password: String))));
+    try!(m.add(
+        py,
+        "repo_iter",
+        py_fn!(py, repo_iter(password: String, collection: String = "page-content".to_string()))
+    ));
+    try!(m.add(
+        py,
+        "actions",
+        py_fn!(
+            py,
+            actions(
+                password: String,
+                term: Option<String> = None,
+                start: Option<i64> = None,
+                length: Option<i64> = None,
+                tag: Option<String> = None
+            )
+        )
+    ));
+    try!(m.add(py, "search", py_fn!(py, search(what: String))));
+    try!(m.add(py, "topics", py_fn!(py, topics())));
     Ok(())
 });
 
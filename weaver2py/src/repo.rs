@@ -1,27 +1,30 @@
-use bincode;
-use cpython::{PyDict, PyErr, PyList, PyObject, PyResult, PyString, Python, PythonObject};
+use cpython::{PyClone, PyDict, PyErr, PyList, PyObject, PyResult, PyString, Python, PythonObject};
 use error::as_py_err;
 use lib_goo::config::db::PasswordSource;
-use lib_goo::entities::PageContent;
-use lib_index::repo::EncryptedRepo;
+use lib_goo::entities::{NewAction, PageContent};
+use lib_index::repo::{decode_action, decode_page, Collection, EncryptedRepo, Repo};
+use std::cell::RefCell;
 
-/// List all the files in the Repo. For now this is returned in memory.
-pub fn repo_list(py: Python, password: String) -> PyResult<PyList> {
-    let source = if password.is_empty() {
+/// Use the keyring when no password is passed in.
+pub(crate) fn password_source(password: String) -> PasswordSource {
+    if password.is_empty() {
         PasswordSource::Keyring
     } else {
         PasswordSource::PassIn(password)
-    };
-    let repo = EncryptedRepo::build(&source).map_err(|e| as_py_err(py, e))?;
+    }
+}
+
+/// List all the files in the Repo. For now this is returned in memory.
+pub fn repo_list(py: Python, password: String) -> PyResult<PyList> {
+    let repo = EncryptedRepo::build(&password_source(password)).map_err(|e| as_py_err(py, e))?;
 
     let mut entries: Vec<PyObject> = Vec::new();
     for entry in repo
-        .list(&PageContent::collection_name().into())
+        .entries(&PageContent::collection_name().into())
         .map_err(|e| as_py_err(py, e))?
     {
         let decrypted = entry.map_err(|e| as_py_err(py, e))?;
-        let page_content = bincode::deserialize::<PageContent>(decrypted.as_slice())
-            .map_err(|e| as_py_err(py, e))?;
+        let page_content = decode_page(decrypted.as_slice()).map_err(|e| as_py_err(py, e))?;
         let pyo = page_content_to_py(py, page_content)?;
         entries.push(pyo);
     }
@@ -29,6 +32,56 @@ pub fn repo_list(py: Python, password: String) -> PyResult<PyList> {
     Ok(out)
 }
 
+/// Iterate lazily over the entries of the page-content or action collection.
+pub fn repo_iter(py: Python, password: String, collection: String) -> PyResult<RepoIter> {
+    if collection != PageContent::collection_name() && collection != NewAction::collection_name()
+    {
+        return Err(PyErr::new::<PyString, _>(
+            py,
+            format!("unsupported collection {}", collection),
+        ));
+    }
+    let repo = EncryptedRepo::build(&password_source(password)).map_err(|e| as_py_err(py, e))?;
+    let collection = Collection(collection);
+    let ids = repo
+        .list(&collection)
+        .map_err(|e| as_py_err(py, e))?
+        .into_iter()
+        .map(|handle| handle.id)
+        .collect::<Vec<String>>();
+    RepoIter::create_instance(py, repo, collection, RefCell::new(ids))
+}
+
+py_class!(pub class RepoIter |py| {
+    data repo: EncryptedRepo;
+    data collection: Collection;
+    data remaining: RefCell<Vec<String>>;
+
+    def __iter__(&self) -> PyResult<RepoIter> {
+        Ok(self.clone_ref(py))
+    }
+
+    def __next__(&self) -> PyResult<Option<PyObject>> {
+        let id = match self.remaining(py).borrow_mut().pop() {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let content = self.repo(py)
+            .read(self.collection(py), &id)
+            .map_err(|e| as_py_err(py, e))?;
+        let collection = self.collection(py);
+        let entry = if collection.0 == NewAction::collection_name() {
+            let action = decode_action(&content).map_err(|e| as_py_err(py, e))?;
+            new_action_to_py(py, action)?
+        } else {
+            let page_content = decode_page(&content).map_err(|e| as_py_err(py, e))?;
+            page_content_to_py(py, page_content)?
+        };
+        entry.cast_as::<PyDict>(py)?.set_item(py, "id", id)?;
+        Ok(Some(entry))
+    }
+});
+
 /// Create a Python Object from a Page Content.
 fn page_content_to_py(py: Python, page_content: PageContent) -> PyResult<PyObject> {
     let one = PyDict::new(py);
@@ -37,3 +90,17 @@ fn page_content_to_py(py: Python, page_content: PageContent) -> PyResult<PyObjec
     one.set_item(py, "body", page_content.body)?;
     Ok(one.into_object())
 }
+
+/// Create a Python Object from an action saved in the repo.
+fn new_action_to_py(py: Python, action: NewAction) -> PyResult<PyObject> {
+    let one = PyDict::new(py);
+    one.set_item(py, "executed", action.executed)?;
+    one.set_item(py, "kind", action.kind)?;
+    one.set_item(py, "command", action.command)?;
+    one.set_item(py, "location", action.location)?;
+    one.set_item(py, "epic", action.epic)?;
+    one.set_item(py, "host", action.host)?;
+    one.set_item(py, "parent_id", action.parent_id)?;
+    one.set_item(py, "status_code", action.status_code)?;
+    Ok(one.into_object())
+}
@@ -0,0 +1,24 @@
+use cpython::{PyDict, PyList, PyObject, PyResult, Python, PythonObject};
+use error::as_py_err;
+use lib_goo::config::Settings;
+use lib_index::{Indexer, TantivyIndexer};
+
+/// Run a full text search, returns the total and the matching pages.
+pub fn search(py: Python, what: String) -> PyResult<PyDict> {
+    let settings = Settings::load().map_err(|e| as_py_err(py, e))?;
+    let indexer = TantivyIndexer::build(settings.index).map_err(|e| as_py_err(py, e))?;
+    let results = indexer.search(&what).map_err(|e| as_py_err(py, e))?;
+
+    let mut matches: Vec<PyObject> = Vec::with_capacity(results.matches.len());
+    for page_content in results.matches {
+        let one = PyDict::new(py);
+        one.set_item(py, "url", page_content.url)?;
+        one.set_item(py, "title", page_content.title)?;
+        one.set_item(py, "body", page_content.body)?;
+        matches.push(one.into_object());
+    }
+    let out = PyDict::new(py);
+    out.set_item(py, "total", results.total)?;
+    out.set_item(py, "matches", PyList::new(py, matches.as_slice()))?;
+    Ok(out)
+}
@@ -0,0 +1,39 @@
+use cpython::{PyDict, PyList, PyObject, PyResult, Python, PythonObject};
+use error::as_py_err;
+use lib_db::topics::TopicStore;
+
+/// Read the topics computed by the analyses, None when they were not computed yet.
+/// The `words` and `documents` lists are flat, to load directly in a data frame.
+pub fn topics(py: Python) -> PyResult<Option<PyDict>> {
+    let store = match TopicStore::load().map_err(|e| as_py_err(py, e))? {
+        Some(s) => s,
+        None => return Ok(None),
+    };
+
+    let mut words: Vec<PyObject> = Vec::new();
+    for (ndx, topic) in store.topics().iter().enumerate() {
+        for word in &topic.words {
+            let one = PyDict::new(py);
+            one.set_item(py, "topic", ndx)?;
+            one.set_item(py, "word", &word.w)?;
+            one.set_item(py, "weight", word.e)?;
+            words.push(one.into_object());
+        }
+    }
+
+    let mut documents: Vec<PyObject> = Vec::new();
+    for (url, relevant) in store.documents() {
+        for rel in relevant {
+            let one = PyDict::new(py);
+            one.set_item(py, "url", url)?;
+            one.set_item(py, "topic", rel.t)?;
+            one.set_item(py, "probability", rel.p)?;
+            documents.push(one.into_object());
+        }
+    }
+
+    let out = PyDict::new(py);
+    out.set_item(py, "words", PyList::new(py, words.as_slice()))?;
+    out.set_item(py, "documents", PyList::new(py, documents.as_slice()))?;
+    Ok(Some(out))
+}
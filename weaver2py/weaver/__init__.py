@@ -1,4 +1,16 @@
 import weaver2py
 
 def list(pwd):
-    return weaver2py.repo_list(pwd)
\ No newline at end of file
+    return weaver2py.repo_list(pwd)
+
+def iterate(pwd, collection="page-content"):
+    return weaver2py.repo_iter(pwd, collection)
+
+def actions(pwd, term=None, start=None, length=None, tag=None):
+    return weaver2py.actions(pwd, term, start, length, tag)
+
+def search(what):
+    return weaver2py.search(what)
+
+def topics():
+    return weaver2py.topics()